@@ -3,15 +3,15 @@
 extern crate scopeguard;
 use ash::version::{EntryV1_0, InstanceV1_0};
 use vk_sample_common::config;
+use vk_sample_common::debug;
+use vk_sample_common::error::{Error, ErrorContext};
 
-fn main() {
+fn main() -> Result<(), Error> {
     let config = config::Configs::new("get_instance");
+    let app_name = std::ffi::CString::new(config.prog_name.as_str())
+        .context("アプリケーションの名前が正しくない")?;
     let app_info = ash::vk::ApplicationInfo::builder()
-        .application_name(
-            std::ffi::CString::new(config.prog_name.as_str())
-                .unwrap()
-                .as_c_str(),
-        )
+        .application_name(app_name.as_c_str())
         .application_version(ash::vk::make_version(1, 0, 0))
         .engine_name(unsafe {
             std::ffi::CStr::from_ptr("sample_engine\0".as_ptr() as *const std::os::raw::c_char)
@@ -20,23 +20,34 @@ fn main() {
         .api_version(ash::vk::make_version(1, 1, 0))
         .build();
 
-    let entry = ash::Entry::new().unwrap();
+    let entry = ash::Entry::new().context("Vulkan をロードできない")?;
 
     let ext: [*const i8; 0] = [];
-    let layers = if config.validation {
-        vec!["VK_LAYER_LUNARG_standard_validation\0".as_ptr() as *const i8]
-    } else {
-        vec![]
-    };
+    //  検証レイヤーは利用できるものだけを指定する
+    let layer_names = debug::validation_layers(config.validation, debug::available_layers(&entry))
+        .iter()
+        .map(|layer| std::ffi::CString::new(*layer))
+        .collect::<Result<Vec<_>, _>>()
+        .context("レイヤーの名前が正しくない")?;
+    let layers = layer_names
+        .iter()
+        .map(|item| item.as_ptr())
+        .collect::<Vec<_>>();
 
     let create_info = ash::vk::InstanceCreateInfo::builder()
         .application_info(&app_info)
         .enabled_layer_names(&layers)
         .enabled_extension_names(&ext);
 
-    let instance = unsafe { entry.create_instance(&create_info, None).unwrap() };
+    let instance = unsafe {
+        entry
+            .create_instance(&create_info, None)
+            .context("インスタンスを作成できない")?
+    };
 
     defer! {
         unsafe { instance.destroy_instance(None); }
     }
+
+    Ok(())
 }
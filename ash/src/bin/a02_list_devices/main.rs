@@ -1,97 +1,76 @@
 //  02_list_devices
 #[macro_use(defer)]
 extern crate scopeguard;
-use ash::version::{EntryV1_0, InstanceV1_0};
-use vk_sample_common::config;
+use ash::version::InstanceV1_0;
+use vk_sample_common::error::{Error, ErrorContext};
+use vk_sample_common::{config, context, report};
 
-fn main() {
+fn main() -> Result<(), Error> {
     let config = config::Configs::new("list_devices");
-    let app_info = ash::vk::ApplicationInfo::builder()
-        .application_name(
-            std::ffi::CString::new(config.prog_name.as_str())
-                .unwrap()
-                .as_c_str(),
-        )
-        .application_version(ash::vk::make_version(1, 0, 0))
-        .engine_name(unsafe {
-            std::ffi::CStr::from_ptr("sample_engine\0".as_ptr() as *const std::os::raw::c_char)
-        })
-        .engine_version(ash::vk::make_version(1, 0, 0))
-        .api_version(ash::vk::make_version(1, 1, 0))
-        .build();
-
-    let entry = ash::Entry::new().unwrap();
 
-    let ext: [*const i8; 0] = [];
-    let layers = if config.validation {
-        vec!["VK_LAYER_LUNARG_standard_validation\0".as_ptr() as *const i8]
+    //  サーフェスのフォーマットを調べるために見えないウィンドウを作る
+    //  ディスプレイが無い環境やヘッドレスの場合はサーフェスの項目を省く
+    let mut glfw = if config.headless {
+        None
     } else {
-        vec![]
+        glfw::init(glfw::LOG_ERRORS).ok()
+    };
+    let window = glfw.as_mut().and_then(|glfw| {
+        glfw.window_hint(glfw::WindowHint::Visible(false));
+        context::create_window(glfw, &config).ok()
+    });
+    let ext = match (glfw.as_ref(), window.as_ref()) {
+        (Some(glfw), Some(_)) => context::required_instance_extensions(glfw),
+        _ => vec![],
     };
 
-    let create_info = ash::vk::InstanceCreateInfo::builder()
-        .application_info(&app_info)
-        .enabled_layer_names(&layers)
-        .enabled_extension_names(&ext);
-
-    let instance = unsafe { entry.create_instance(&create_info, None).unwrap() };
+    let entry = ash::Entry::new().context("Vulkan をロードできない")?;
+    let instance = context::create_instance(&entry, &config, &ext)?;
 
     defer! {
         unsafe { instance.destroy_instance(None); }
     }
 
-    let devices = unsafe { instance.enumerate_physical_devices().unwrap() };
-    if devices.len() == 0 {
-        eprintln!("利用可能なデバイスがない");
-        return;
+    let surface_loader = ash::extensions::khr::Surface::new(&entry, &instance);
+    let surface = window
+        .as_ref()
+        .and_then(|(window, _)| context::create_surface(&instance, window).ok());
+
+    defer! {
+        if let Some(surface) = surface {
+            unsafe { surface_loader.destroy_surface(surface, None); }
+        }
     }
-    for device in devices {
-        let props = unsafe { instance.get_physical_device_properties(device) };
-        println!(
-            "{}({})",
-            unsafe { std::ffi::CStr::from_ptr(props.device_name.as_ptr()) }
-                .to_str()
-                .unwrap(),
-            match props.device_type {
-                ash::vk::PhysicalDeviceType::INTEGRATED_GPU => "統合GPU",
-                ash::vk::PhysicalDeviceType::DISCRETE_GPU => "ディスクリートGPU",
-                ash::vk::PhysicalDeviceType::VIRTUAL_GPU => "仮想GPU",
-                ash::vk::PhysicalDeviceType::CPU => "CPU",
-                _ => "その他のデバイス",
-            }
-        );
 
-        let version = |ver: u32| {
-            format!(
-                "{}.{}.{}",
-                ash::vk::version_major(ver),
-                ash::vk::version_minor(ver),
-                ash::vk::version_patch(ver)
+    let devices = unsafe {
+        instance
+            .enumerate_physical_devices()
+            .context("デバイスを列挙できない")?
+    };
+    if devices.len() == 0 {
+        return Err(Error::from("利用可能なデバイスがない"));
+    }
+    let reports = devices
+        .iter()
+        .map(|device| {
+            report::device_report(
+                &instance,
+                *device,
+                surface.map(|surface| (&surface_loader, surface)),
             )
-        };
-
-        println!("  APIバージョン");
-        println!("    {}", version(props.api_version));
-        println!("  ドライババージョン");
-        println!("    {}", version(props.driver_version));
-        println!("  ベンダーID");
-        println!("    {}", props.vendor_id);
-        println!("  デバイスID");
-        println!("    {}", props.device_id);
+        })
+        .collect::<Vec<_>>();
 
-        let avail_dext = unsafe {
-            instance
-                .enumerate_device_extension_properties(device)
-                .unwrap()
-        };
-        println!("  利用可能な拡張");
-        for ext in avail_dext.iter() {
-            println!(
-                "    {}",
-                unsafe { std::ffi::CStr::from_ptr(ext.extension_name.as_ptr()) }
-                    .to_str()
-                    .unwrap()
-            );
+    match config.format {
+        config::OutputFormat::Json => {
+            println!("{:#}", serde_json::json!({ "devices": reports }))
+        }
+        config::OutputFormat::Text => {
+            for device in reports.iter() {
+                report::print_text(device);
+            }
         }
     }
+
+    Ok(())
 }
@@ -1,120 +1,37 @@
 //  04_create_window
 #[macro_use(defer)]
 extern crate scopeguard;
-use ash::version::{EntryV1_0, InstanceV1_0};
+use ash::version::InstanceV1_0;
 use ash::vk::Handle;
-use vk_sample_common::config;
+use vk_sample_common::error::{Error, ErrorContext};
+use vk_sample_common::{config, context, selection};
 
-fn main() {
-    let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
+fn main() -> Result<(), Error> {
+    let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).context("GLFW を初期化できない")?;
 
     let config = config::Configs::new("create_window");
-    let app_info = ash::vk::ApplicationInfo::builder()
-        .application_name(
-            std::ffi::CString::new(config.prog_name.as_str())
-                .unwrap()
-                .as_c_str(),
-        )
-        .application_version(ash::vk::make_version(1, 0, 0))
-        .engine_name(unsafe {
-            std::ffi::CStr::from_ptr("sample_engine\0".as_ptr() as *const std::os::raw::c_char)
-        })
-        .engine_version(ash::vk::make_version(1, 0, 0))
-        .api_version(ash::vk::make_version(1, 1, 0))
-        .build();
-
-    let entry = ash::Entry::new().unwrap();
-
-    let ext = glfw
-        .get_required_instance_extensions()
-        .unwrap()
-        .iter()
-        .map(|item| std::ffi::CString::new(item.as_str()).unwrap())
-        .collect::<Vec<_>>();
-    let ext_raw = &ext.iter().map(|item| item.as_ptr()).collect::<Vec<_>>();
-    let layers = if config.validation {
-        vec!["VK_LAYER_LUNARG_standard_validation\0".as_ptr() as *const i8]
-    } else {
-        vec![]
-    };
-
-    let create_info = ash::vk::InstanceCreateInfo::builder()
-        .application_info(&app_info)
-        .enabled_layer_names(&layers)
-        .enabled_extension_names(ext_raw.as_slice());
-
-    let instance = unsafe { entry.create_instance(&create_info, None).unwrap() };
+    let entry = ash::Entry::new().context("Vulkan をロードできない")?;
+    let ext = context::required_instance_extensions(&glfw);
+    let instance = context::create_instance(&entry, &config, &ext)?;
 
     defer! {
         unsafe { instance.destroy_instance(None); }
     }
 
-    let devices = unsafe { instance.enumerate_physical_devices().unwrap() };
-    if devices.len() == 0 {
-        eprintln!("利用可能なデバイスがない");
-        return;
-    }
-
-    let dext = [std::ptr::null::<i8>(); 0];
-    let validated_devices = devices
-        .into_iter()
-        .filter(|device| {
-            if dext.len() > 0 {
-                let avail_dext = unsafe {
-                    instance
-                        .enumerate_device_extension_properties(*device)
-                        .unwrap()
-                };
-                if dext
-                    .iter()
-                    .find(|w| {
-                        avail_dext
-                            .iter()
-                            .find(|v| unsafe {
-                                std::ffi::CStr::from_ptr(v.extension_name.as_ptr())
-                                    == std::ffi::CStr::from_ptr(**w)
-                            })
-                            .is_some()
-                    })
-                    .is_none()
-                {
-                    return false;
-                }
-            }
-
-            let queue_props =
-                unsafe { instance.get_physical_device_queue_family_properties(*device) };
-            for i in 0..queue_props.len() {
-                if glfw.get_physical_device_presentation_support_raw(
-                    instance.handle().as_raw() as vk_sys::Instance,
-                    device.as_raw() as vk_sys::PhysicalDevice,
-                    i as u32,
-                ) {
-                    return true;
-                }
-            }
-            false
-        })
-        .collect::<Vec<_>>();
-
-    if validated_devices.len() == 0 {
-        eprintln!("必要な拡張とレイヤーを備えたデバイスがない");
-        return;
-    }
-
-    println!("利用可能なデバイス");
-    for i in 0..validated_devices.len() {
-        println!("{}: {}", i, unsafe {
-            std::ffi::CStr::from_ptr(
-                instance
-                    .get_physical_device_properties(validated_devices[i])
-                    .device_name
-                    .as_ptr(),
+    //  GLFW のウィンドウに表示できるデバイスを候補にする
+    let candidates = {
+        let presentable = |device: ash::vk::PhysicalDevice, i| {
+            glfw.get_physical_device_presentation_support_raw(
+                instance.handle().as_raw() as vk_sys::Instance,
+                device.as_raw() as vk_sys::PhysicalDevice,
+                i,
             )
-            .to_str()
-            .unwrap()
-        })
-    }
+        };
+        let requirements = selection::Requirements::new(&[]);
+        selection::evaluate_devices(&instance, &requirements, Some(&presentable))?
+    };
+
+    selection::print_candidates(&candidates);
 
     glfw.window_hint(glfw::WindowHint::ClientApi(glfw::ClientApiHint::NoApi));
 
@@ -133,17 +50,19 @@ fn main() {
                 },
             )
         })
-        .expect("ウィンドウを作成できない");
+        .context("ウィンドウを作成できない")?;
 
     let mut raw_surface: vk_sys::SurfaceKHR = 0;
-    if window.create_window_surface(
+    let result = window.create_window_surface(
         instance.handle().as_raw() as vk_sys::Instance,
         std::ptr::null(),
         &mut raw_surface,
-    ) != 0
-    {
-        eprintln!("サーフェスを作成できない");
-        return;
+    );
+    if result != 0 {
+        return Err(Error::Vulkan {
+            context: String::from("サーフェスを作成できない"),
+            result: ash::vk::Result::from_raw(result as i32),
+        });
     }
 
     let surface_loader = ash::extensions::khr::Surface::new(&entry, &instance);
@@ -152,4 +71,6 @@ fn main() {
     defer! {
          unsafe { surface_loader.destroy_surface(surface, None); }
     }
+
+    Ok(())
 }
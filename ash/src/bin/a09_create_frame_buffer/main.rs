@@ -1,232 +1,31 @@
 //  09_create_frame_buffer
 #[macro_use(defer)]
 extern crate scopeguard;
-use ash::version::{DeviceV1_0, EntryV1_0, InstanceV1_0};
+use ash::version::DeviceV1_0;
 use ash::vk::Handle;
 use vk_sample_common::config;
+use vk_sample_common::context::{create_window, VulkanContext};
+use vk_sample_common::error::{Error, ErrorContext};
 
 #[allow(unused_variables)]
-fn main() {
-    let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
+fn main() -> Result<(), Error> {
+    let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).context("GLFW を初期化できない")?;
 
     let config = config::Configs::new("create_frame_buffer");
-    let app_info = ash::vk::ApplicationInfo::builder()
-        .application_name(
-            std::ffi::CString::new(config.prog_name.as_str())
-                .unwrap()
-                .as_c_str(),
-        )
-        .application_version(ash::vk::make_version(1, 0, 0))
-        .engine_name(unsafe {
-            std::ffi::CStr::from_ptr("sample_engine\0".as_ptr() as *const std::os::raw::c_char)
-        })
-        .engine_version(ash::vk::make_version(1, 0, 0))
-        .api_version(ash::vk::make_version(1, 1, 0))
-        .build();
-
-    let entry = ash::Entry::new().unwrap();
-
-    let ext = glfw
-        .get_required_instance_extensions()
-        .unwrap()
-        .iter()
-        .map(|item| std::ffi::CString::new(item.as_str()).unwrap())
-        .collect::<Vec<_>>();
-    let ext_raw = &ext.iter().map(|item| item.as_ptr()).collect::<Vec<_>>();
-    let layers = if config.validation {
-        vec!["VK_LAYER_LUNARG_standard_validation\0".as_ptr() as *const i8]
-    } else {
-        vec![]
-    };
-
-    let create_info = ash::vk::InstanceCreateInfo::builder()
-        .application_info(&app_info)
-        .enabled_layer_names(&layers)
-        .enabled_extension_names(ext_raw.as_slice());
-
-    let instance = unsafe { entry.create_instance(&create_info, None).unwrap() };
-
-    defer! {
-        unsafe { instance.destroy_instance(None); }
-    }
-
-    let devices = unsafe { instance.enumerate_physical_devices().unwrap() };
-    if devices.len() == 0 {
-        eprintln!("利用可能なデバイスがない");
-        return;
-    }
-
-    let dext = [ash::extensions::khr::Swapchain::name().as_ptr()];
-    let validated_devices = devices
-        .iter()
-        .filter(|device| {
-            if dext.len() > 0 {
-                let avail_dext = unsafe {
-                    instance
-                        .enumerate_device_extension_properties(**device)
-                        .unwrap()
-                };
-                if dext
-                    .iter()
-                    .find(|w| {
-                        avail_dext
-                            .iter()
-                            .find(|v| unsafe {
-                                std::ffi::CStr::from_ptr(v.extension_name.as_ptr())
-                                    == std::ffi::CStr::from_ptr(**w)
-                            })
-                            .is_some()
-                    })
-                    .is_none()
-                {
-                    return false;
-                }
-            }
-
-            let queue_props =
-                unsafe { instance.get_physical_device_queue_family_properties(**device) };
-            for i in 0..queue_props.len() {
-                if glfw.get_physical_device_presentation_support_raw(
-                    instance.handle().as_raw() as vk_sys::Instance,
-                    device.as_raw() as vk_sys::PhysicalDevice,
-                    i as u32,
-                ) {
-                    return true;
-                }
-            }
-            false
-        })
-        .collect::<Vec<_>>();
-
-    if validated_devices.len() == 0 {
-        eprintln!("必要な拡張とレイヤーを備えたデバイスがない");
-        return;
-    }
-
-    println!("利用可能なデバイス");
-    for i in 0..validated_devices.len() {
-        println!("{}: {}", i, unsafe {
-            std::ffi::CStr::from_ptr(
-                instance
-                    .get_physical_device_properties(*validated_devices[i])
-                    .device_name
-                    .as_ptr(),
-            )
-            .to_str()
-            .unwrap()
-        })
-    }
-
-    glfw.window_hint(glfw::WindowHint::ClientApi(glfw::ClientApiHint::NoApi));
-
-    let (window, _) = glfw
-        .with_primary_monitor(|glfw, m| {
-            glfw.create_window(
-                config.width,
-                config.height,
-                config.prog_name.as_str(),
-                if config.fullscreen {
-                    m.map_or(glfw::WindowMode::Windowed, |m| {
-                        glfw::WindowMode::FullScreen(m)
-                    })
-                } else {
-                    glfw::WindowMode::Windowed
-                },
-            )
-        })
-        .expect("ウィンドウを作成できない");
-
-    let mut raw_surface: vk_sys::SurfaceKHR = 0;
-    if window.create_window_surface(
-        instance.handle().as_raw() as vk_sys::Instance,
-        std::ptr::null(),
-        &mut raw_surface,
-    ) != 0
-    {
-        eprintln!("サーフェスを作成できない");
-        return;
-    }
-
-    let surface_loader = ash::extensions::khr::Surface::new(&entry, &instance);
-    let surface = ash::vk::SurfaceKHR::from_raw(raw_surface);
-
-    defer! {
-         unsafe { surface_loader.destroy_surface(surface, None); }
-    }
-
-    if config.device_index as usize >= devices.len() {
-        eprintln!("{}番目のデバイスは存在しない", config.device_index);
-        return;
-    }
-
-    let physical_device = validated_devices[config.device_index as usize];
-    let queue_props =
-        unsafe { instance.get_physical_device_queue_family_properties(*physical_device) };
-
-    let supported = (0..queue_props.len())
-        .map(|i| unsafe {
-            surface_loader
-                .get_physical_device_surface_support(*physical_device, i as u32, surface)
-                .unwrap()
-        })
-        .collect::<Vec<_>>();
-    let graphics_queue_index = (0..queue_props.len()).find(|i| {
-        queue_props[*i]
-            .queue_flags
-            .intersects(ash::vk::QueueFlags::GRAPHICS)
-    });
-    let present_queue_index = match graphics_queue_index {
-        Some(t) => Some(t),
-        None => (0..supported.len()).find(|i| supported[*i]),
-    };
-    if graphics_queue_index.is_none() || present_queue_index.is_none() {
-        eprintln!("必要なキューが備わっていない");
-        return;
-    }
-    let graphics_queue_index = graphics_queue_index.unwrap() as u32;
-    let present_queue_index = present_queue_index.unwrap() as u32;
-    let eq_queue = graphics_queue_index == present_queue_index;
-
-    let builder = |index| {
-        let priority = [0.0];
-        ash::vk::DeviceQueueCreateInfo::builder()
-            .queue_family_index(index as u32)
-            .queue_priorities(&priority)
-            .build()
-    };
-    let queues = if eq_queue {
-        vec![builder(graphics_queue_index)]
-    } else {
-        vec![builder(graphics_queue_index), builder(present_queue_index)]
-    };
+    let (window, _) = create_window(&mut glfw, &config)?;
 
-    let features = unsafe { instance.get_physical_device_features(*physical_device) };
-    let device = unsafe {
-        instance
-            .create_device(
-                *physical_device,
-                &ash::vk::DeviceCreateInfo::builder()
-                    .queue_create_infos(&queues)
-                    .enabled_extension_names(&dext)
-                    .enabled_features(&features)
-                    .build(),
-                None,
-            )
-            .unwrap()
-    };
-
-    defer! {
-        unsafe { device.destroy_device(None); }
-    }
+    let dext = [ash::extensions::khr::Swapchain::name()];
+    let context = VulkanContext::new(&glfw, &window, &config, &dext)?;
+    let device = &context.device;
 
     let formats = unsafe {
-        surface_loader
-            .get_physical_device_surface_formats(*physical_device, surface)
-            .unwrap()
+        context
+            .surface_loader
+            .get_physical_device_surface_formats(context.physical_device, context.surface)
+            .context("サーフェスのフォーマットを取得できない")?
     };
     if formats.len() == 0 {
-        eprintln!("利用可能なピクセルフォーマットが無い");
-        return;
+        return Err(Error::from("利用可能なピクセルフォーマットが無い"));
     }
     let format = match formats
         .iter()
@@ -237,12 +36,13 @@ fn main() {
             .iter()
             .find(|f| f.format == ash::vk::Format::R8G8B8A8_UNORM),
     }
-    .expect("利用可能なピクセルフォーマットが無い");
+    .context("利用可能なピクセルフォーマットが無い")?;
 
     let surface_capabilities = unsafe {
-        surface_loader
-            .get_physical_device_surface_capabilities(*physical_device, surface)
-            .unwrap()
+        context
+            .surface_loader
+            .get_physical_device_surface_capabilities(context.physical_device, context.surface)
+            .context("サーフェスの能力を取得できない")?
     };
     let swapchain_extent = if surface_capabilities.current_extent.width == -1i32 as u32 {
         ash::vk::Extent2D::builder()
@@ -257,12 +57,12 @@ fn main() {
         surface_capabilities.max_image_count,
     );
 
-    let swapchain_loader = ash::extensions::khr::Swapchain::new(&instance, &device);
+    let swapchain_loader = &context.swapchain_loader;
     let swapchain = unsafe {
         swapchain_loader
             .create_swapchain(
                 &ash::vk::SwapchainCreateInfoKHR::builder()
-                    .surface(surface)
+                    .surface(context.surface)
                     .min_image_count(swapchain_image_count)
                     .image_format(format.format)
                     .image_color_space(format.color_space)
@@ -286,7 +86,7 @@ fn main() {
                     .build(),
                 None,
             )
-            .unwrap()
+            .context("スワップチェーンを作成できない")?
     };
 
     defer! { unsafe { swapchain_loader.destroy_swapchain(swapchain, None); } }
@@ -336,23 +136,27 @@ fn main() {
                     .build(),
                 None,
             )
-            .unwrap()
+            .context("レンダーパスを作成できない")?
     };
 
     defer! { unsafe { device.destroy_render_pass(render_pass, None); }}
 
     let allocator_info = vk_mem::AllocatorCreateInfo {
-        physical_device: *physical_device,
+        physical_device: context.physical_device,
         device: device.clone(),
-        instance: instance.clone(),
+        instance: context.instance.clone(),
         ..vk_mem::AllocatorCreateInfo::default()
     };
-    let allocator = vk_mem::Allocator::new(&allocator_info).expect("アロケータを作成できない");
+    let allocator = vk_mem::Allocator::new(&allocator_info).context("アロケータを作成できない")?;
 
     let mut framebuffers = Vec::<FrameBuffer>::new();
-    for swapchain_image in unsafe { swapchain_loader.get_swapchain_images(swapchain).unwrap() } {
+    for swapchain_image in unsafe {
+        swapchain_loader
+            .get_swapchain_images(swapchain)
+            .context("スワップチェーンのイメージを取得できない")?
+    } {
         let mut attachments_raw = Vec::<ash::vk::ImageView>::new();
-        let mut framebuffer = FrameBuffer::new(&device, &allocator);
+        let mut framebuffer = FrameBuffer::new(device, &allocator);
         framebuffer.color_image_attachment = unsafe {
             device
                 .create_image_view(
@@ -363,7 +167,7 @@ fn main() {
                         .build(),
                     None,
                 )
-                .unwrap()
+                .context("イメージビューを作成できない")?
         };
         attachments_raw.push(framebuffer.color_image_attachment);
 
@@ -389,7 +193,7 @@ fn main() {
         };
         let (depth_image, depth_image_allocation, depth_image_alloc_info) = allocator
             .create_image(&depth_image_create_info, &depth_image_alloc_info)
-            .expect("イメージを作成できない");
+            .context("イメージを作成できない")?;
         framebuffer.depth_image = depth_image;
         framebuffer.depth_image_allocation = depth_image_allocation;
         framebuffer.depth_image_attachment = unsafe {
@@ -402,7 +206,7 @@ fn main() {
                         .build(),
                     None,
                 )
-                .unwrap()
+                .context("イメージビューを作成できない")?
         };
         attachments_raw.push(framebuffer.depth_image_attachment);
 
@@ -418,11 +222,13 @@ fn main() {
                         .build(),
                     None,
                 )
-                .unwrap()
+                .context("フレームバッファを作成できない")?
         };
 
         framebuffers.push(framebuffer);
     }
+
+    Ok(())
 }
 
 struct FrameBuffer<'a> {
@@ -470,9 +276,13 @@ impl Drop for FrameBuffer<'_> {
         }
 
         if self.depth_image.as_raw() != 0 {
-            self.allocator
+            //  Drop では返せないので表示だけする
+            if let Err(e) = self
+                .allocator
                 .destroy_image(self.depth_image, &self.depth_image_allocation)
-                .unwrap();
+            {
+                eprintln!("デプスイメージを破棄できない: {}", e);
+            }
         }
     }
 }
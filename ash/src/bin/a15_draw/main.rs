@@ -0,0 +1,1495 @@
+//  15_draw
+use ash::version::DeviceV1_0;
+use std::io::Read;
+use vk_sample_common::camera::CameraController;
+use vk_sample_common::config;
+use vk_sample_common::context::{create_window, VulkanContext};
+use vk_sample_common::debug::DebugNames;
+use vk_sample_common::error::{Error, ErrorContext};
+use vk_sample_common::memory_report;
+use vk_sample_common::resource;
+use vk_sample_common::screenshot;
+use vk_sample_common::selection;
+use vk_sample_common::upload::Uploader;
+
+#[allow(unused_variables)]
+fn main() -> Result<(), Error> {
+    let config = config::Configs::new("draw");
+
+    //  ディスプレイが無い環境では GLFW を初期化できないので、ヘッドレスの場合は使わない
+    let mut glfw = if config.headless {
+        None
+    } else {
+        Some(glfw::init(glfw::FAIL_ON_ERRORS).context("GLFW を初期化できない")?)
+    };
+    let (mut window, events) = match glfw.as_mut() {
+        Some(glfw) => {
+            let (window, events) = create_window(glfw, &config)?;
+            (Some(window), Some(events))
+        }
+        None => (None, None),
+    };
+    //  大きさが変わったらスワップチェーンを作り直す
+    //  キーボードとマウスの入力でカメラを動かす
+    if let Some(window) = window.as_mut() {
+        window.set_framebuffer_size_polling(true);
+        window.set_key_polling(true);
+        window.set_mouse_button_polling(true);
+        window.set_cursor_pos_polling(true);
+        window.set_scroll_polling(true);
+    }
+
+    //  デプスバッファに D16_UNORM を、ヘッドレスの場合はカラーバッファに R8G8B8A8_UNORM を使う
+    let mut requirements = if config.headless {
+        selection::Requirements::new(&[])
+    } else {
+        selection::Requirements::new(&[ash::extensions::khr::Swapchain::name()])
+    };
+    requirements.formats.push(selection::FormatRequirement {
+        format: ash::vk::Format::D16_UNORM,
+        tiling: ash::vk::ImageTiling::OPTIMAL,
+        features: ash::vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
+    });
+    if config.headless {
+        requirements.formats.push(selection::FormatRequirement {
+            format: ash::vk::Format::R8G8B8A8_UNORM,
+            tiling: ash::vk::ImageTiling::OPTIMAL,
+            features: ash::vk::FormatFeatureFlags::COLOR_ATTACHMENT
+                | ash::vk::FormatFeatureFlags::TRANSFER_SRC,
+        });
+    }
+    let context = VulkanContext::with_requirements(
+        glfw.as_ref().zip(window.as_ref()),
+        &config,
+        &requirements,
+    )?;
+    let device = &context.device;
+    //  --debug を指定した場合はオブジェクトに名前を付ける
+    let debug_names = &context.debug_names;
+
+    let graphics_command_pool = resource::CommandPool::new(device, unsafe {
+        device
+            .create_command_pool(
+                &ash::vk::CommandPoolCreateInfo::builder()
+                    .queue_family_index(context.graphics_queue_index)
+                    .flags(ash::vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
+                    .build(),
+                None,
+            )
+            .context("コマンドプールを作成できない")?
+    });
+    debug_names.name_object(*graphics_command_pool, "graphics command pool");
+
+    //  グラフィクスキューと表示キューが異なる場合は表示キュー用のコマンドプールも必要
+    let eq_queue = context.graphics_queue_index == context.present_queue_index;
+    let separate_present_command_pool = if eq_queue {
+        None
+    } else {
+        Some(resource::CommandPool::new(device, unsafe {
+            device
+                .create_command_pool(
+                    &ash::vk::CommandPoolCreateInfo::builder()
+                        .queue_family_index(context.present_queue_index)
+                        .flags(ash::vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
+                        .build(),
+                    None,
+                )
+                .context("コマンドプールを作成できない")?
+        }))
+    };
+    if let Some(pool) = separate_present_command_pool.as_ref() {
+        debug_names.name_object(**pool, "present command pool");
+    }
+    let present_command_pool = separate_present_command_pool
+        .as_ref()
+        .map_or(*graphics_command_pool, |pool| **pool);
+
+    //  06_create_swapchain
+    //  ヘッドレスの場合はスワップチェーンの代わりにオフスクリーンのイメージへ描画する
+    let swapchain_loader = &context.swapchain_loader;
+    let (format, image_usage) = if config.headless {
+        (
+            ash::vk::SurfaceFormatKHR {
+                format: ash::vk::Format::R8G8B8A8_UNORM,
+                color_space: ash::vk::ColorSpaceKHR::SRGB_NONLINEAR,
+            },
+            ash::vk::ImageUsageFlags::COLOR_ATTACHMENT | ash::vk::ImageUsageFlags::TRANSFER_SRC,
+        )
+    } else {
+        let formats = unsafe {
+            context
+                .surface_loader
+                .get_physical_device_surface_formats(context.physical_device, context.surface)
+                .context("サーフェスのフォーマットを取得できない")?
+        };
+        if formats.len() == 0 {
+            return Err(Error::from("利用可能なピクセルフォーマットが無い"));
+        }
+        let format = match formats
+            .iter()
+            .find(|f| f.format == ash::vk::Format::B8G8R8A8_UNORM)
+        {
+            Some(t) => Some(t),
+            None => formats
+                .iter()
+                .find(|f| f.format == ash::vk::Format::R8G8B8A8_UNORM),
+        }
+        .context("利用可能なピクセルフォーマットが無い")?
+        .clone();
+
+        let surface_capabilities = unsafe {
+            context
+                .surface_loader
+                .get_physical_device_surface_capabilities(context.physical_device, context.surface)
+                .context("サーフェスの能力を取得できない")?
+        };
+        //  スクリーンショットを撮る場合はスワップチェーンのイメージからコピーできる必要がある
+        let image_usage = if config.screenshot.is_some() {
+            ash::vk::ImageUsageFlags::COLOR_ATTACHMENT | ash::vk::ImageUsageFlags::TRANSFER_SRC
+        } else {
+            ash::vk::ImageUsageFlags::COLOR_ATTACHMENT
+        };
+        if !surface_capabilities
+            .supported_usage_flags
+            .contains(image_usage)
+        {
+            return Err(Error::from(
+                "スワップチェーンのイメージをコピー元にできない",
+            ));
+        }
+        (format, image_usage)
+    };
+
+    let initial_extent = ash::vk::Extent2D {
+        width: config.width,
+        height: config.height,
+    };
+    let mut swapchain = if config.headless {
+        Swapchain::headless(initial_extent)
+    } else {
+        Swapchain::new(
+            &context,
+            format,
+            image_usage,
+            initial_extent,
+            ash::vk::SwapchainKHR::null(),
+        )
+        .context("スワップチェーンを作成できない")?
+    };
+    let swapchain_image_count = swapchain.image_count();
+
+    //  07_create_descriptor_set
+    let max_descriptor_set_count = 20_u32;
+    let descriptor_pool_size = [ash::vk::DescriptorPoolSize::builder()
+        .ty(ash::vk::DescriptorType::UNIFORM_BUFFER)
+        .descriptor_count(1)
+        .build()];
+    let descriptor_pool = resource::DescriptorPool::new(device, unsafe {
+        device
+            .create_descriptor_pool(
+                &ash::vk::DescriptorPoolCreateInfo::builder()
+                    .pool_sizes(descriptor_pool_size.as_ref())
+                    .max_sets(max_descriptor_set_count)
+                    .flags(ash::vk::DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET)
+                    .build(),
+                None,
+            )
+            .context("デスクリプタプールを作成できない")?
+    });
+    debug_names.name_object(*descriptor_pool, "descriptor pool");
+
+    let descriptor_set_layout_bindings = [ash::vk::DescriptorSetLayoutBinding::builder()
+        .descriptor_type(ash::vk::DescriptorType::UNIFORM_BUFFER)
+        .descriptor_count(1)
+        .binding(0)
+        .stage_flags(ash::vk::ShaderStageFlags::VERTEX)
+        .build()];
+    let mut descriptor_set_layouts =
+        Vec::<resource::DescriptorSetLayout>::with_capacity(swapchain_image_count as usize);
+    for i in 0..swapchain_image_count {
+        descriptor_set_layouts.push(resource::DescriptorSetLayout::new(device, unsafe {
+            device
+                .create_descriptor_set_layout(
+                    &ash::vk::DescriptorSetLayoutCreateInfo::builder().build(),
+                    None,
+                )
+                .context("デスクリプタセットレイアウトを作成できない")?
+        }));
+    }
+    let descriptor_set_layout = descriptor_set_layouts
+        .iter()
+        .map(|layout| **layout)
+        .collect::<Vec<_>>();
+
+    //  デスクリプタセットはデスクリプタプールを破棄する時に一緒に解放される
+    let descriptor_set = unsafe {
+        device
+            .allocate_descriptor_sets(
+                &ash::vk::DescriptorSetAllocateInfo::builder()
+                    .descriptor_pool(*descriptor_pool)
+                    .set_layouts(descriptor_set_layout.as_slice())
+                    .build(),
+            )
+            .context("デスクリプタセットを確保できない")?
+    };
+
+    //  08_create_render_pass
+    let attachments = [
+        ash::vk::AttachmentDescription::builder()
+            .format(format.format)
+            .samples(ash::vk::SampleCountFlags::TYPE_1)
+            .load_op(ash::vk::AttachmentLoadOp::CLEAR)
+            .store_op(ash::vk::AttachmentStoreOp::STORE)
+            .stencil_load_op(ash::vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(ash::vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(ash::vk::ImageLayout::UNDEFINED)
+            .final_layout(if config.headless {
+                ash::vk::ImageLayout::TRANSFER_SRC_OPTIMAL
+            } else {
+                ash::vk::ImageLayout::PRESENT_SRC_KHR
+            })
+            .build(),
+        ash::vk::AttachmentDescription::builder()
+            .format(ash::vk::Format::D16_UNORM)
+            .samples(ash::vk::SampleCountFlags::TYPE_1)
+            .load_op(ash::vk::AttachmentLoadOp::CLEAR)
+            .store_op(ash::vk::AttachmentStoreOp::DONT_CARE)
+            .stencil_load_op(ash::vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(ash::vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(ash::vk::ImageLayout::UNDEFINED)
+            .final_layout(ash::vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+            .build(),
+    ];
+    let color_reference = [ash::vk::AttachmentReference::builder()
+        .attachment(0)
+        .layout(ash::vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+        .build()];
+    let depth_reference = ash::vk::AttachmentReference::builder()
+        .attachment(1)
+        .layout(ash::vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+        .build();
+    let subpass = [ash::vk::SubpassDescription::builder()
+        .pipeline_bind_point(ash::vk::PipelineBindPoint::GRAPHICS)
+        .color_attachments(&color_reference)
+        .depth_stencil_attachment(&depth_reference)
+        .build()];
+    //  スワップチェーンのイメージを取得し終えるまでカラーアタッチメントへの書き込みを待たせる
+    let dependencies = [ash::vk::SubpassDependency::builder()
+        .src_subpass(ash::vk::SUBPASS_EXTERNAL)
+        .dst_subpass(0)
+        .src_stage_mask(ash::vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+        .dst_stage_mask(ash::vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+        .dst_access_mask(ash::vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+        .build()];
+
+    let render_pass = resource::RenderPass::new(device, unsafe {
+        device
+            .create_render_pass(
+                &ash::vk::RenderPassCreateInfo::builder()
+                    .attachments(&attachments)
+                    .subpasses(&subpass)
+                    .dependencies(&dependencies)
+                    .build(),
+                None,
+            )
+            .context("レンダーパスを作成できない")?
+    });
+    debug_names.name_object(*render_pass, "render pass");
+
+    //  09_create_frame_buffer
+    let allocator_info = vk_mem::AllocatorCreateInfo {
+        physical_device: context.physical_device,
+        device: device.clone(),
+        instance: context.instance.clone(),
+        ..vk_mem::AllocatorCreateInfo::default()
+    };
+    let allocator = vk_mem::Allocator::new(&allocator_info).context("アロケータを作成できない")?;
+
+    let mut framebuffers = create_framebuffers(
+        device,
+        &allocator,
+        debug_names,
+        *render_pass,
+        format.format,
+        &swapchain,
+    )?;
+
+    //  10_create_shader_module
+    let vertex_shader_file_path: std::path::PathBuf =
+        [config.shader_dir.as_str(), "simple.vert.spv"]
+            .iter()
+            .collect();
+    let mut vertex_shader_file =
+        std::fs::File::open(vertex_shader_file_path).context("頂点シェーダを読む事ができない")?;
+    let mut vertex_shader_bin = Vec::<u8>::new();
+    vertex_shader_file
+        .read_to_end(&mut vertex_shader_bin)
+        .context("頂点シェーダを読む事ができない")?;
+    //let vertex_shader_bin = vulkan_samples_2019_rust_ash::to_vec_u32(vertex_shader_bin.as_slice());
+    let vertex_shader_module = resource::ShaderModule::new(device, unsafe {
+        device
+            .create_shader_module(
+                &ash::vk::ShaderModuleCreateInfo::builder()
+                    .code(vk_sample_common::from_slice(&vertex_shader_bin.as_slice()))
+                    .build(),
+                None,
+            )
+            .context("シェーダモジュールを作成できない")?
+    });
+    debug_names.name_object(*vertex_shader_module, "simple.vert");
+
+    let fragment_shader_file_path: std::path::PathBuf =
+        [config.shader_dir.as_str(), "simple.frag.spv"]
+            .iter()
+            .collect();
+    let mut fragment_shader_file = std::fs::File::open(fragment_shader_file_path)
+        .context("フラグメントシェーダを読む事ができない")?;
+    let mut fragment_shader_bin = Vec::<u8>::new();
+    fragment_shader_file
+        .read_to_end(&mut fragment_shader_bin)
+        .context("フラグメントシェーダを読む事ができない")?;
+    let fragment_shader_module = resource::ShaderModule::new(device, unsafe {
+        device
+            .create_shader_module(
+                &ash::vk::ShaderModuleCreateInfo::builder()
+                    .code(vk_sample_common::from_slice(
+                        &fragment_shader_bin.as_slice(),
+                    ))
+                    .build(),
+                None,
+            )
+            .context("シェーダモジュールを作成できない")?
+    });
+    debug_names.name_object(*fragment_shader_module, "simple.frag");
+
+    //  11_create_pipeline
+    let pipeline_shader_stages = [
+        ash::vk::PipelineShaderStageCreateInfo::builder()
+            .stage(ash::vk::ShaderStageFlags::VERTEX)
+            .module(*vertex_shader_module)
+            .name(unsafe {
+                std::ffi::CStr::from_ptr("main\0".as_ptr() as *const std::os::raw::c_char)
+            })
+            .build(),
+        ash::vk::PipelineShaderStageCreateInfo::builder()
+            .stage(ash::vk::ShaderStageFlags::FRAGMENT)
+            .module(*fragment_shader_module)
+            .name(unsafe {
+                std::ffi::CStr::from_ptr("main\0".as_ptr() as *const std::os::raw::c_char)
+            })
+            .build(),
+    ];
+
+    let push_constant_range = [ash::vk::PushConstantRange::builder()
+        .stage_flags(ash::vk::ShaderStageFlags::VERTEX | ash::vk::ShaderStageFlags::FRAGMENT)
+        .offset(0)
+        .size(std::mem::size_of::<vk_sample_common::PushConstants>() as u32)
+        .build()];
+    let pipeline_layout = resource::PipelineLayout::new(device, unsafe {
+        device
+            .create_pipeline_layout(
+                &ash::vk::PipelineLayoutCreateInfo::builder()
+                    .set_layouts(descriptor_set_layout.as_slice())
+                    .push_constant_ranges(push_constant_range.as_ref())
+                    .build(),
+                None,
+            )
+            .context("パイプラインレイアウトを作成できない")?
+    });
+    debug_names.name_object(*pipeline_layout, "pipeline layout");
+
+    let vertex_input_binding = [ash::vk::VertexInputBindingDescription::builder()
+        .binding(0)
+        .stride(std::mem::size_of::<vk_sample_common::Vertex>() as u32)
+        .input_rate(ash::vk::VertexInputRate::VERTEX)
+        .build()];
+    let vertex_input_attribute = [
+        ash::vk::VertexInputAttributeDescription::builder()
+            .location(0)
+            .binding(0)
+            .format(ash::vk::Format::R32G32B32_SFLOAT)
+            .offset(vk_sample_common::offset_of!(vk_sample_common::Vertex, position) as u32)
+            .build(),
+        ash::vk::VertexInputAttributeDescription::builder()
+            .location(1)
+            .binding(0)
+            .format(ash::vk::Format::R32G32B32_SFLOAT)
+            .offset(vk_sample_common::offset_of!(vk_sample_common::Vertex, normal) as u32)
+            .build(),
+        ash::vk::VertexInputAttributeDescription::builder()
+            .location(2)
+            .binding(0)
+            .format(ash::vk::Format::R32G32B32A32_SFLOAT)
+            .offset(vk_sample_common::offset_of!(vk_sample_common::Vertex, tangent) as u32)
+            .build(),
+        ash::vk::VertexInputAttributeDescription::builder()
+            .location(3)
+            .binding(0)
+            .format(ash::vk::Format::R32G32_SFLOAT)
+            .offset(vk_sample_common::offset_of!(vk_sample_common::Vertex, texcoord) as u32)
+            .build(),
+    ];
+    let input_assembly_info = ash::vk::PipelineInputAssemblyStateCreateInfo::builder()
+        .topology(ash::vk::PrimitiveTopology::TRIANGLE_LIST)
+        .build();
+    let viewport_info = ash::vk::PipelineViewportStateCreateInfo::builder()
+        .viewport_count(1)
+        .scissor_count(1)
+        .build();
+    let rasterization_info = ash::vk::PipelineRasterizationStateCreateInfo::builder()
+        .depth_clamp_enable(false)
+        .rasterizer_discard_enable(false)
+        .polygon_mode(ash::vk::PolygonMode::FILL)
+        .cull_mode(ash::vk::CullModeFlags::NONE)
+        .front_face(ash::vk::FrontFace::COUNTER_CLOCKWISE)
+        .depth_bias_enable(false)
+        .line_width(1.0)
+        .build();
+    let stencil_op = ash::vk::StencilOpState::builder()
+        .fail_op(ash::vk::StencilOp::KEEP)
+        .pass_op(ash::vk::StencilOp::KEEP)
+        .compare_op(ash::vk::CompareOp::ALWAYS)
+        .build();
+    let depth_stencil_info = ash::vk::PipelineDepthStencilStateCreateInfo::builder()
+        .depth_test_enable(true)
+        .depth_write_enable(true)
+        .depth_compare_op(ash::vk::CompareOp::LESS_OR_EQUAL)
+        .depth_bounds_test_enable(false)
+        .stencil_test_enable(false)
+        .front(stencil_op)
+        .back(stencil_op)
+        .build();
+    let color_blend_attachments = [ash::vk::PipelineColorBlendAttachmentState::builder()
+        .color_write_mask(
+            ash::vk::ColorComponentFlags::R
+                | ash::vk::ColorComponentFlags::G
+                | ash::vk::ColorComponentFlags::B
+                | ash::vk::ColorComponentFlags::A,
+        )
+        .build()];
+    let color_blend_info = ash::vk::PipelineColorBlendStateCreateInfo::builder()
+        .attachments(color_blend_attachments.as_ref())
+        .build();
+    let dynamic_states = [
+        ash::vk::DynamicState::VIEWPORT,
+        ash::vk::DynamicState::SCISSOR,
+    ];
+    let dynamic_state_info = ash::vk::PipelineDynamicStateCreateInfo::builder()
+        .dynamic_states(dynamic_states.as_ref())
+        .build();
+    let vertex_input_state = ash::vk::PipelineVertexInputStateCreateInfo::builder()
+        .vertex_attribute_descriptions(vertex_input_attribute.as_ref())
+        .vertex_binding_descriptions(vertex_input_binding.as_ref())
+        .build();
+    let multisample_info = ash::vk::PipelineMultisampleStateCreateInfo::builder().build();
+
+    let pipeline_create_info = [ash::vk::GraphicsPipelineCreateInfo::builder()
+        .stages(pipeline_shader_stages.as_ref())
+        .vertex_input_state(&vertex_input_state)
+        .input_assembly_state(&input_assembly_info)
+        .viewport_state(&viewport_info)
+        .rasterization_state(&rasterization_info)
+        .multisample_state(&multisample_info)
+        .depth_stencil_state(&depth_stencil_info)
+        .color_blend_state(&color_blend_info)
+        .dynamic_state(&dynamic_state_info)
+        .layout(*pipeline_layout)
+        .render_pass(*render_pass)
+        .build()];
+
+    //  作成情報は 1 つだけなのでパイプラインも 1 つ
+    let graphics_pipeline = resource::Pipeline::new(device, unsafe {
+        device
+            .create_graphics_pipelines(
+                ash::vk::PipelineCache::null(),
+                pipeline_create_info.as_ref(),
+                None,
+            )
+            .map_err(|(_, result)| result)
+            .context("パイプラインを作成できない")?[0]
+    });
+    debug_names.name_object(*graphics_pipeline, "graphics pipeline");
+
+    //  12_create_vertex_buffer
+    //  --mesh が指定されていない場合は三角形を 1 枚描画する
+    let mesh = if config.mesh_file.is_empty() {
+        //  接線はテクスチャ座標から作る
+        let mut mesh = vk_sample_common::mesh::Mesh {
+            vertices: vec![
+                vk_sample_common::Vertex {
+                    position: nalgebra_glm::Vec3::new(0.0, 0.0, 0.0),
+                    normal: nalgebra_glm::Vec3::new(0.0, 0.0, 1.0),
+                    tangent: nalgebra_glm::Vec4::new(0.0, 0.0, 0.0, 0.0),
+                    texcoord: nalgebra_glm::Vec2::new(0.0, 0.0),
+                },
+                vk_sample_common::Vertex {
+                    position: nalgebra_glm::Vec3::new(1.0, 0.0, 0.0),
+                    normal: nalgebra_glm::Vec3::new(0.0, 0.0, 1.0),
+                    tangent: nalgebra_glm::Vec4::new(0.0, 0.0, 0.0, 0.0),
+                    texcoord: nalgebra_glm::Vec2::new(1.0, 0.0),
+                },
+                vk_sample_common::Vertex {
+                    position: nalgebra_glm::Vec3::new(0.0, 1.0, 0.0),
+                    normal: nalgebra_glm::Vec3::new(0.0, 0.0, 1.0),
+                    tangent: nalgebra_glm::Vec4::new(0.0, 0.0, 0.0, 0.0),
+                    texcoord: nalgebra_glm::Vec2::new(0.0, 1.0),
+                },
+            ],
+            indices: vec![0, 1, 2],
+            ..vk_sample_common::mesh::Mesh::default()
+        };
+        mesh.generate_tangents();
+        mesh
+    } else {
+        vk_sample_common::mesh::load(&config.mesh_file)?
+    };
+
+    let indices = mesh.indices();
+    let index_type = match indices {
+        vk_sample_common::mesh::Indices::U16(_) => ash::vk::IndexType::UINT16,
+        vk_sample_common::mesh::Indices::U32(_) => ash::vk::IndexType::UINT32,
+    };
+    let vertex_buffer_size = mesh.vertices.len() * std::mem::size_of::<vk_sample_common::Vertex>();
+    let index_buffer_size = indices.as_bytes().len();
+    let vertex_buffer_create_info = ash::vk::BufferCreateInfo::builder()
+        .size(vertex_buffer_size as u64)
+        .usage(ash::vk::BufferUsageFlags::VERTEX_BUFFER | ash::vk::BufferUsageFlags::TRANSFER_DST)
+        .build();
+    let vertex_buffer_alloc_info = vk_mem::AllocationCreateInfo {
+        usage: vk_mem::MemoryUsage::GpuOnly,
+        ..vk_mem::AllocationCreateInfo::default()
+    };
+    let vertex_buffer = resource::AllocatedBuffer::new(
+        &allocator,
+        &vertex_buffer_create_info,
+        &vertex_buffer_alloc_info,
+    )
+    .context("頂点バッファを作成できない")?;
+    debug_names.name_object(*vertex_buffer, "vertex buffer");
+
+    let index_buffer_create_info = ash::vk::BufferCreateInfo::builder()
+        .size(index_buffer_size as u64)
+        .usage(ash::vk::BufferUsageFlags::INDEX_BUFFER | ash::vk::BufferUsageFlags::TRANSFER_DST)
+        .build();
+    let index_buffer_alloc_info = vk_mem::AllocationCreateInfo {
+        usage: vk_mem::MemoryUsage::GpuOnly,
+        ..vk_mem::AllocationCreateInfo::default()
+    };
+    let index_buffer = resource::AllocatedBuffer::new(
+        &allocator,
+        &index_buffer_create_info,
+        &index_buffer_alloc_info,
+    )
+    .context("インデックスバッファを作成できない")?;
+    debug_names.name_object(*index_buffer, "index buffer");
+
+    //  頂点とインデックスはステージングバッファに並べ、 1 つのコマンドバッファでまとめてコピーする
+    //  ステージングバッファは転送が終われば要らないので、ここで破棄する
+    {
+        let mut uploader = Uploader::new(&context, 0)?;
+        uploader.upload_buffer(*vertex_buffer, 0, &mesh.vertices)?;
+        uploader.upload_buffer(*index_buffer, 0, indices.as_bytes())?;
+        uploader.wait()?;
+    }
+
+    //  13_create_semaphore
+    let mut semaphores = Vec::<Semaphores>::with_capacity(swapchain_image_count as usize);
+    for i in 0..swapchain_image_count {
+        //  途中で失敗しても、作成済みのものは Drop で破棄される
+        let fence = resource::create_fence(device, ash::vk::FenceCreateFlags::SIGNALED)
+            .context("フェンスを作成できない")?;
+        let image_acquired_semaphore =
+            resource::create_semaphore(device).context("セマフォを作成できない")?;
+        let draw_complete_semaphore =
+            resource::create_semaphore(device).context("セマフォを作成できない")?;
+        let image_ownership_semaphore =
+            resource::create_semaphore(device).context("セマフォを作成できない")?;
+        debug_names.name_object(*fence, &format!("frame fence {}", i));
+        debug_names.name_object(
+            *image_acquired_semaphore,
+            &format!("image acquired semaphore {}", i),
+        );
+        debug_names.name_object(
+            *draw_complete_semaphore,
+            &format!("draw complete semaphore {}", i),
+        );
+        debug_names.name_object(
+            *image_ownership_semaphore,
+            &format!("image ownership semaphore {}", i),
+        );
+
+        semaphores.push(Semaphores {
+            fence: fence,
+            image_acquired_semaphore: image_acquired_semaphore,
+            draw_complete_semaphore: draw_complete_semaphore,
+            image_ownership_semaphore: image_ownership_semaphore,
+        })
+    }
+
+    //  14_create_command_buffer
+    let mut graphics_command_buffers =
+        resource::CommandBuffers::new(device, *graphics_command_pool, swapchain_image_count)
+            .context("コマンドバッファを確保できない")?;
+    let mut present_command_buffers = resource::CommandBuffers::new(
+        device,
+        present_command_pool,
+        if eq_queue { 0 } else { swapchain_image_count },
+    )
+    .context("コマンドバッファを確保できない")?;
+    graphics_command_buffers.name(debug_names, "graphics command buffer");
+    present_command_buffers.name(debug_names, "present command buffer");
+
+    //  15_draw
+    let clear_values = [
+        ash::vk::ClearValue {
+            color: ash::vk::ClearColorValue {
+                float32: [0.0, 0.0, 0.0, 0.0],
+            },
+        },
+        ash::vk::ClearValue {
+            depth_stencil: ash::vk::ClearDepthStencilValue {
+                depth: 1.0,
+                stencil: 0,
+            },
+        },
+    ];
+
+    //  メッシュは全体が画面の中央に収まる位置から見始め、ウィンドウの入力でカメラを動かす
+    //  三角形はそのまま見えるよう座標変換は行わない
+    let aspect = |extent: ash::vk::Extent2D| extent.width as f32 / extent.height.max(1) as f32;
+    let mut camera_controller = if config.mesh_file.is_empty() {
+        None
+    } else {
+        mesh.bounds()
+            .map(|bounds| CameraController::new(&bounds, aspect(swapchain.extent)))
+    };
+    let push_constants = |extent: ash::vk::Extent2D,
+                          camera_controller: Option<&CameraController>| {
+        match camera_controller {
+            Some(c) => c.camera().push_constants(aspect(extent)),
+            None => vk_sample_common::PushConstants::new(
+                nalgebra_glm::identity(),
+                nalgebra_glm::identity(),
+                nalgebra_glm::vec3(0.0, 0.0, -2.0),
+                nalgebra_glm::vec3(0.0, 0.0, 2.0),
+            ),
+        }
+    };
+
+    let color_subresource_range = ash::vk::ImageSubresourceRange::builder()
+        .aspect_mask(ash::vk::ImageAspectFlags::COLOR)
+        .level_count(1)
+        .layer_count(1)
+        .build();
+    //  表示キューが別のキューファミリーの場合に、グラフィクスキューから表示キューへイメージの所有権を移す
+    let ownership_barrier = |image: ash::vk::Image| {
+        [ash::vk::ImageMemoryBarrier::builder()
+            .src_access_mask(ash::vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+            .old_layout(ash::vk::ImageLayout::PRESENT_SRC_KHR)
+            .new_layout(ash::vk::ImageLayout::PRESENT_SRC_KHR)
+            .src_queue_family_index(context.graphics_queue_index)
+            .dst_queue_family_index(context.present_queue_index)
+            .image(image)
+            .subresource_range(color_subresource_range)
+            .build()]
+    };
+    let cmd_release_image = |command_buffer: ash::vk::CommandBuffer, image: ash::vk::Image| unsafe {
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            ash::vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            ash::vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+            ash::vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &ownership_barrier(image),
+        );
+    };
+
+    //  カメラの行列は毎フレーム変わるので、描画するイメージのコマンドバッファを毎回記録し直す
+    //  スワップチェーンを作り直してイメージが増えた場合はデスクリプタセットを使い回す
+    //  release が false の場合は所有権を移さないので、後で record_release_command_buffer で移す
+    let record_command_buffer = |i: usize,
+                                 swapchain: &Swapchain,
+                                 framebuffers: &[FrameBuffer],
+                                 graphics_command_buffers: &[ash::vk::CommandBuffer],
+                                 present_command_buffers: &[ash::vk::CommandBuffer],
+                                 push_constants: &vk_sample_common::PushConstants,
+                                 release: bool|
+     -> Result<(), Error> {
+        let command_buffer = graphics_command_buffers[i];
+        unsafe {
+            device
+                .reset_command_buffer(command_buffer, ash::vk::CommandBufferResetFlags::empty())
+                .context("コマンドバッファをリセットできない")?;
+            device
+                .begin_command_buffer(
+                    command_buffer,
+                    &ash::vk::CommandBufferBeginInfo::builder()
+                        .flags(ash::vk::CommandBufferUsageFlags::SIMULTANEOUS_USE)
+                        .build(),
+                )
+                .context("コマンドバッファの記録を開始できない")?;
+        }
+        let pass_info = ash::vk::RenderPassBeginInfo::builder()
+            .render_pass(*render_pass)
+            .framebuffer(*framebuffers[i].framebuffer)
+            .render_area(
+                ash::vk::Rect2D::builder()
+                    .offset(ash::vk::Offset2D { x: 0, y: 0 })
+                    .extent(swapchain.extent)
+                    .build(),
+            )
+            .clear_values(clear_values.as_ref())
+            .build();
+        debug_names.begin_label(command_buffer, &format!("draw image {}", i));
+        unsafe {
+            device.cmd_begin_render_pass(
+                command_buffer,
+                &pass_info,
+                ash::vk::SubpassContents::INLINE,
+            );
+            device.cmd_bind_pipeline(
+                command_buffer,
+                ash::vk::PipelineBindPoint::GRAPHICS,
+                *graphics_pipeline,
+            );
+        }
+        let viewport = [ash::vk::Viewport::builder()
+            .width(swapchain.extent.width as f32)
+            .height(swapchain.extent.height as f32)
+            .min_depth(0.0_f32)
+            .max_depth(1.0_f32)
+            .build()];
+        unsafe {
+            device.cmd_set_viewport(command_buffer, 0, viewport.as_ref());
+        }
+        let scissor = [ash::vk::Rect2D::builder()
+            .offset(ash::vk::Offset2D { x: 0, y: 0 })
+            .extent(swapchain.extent)
+            .build()];
+        unsafe {
+            device.cmd_set_scissor(command_buffer, 0, scissor.as_ref());
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                ash::vk::PipelineBindPoint::GRAPHICS,
+                *pipeline_layout,
+                0,
+                &[descriptor_set[i % descriptor_set.len()]],
+                &[0; 0],
+            );
+            device.cmd_push_constants(
+                command_buffer,
+                *pipeline_layout,
+                ash::vk::ShaderStageFlags::VERTEX | ash::vk::ShaderStageFlags::FRAGMENT,
+                0,
+                push_constants.as_bytes(),
+            );
+        }
+
+        let vertex_buffers = [*vertex_buffer];
+        let vertex_buffer_offsets = [0];
+        unsafe {
+            device.cmd_bind_vertex_buffers(
+                command_buffer,
+                0,
+                vertex_buffers.as_ref(),
+                vertex_buffer_offsets.as_ref(),
+            );
+            device.cmd_bind_index_buffer(command_buffer, *index_buffer, 0, index_type);
+            device.cmd_draw_indexed(command_buffer, indices.len() as u32, 1, 0, 0, 0);
+            device.cmd_end_render_pass(command_buffer);
+        }
+        debug_names.end_label(command_buffer);
+
+        //  表示キューが別のキューファミリーの場合はイメージの所有権を移す
+        if !eq_queue {
+            if release {
+                cmd_release_image(command_buffer, swapchain.images[i]);
+            }
+            unsafe {
+                //  表示キューのコマンドバッファは内容が変わらないので、毎フレームの記録では空で渡す
+                if let Some(present_command_buffer) = present_command_buffers.get(i) {
+                    device
+                        .begin_command_buffer(
+                            *present_command_buffer,
+                            &ash::vk::CommandBufferBeginInfo::builder()
+                                .flags(ash::vk::CommandBufferUsageFlags::SIMULTANEOUS_USE)
+                                .build(),
+                        )
+                        .context("コマンドバッファの記録を開始できない")?;
+                    device.cmd_pipeline_barrier(
+                        *present_command_buffer,
+                        ash::vk::PipelineStageFlags::TOP_OF_PIPE,
+                        ash::vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                        ash::vk::DependencyFlags::empty(),
+                        &[],
+                        &[],
+                        &ownership_barrier(swapchain.images[i]),
+                    );
+                    device
+                        .end_command_buffer(*present_command_buffer)
+                        .context("コマンドバッファの記録を終了できない")?;
+                }
+            }
+        }
+
+        unsafe {
+            device
+                .end_command_buffer(command_buffer)
+                .context("コマンドバッファの記録を終了できない")?;
+        }
+        Ok(())
+    };
+    //  描画済みのイメージの所有権を移すだけのコマンドバッファを記録する
+    let record_release_command_buffer =
+        |command_buffer: ash::vk::CommandBuffer, image: ash::vk::Image| -> Result<(), Error> {
+            unsafe {
+                device
+                    .reset_command_buffer(command_buffer, ash::vk::CommandBufferResetFlags::empty())
+                    .context("コマンドバッファをリセットできない")?;
+                device
+                    .begin_command_buffer(
+                        command_buffer,
+                        &ash::vk::CommandBufferBeginInfo::builder()
+                            .flags(ash::vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
+                            .build(),
+                    )
+                    .context("コマンドバッファの記録を開始できない")?;
+            }
+            cmd_release_image(command_buffer, image);
+            unsafe {
+                device
+                    .end_command_buffer(command_buffer)
+                    .context("コマンドバッファの記録を終了できない")?;
+            }
+            Ok(())
+        };
+    let record_command_buffers = |swapchain: &Swapchain,
+                                  framebuffers: &[FrameBuffer],
+                                  graphics_command_buffers: &[ash::vk::CommandBuffer],
+                                  present_command_buffers: &[ash::vk::CommandBuffer],
+                                  push_constants: &vk_sample_common::PushConstants|
+     -> Result<(), Error> {
+        for i in 0..framebuffers.len() {
+            record_command_buffer(
+                i,
+                swapchain,
+                framebuffers,
+                graphics_command_buffers,
+                present_command_buffers,
+                push_constants,
+                true,
+            )?;
+        }
+        Ok(())
+    };
+    record_command_buffers(
+        &swapchain,
+        &framebuffers,
+        &graphics_command_buffers,
+        &present_command_buffers,
+        &push_constants(swapchain.extent, camera_controller.as_ref()),
+    )?;
+
+    //  描画中に失敗した場合も、破棄する前に GPU の処理が終わるのを待ってからエラーを返す
+    let result = (|| -> Result<(), Error> {
+        if let (Some(glfw), Some(window), Some(events)) =
+            (glfw.as_mut(), window.as_ref(), events.as_ref())
+        {
+            let mut current_frame = 0;
+            let mut recreate_swapchain = false;
+            //  イメージごとに、そのイメージを最後に描画したフレームのフェンス
+            let mut image_fences = vec![ash::vk::Fence::null(); framebuffers.len()];
+            let mut last_time = glfw.get_time();
+            let take_screenshot = config.screenshot.is_some();
+            while !window.should_close() {
+                glfw.poll_events();
+                for (_, event) in glfw::flush_messages(events) {
+                    if let glfw::WindowEvent::FramebufferSize(_, _) = event {
+                        recreate_swapchain = true;
+                    }
+                    //  M で GPU メモリの使われ方を表示する
+                    if let glfw::WindowEvent::Key(glfw::Key::M, _, glfw::Action::Press, _) = event {
+                        memory_report::print_text(&memory_report::memory_report(
+                            &context, &allocator,
+                        )?);
+                    }
+                    if let Some(camera_controller) = camera_controller.as_mut() {
+                        camera_controller.handle_event(&event);
+                    }
+                }
+                let now = glfw.get_time();
+                if let Some(camera_controller) = camera_controller.as_mut() {
+                    camera_controller.update((now - last_time) as f32);
+                }
+                last_time = now;
+
+                if recreate_swapchain {
+                    //  最小化されている間は大きさが 0 になりスワップチェーンを作れないので、元に戻るまで待つ
+                    let (width, height) = window.get_framebuffer_size();
+                    if width <= 0 || height <= 0 {
+                        glfw.wait_events();
+                        continue;
+                    }
+
+                    //  古いスワップチェーンのイメージを使うコマンドが全て終わってから作り直す
+                    unsafe {
+                        device
+                            .device_wait_idle()
+                            .context("デバイスの処理の完了を待てない")?;
+                    }
+                    let new_swapchain = Swapchain::new(
+                        &context,
+                        format,
+                        image_usage,
+                        ash::vk::Extent2D {
+                            width: width as u32,
+                            height: height as u32,
+                        },
+                        swapchain.handle(),
+                    )
+                    .context("スワップチェーンを作り直せない")?;
+                    //  古いイメージを参照するフレームバッファを先に破棄する
+                    framebuffers.clear();
+                    swapchain = new_swapchain;
+                    framebuffers = create_framebuffers(
+                        device,
+                        &allocator,
+                        debug_names,
+                        *render_pass,
+                        format.format,
+                        &swapchain,
+                    )?;
+                    if graphics_command_buffers.len() != framebuffers.len() {
+                        graphics_command_buffers = resource::CommandBuffers::new(
+                            device,
+                            *graphics_command_pool,
+                            framebuffers.len() as u32,
+                        )
+                        .context("コマンドバッファを確保できない")?;
+                        graphics_command_buffers.name(debug_names, "graphics command buffer");
+                        if !eq_queue {
+                            present_command_buffers = resource::CommandBuffers::new(
+                                device,
+                                present_command_pool,
+                                framebuffers.len() as u32,
+                            )
+                            .context("コマンドバッファを確保できない")?;
+                            present_command_buffers.name(debug_names, "present command buffer");
+                        }
+                    }
+                    record_command_buffers(
+                        &swapchain,
+                        &framebuffers,
+                        &graphics_command_buffers,
+                        &present_command_buffers,
+                        &push_constants(swapchain.extent, camera_controller.as_ref()),
+                    )?;
+                    image_fences = vec![ash::vk::Fence::null(); framebuffers.len()];
+                    recreate_swapchain = false;
+                }
+
+                let sync = &semaphores[current_frame];
+                current_frame = (current_frame + 1) % semaphores.len();
+                unsafe {
+                    device
+                        .wait_for_fences(&[*sync.fence], true, std::u64::MAX)
+                        .context("フェンスを待てない")?;
+                }
+
+                let image_index = match unsafe {
+                    swapchain_loader.acquire_next_image(
+                        swapchain.handle(),
+                        std::u64::MAX,
+                        *sync.image_acquired_semaphore,
+                        ash::vk::Fence::null(),
+                    )
+                } {
+                    Ok((index, _)) => index as usize,
+                    //  セマフォは待つ状態にならないので、作り直してから取得し直す
+                    Err(ash::vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                        recreate_swapchain = true;
+                        continue;
+                    }
+                    Err(e) => {
+                        return Err(Error::Vulkan {
+                            context: String::from("スワップチェーンからイメージを取得できない"),
+                            result: e,
+                        });
+                    }
+                };
+
+                //  前にこのイメージを描画したコマンドバッファが終わってから記録し直す
+                if image_fences[image_index] != ash::vk::Fence::null() {
+                    unsafe {
+                        device
+                            .wait_for_fences(&[image_fences[image_index]], true, std::u64::MAX)
+                            .context("フェンスを待てない")?;
+                    }
+                }
+                image_fences[image_index] = *sync.fence;
+                record_command_buffer(
+                    image_index,
+                    &swapchain,
+                    &framebuffers,
+                    &graphics_command_buffers,
+                    &[],
+                    &push_constants(swapchain.extent, camera_controller.as_ref()),
+                    !take_screenshot,
+                )?;
+
+                //  スクリーンショットを撮る場合は、読み出してから表示のためのセマフォを送る
+                let draw_complete_semaphores = if take_screenshot {
+                    vec![]
+                } else {
+                    vec![*sync.draw_complete_semaphore]
+                };
+                let wait_stages = [ash::vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
+                unsafe {
+                    device
+                        .reset_fences(&[*sync.fence])
+                        .context("フェンスをリセットできない")?;
+                    device
+                        .queue_submit(
+                            context.graphics_queue,
+                            &[ash::vk::SubmitInfo::builder()
+                                .wait_semaphores(&[*sync.image_acquired_semaphore])
+                                .wait_dst_stage_mask(&wait_stages)
+                                .command_buffers(&[graphics_command_buffers[image_index]])
+                                .signal_semaphores(&draw_complete_semaphores)
+                                .build()],
+                            *sync.fence,
+                        )
+                        .context("コマンドバッファを送信できない")?;
+                }
+
+                //  スクリーンショットは表示する前のイメージから読み出す
+                //  表示キューが別のキューファミリーの場合は、グラフィクスキューが所有している間に読み出してから所有権を移す
+                if let Some(path) = config.screenshot.as_ref() {
+                    unsafe {
+                        device
+                            .wait_for_fences(&[*sync.fence], true, std::u64::MAX)
+                            .context("フェンスを待てない")?;
+                    }
+                    screenshot::save_image(
+                        &context,
+                        *graphics_command_pool,
+                        swapchain.images[image_index],
+                        ash::vk::ImageLayout::PRESENT_SRC_KHR,
+                        format.format,
+                        swapchain.extent,
+                        path,
+                    )?;
+                    let release_command_buffers = if eq_queue {
+                        vec![]
+                    } else {
+                        let command_buffer = graphics_command_buffers[image_index];
+                        record_release_command_buffer(
+                            command_buffer,
+                            swapchain.images[image_index],
+                        )?;
+                        vec![command_buffer]
+                    };
+                    unsafe {
+                        device
+                            .reset_fences(&[*sync.fence])
+                            .context("フェンスをリセットできない")?;
+                        device
+                            .queue_submit(
+                                context.graphics_queue,
+                                &[ash::vk::SubmitInfo::builder()
+                                    .command_buffers(&release_command_buffers)
+                                    .signal_semaphores(&[*sync.draw_complete_semaphore])
+                                    .build()],
+                                *sync.fence,
+                            )
+                            .context("コマンドバッファを送信できない")?;
+                    }
+                }
+
+                let present_wait_semaphore = if eq_queue {
+                    *sync.draw_complete_semaphore
+                } else {
+                    let ownership_wait_stages = [ash::vk::PipelineStageFlags::ALL_COMMANDS];
+                    unsafe {
+                        device
+                            .queue_submit(
+                                context.present_queue,
+                                &[ash::vk::SubmitInfo::builder()
+                                    .wait_semaphores(&[*sync.draw_complete_semaphore])
+                                    .wait_dst_stage_mask(&ownership_wait_stages)
+                                    .command_buffers(&[present_command_buffers[image_index]])
+                                    .signal_semaphores(&[*sync.image_ownership_semaphore])
+                                    .build()],
+                                ash::vk::Fence::null(),
+                            )
+                            .context("コマンドバッファを送信できない")?;
+                    }
+                    *sync.image_ownership_semaphore
+                };
+
+                match unsafe {
+                    swapchain_loader.queue_present(
+                        context.present_queue,
+                        &ash::vk::PresentInfoKHR::builder()
+                            .wait_semaphores(&[present_wait_semaphore])
+                            .swapchains(&[swapchain.handle()])
+                            .image_indices(&[image_index as u32])
+                            .build(),
+                    )
+                } {
+                    Ok(false) => {}
+                    //  サーフェスに合わなくなっている場合は次のフレームの前に作り直す
+                    Ok(true) | Err(ash::vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                        recreate_swapchain = true;
+                    }
+                    Err(e) => {
+                        return Err(Error::Vulkan {
+                            context: String::from("イメージを表示できない"),
+                            result: e,
+                        });
+                    }
+                }
+
+                //  スクリーンショットを撮った場合は 1 フレームで終わる
+                if config.screenshot.is_some() {
+                    break;
+                }
+            }
+        } else {
+            //  ヘッドレスの場合は 1 フレームだけ描画して終わる
+            let sync = &semaphores[0];
+            unsafe {
+                device
+                    .reset_fences(&[*sync.fence])
+                    .context("フェンスをリセットできない")?;
+                device
+                    .queue_submit(
+                        context.graphics_queue,
+                        &[ash::vk::SubmitInfo::builder()
+                            .command_buffers(&[graphics_command_buffers[0]])
+                            .build()],
+                        *sync.fence,
+                    )
+                    .context("コマンドバッファを送信できない")?;
+                device
+                    .wait_for_fences(&[*sync.fence], true, std::u64::MAX)
+                    .context("フェンスを待てない")?;
+            }
+            if let Some(path) = config.screenshot.as_ref() {
+                screenshot::save_image(
+                    &context,
+                    *graphics_command_pool,
+                    framebuffers[0].color_image,
+                    ash::vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    format.format,
+                    swapchain.extent,
+                    path,
+                )?;
+            }
+        }
+
+        Ok(())
+    })();
+
+    //  破棄する前に GPU の処理が全て終わるのを待つ
+    unsafe {
+        device
+            .device_wait_idle()
+            .context("デバイスの処理の完了を待てない")?;
+    }
+    result?;
+
+    //  破棄する前の GPU メモリの使われ方を書き出す
+    //  同じシーンで実行ごとに比べると、解放し忘れているリソースが分かる
+    if let Some(path) = config.memory_report.as_ref() {
+        memory_report::write_json(path, &memory_report::memory_report(&context, &allocator)?)?;
+    }
+    Ok(())
+}
+
+//  スワップチェーンとそのイメージ
+//  ヘッドレスの場合 swapchain は None で images は空になる
+struct Swapchain<'a> {
+    pub swapchain: Option<resource::Swapchain<'a>>,
+    pub extent: ash::vk::Extent2D,
+    pub images: Vec<ash::vk::Image>,
+}
+
+impl Swapchain<'_> {
+    //  old_swapchain を渡すと作り直したスワップチェーンに表示中のイメージを引き継ぐ
+    //  extent はサーフェスが大きさを決めない場合に使う
+    pub fn new<'a>(
+        context: &'a VulkanContext,
+        format: ash::vk::SurfaceFormatKHR,
+        image_usage: ash::vk::ImageUsageFlags,
+        extent: ash::vk::Extent2D,
+        old_swapchain: ash::vk::SwapchainKHR,
+    ) -> Result<Swapchain<'a>, ash::vk::Result> {
+        let surface_capabilities = unsafe {
+            context
+                .surface_loader
+                .get_physical_device_surface_capabilities(
+                    context.physical_device,
+                    context.surface,
+                )?
+        };
+        let swapchain_extent = if surface_capabilities.current_extent.width == -1i32 as u32 {
+            extent
+        } else {
+            surface_capabilities.current_extent
+        };
+        //  max_image_count が 0 の場合は上限が無い
+        let swapchain_image_count = if surface_capabilities.max_image_count == 0 {
+            surface_capabilities.min_image_count + 1
+        } else {
+            std::cmp::min(
+                surface_capabilities.min_image_count + 1,
+                surface_capabilities.max_image_count,
+            )
+        };
+
+        let swapchain = unsafe {
+            context.swapchain_loader.create_swapchain(
+                &ash::vk::SwapchainCreateInfoKHR::builder()
+                    .surface(context.surface)
+                    .min_image_count(swapchain_image_count)
+                    .image_format(format.format)
+                    .image_color_space(format.color_space)
+                    .image_extent(swapchain_extent)
+                    .image_array_layers(1)
+                    .image_usage(image_usage)
+                    .image_sharing_mode(ash::vk::SharingMode::EXCLUSIVE)
+                    .pre_transform(
+                        if surface_capabilities
+                            .supported_transforms
+                            .intersects(ash::vk::SurfaceTransformFlagsKHR::IDENTITY)
+                        {
+                            ash::vk::SurfaceTransformFlagsKHR::IDENTITY
+                        } else {
+                            surface_capabilities.current_transform
+                        },
+                    )
+                    .composite_alpha(ash::vk::CompositeAlphaFlagsKHR::OPAQUE)
+                    .present_mode(ash::vk::PresentModeKHR::FIFO)
+                    .clipped(true)
+                    .old_swapchain(old_swapchain)
+                    .build(),
+                None,
+            )?
+        };
+        let swapchain = resource::Swapchain::new(&context.swapchain_loader, swapchain);
+        //  実際のイメージの数は min_image_count より多い事がある
+        let images = unsafe { context.swapchain_loader.get_swapchain_images(*swapchain)? };
+
+        context.debug_names.name_object(*swapchain, "swapchain");
+        for (i, image) in images.iter().enumerate() {
+            context
+                .debug_names
+                .name_object(*image, &format!("swapchain image {}", i));
+        }
+
+        Ok(Swapchain {
+            swapchain: Some(swapchain),
+            extent: swapchain_extent,
+            images: images,
+        })
+    }
+
+    pub fn headless<'a>(extent: ash::vk::Extent2D) -> Swapchain<'a> {
+        Swapchain {
+            swapchain: None,
+            extent: extent,
+            images: vec![],
+        }
+    }
+
+    //  ヘッドレスの場合は null
+    pub fn handle(&self) -> ash::vk::SwapchainKHR {
+        self.swapchain
+            .as_ref()
+            .map_or(ash::vk::SwapchainKHR::null(), |swapchain| {
+                swapchain.handle()
+            })
+    }
+
+    //  ヘッドレスの場合はオフスクリーンのイメージ 1 枚に描画する
+    pub fn image_count(&self) -> u32 {
+        if self.images.is_empty() {
+            1
+        } else {
+            self.images.len() as u32
+        }
+    }
+}
+
+//  スワップチェーンのイメージごとにデプスバッファとフレームバッファを作る
+//  ヘッドレスの場合はカラーバッファも作る
+fn create_framebuffers<'a>(
+    device: &'a ash::Device,
+    allocator: &'a vk_mem::Allocator,
+    debug_names: &DebugNames,
+    render_pass: ash::vk::RenderPass,
+    format: ash::vk::Format,
+    swapchain: &Swapchain,
+) -> Result<Vec<FrameBuffer<'a>>, Error> {
+    let mut framebuffers = Vec::<FrameBuffer>::new();
+    for i in 0..swapchain.image_count() as usize {
+        let offscreen_color_image = if swapchain.images.is_empty() {
+            let color_image_create_info = ash::vk::ImageCreateInfo::builder()
+                .format(format)
+                .mip_levels(1)
+                .array_layers(1)
+                .samples(ash::vk::SampleCountFlags::TYPE_1)
+                .usage(
+                    ash::vk::ImageUsageFlags::COLOR_ATTACHMENT
+                        | ash::vk::ImageUsageFlags::TRANSFER_SRC,
+                )
+                .initial_layout(ash::vk::ImageLayout::UNDEFINED)
+                .image_type(ash::vk::ImageType::TYPE_2D)
+                .extent(
+                    ash::vk::Extent3D::builder()
+                        .width(swapchain.extent.width)
+                        .height(swapchain.extent.height)
+                        .depth(1)
+                        .build(),
+                )
+                .build();
+            let color_image_alloc_info = vk_mem::AllocationCreateInfo {
+                usage: vk_mem::MemoryUsage::GpuOnly,
+                ..vk_mem::AllocationCreateInfo::default()
+            };
+            let color_image = resource::AllocatedImage::new(
+                allocator,
+                &color_image_create_info,
+                &color_image_alloc_info,
+            )
+            .context("イメージを作成できない")?;
+            debug_names.name_object(*color_image, &format!("offscreen color image {}", i));
+            Some(color_image)
+        } else {
+            None
+        };
+        let color_image = match offscreen_color_image.as_ref() {
+            Some(image) => **image,
+            None => swapchain.images[i],
+        };
+        let color_image_attachment = resource::ImageView::new(device, unsafe {
+            device
+                .create_image_view(
+                    &ash::vk::ImageViewCreateInfo::builder()
+                        .image(color_image)
+                        .view_type(ash::vk::ImageViewType::TYPE_2D)
+                        .format(format)
+                        .subresource_range(
+                            ash::vk::ImageSubresourceRange::builder()
+                                .aspect_mask(ash::vk::ImageAspectFlags::COLOR)
+                                .level_count(1)
+                                .layer_count(1)
+                                .build(),
+                        )
+                        .build(),
+                    None,
+                )
+                .context("イメージビューを作成できない")?
+        });
+        debug_names.name_object(*color_image_attachment, &format!("color attachment {}", i));
+
+        let depth_image_create_info = ash::vk::ImageCreateInfo::builder()
+            .format(ash::vk::Format::D16_UNORM)
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(ash::vk::SampleCountFlags::TYPE_1)
+            .usage(ash::vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT)
+            .initial_layout(ash::vk::ImageLayout::UNDEFINED)
+            .image_type(ash::vk::ImageType::TYPE_2D)
+            .extent(
+                ash::vk::Extent3D::builder()
+                    .width(swapchain.extent.width)
+                    .height(swapchain.extent.height)
+                    .depth(1)
+                    .build(),
+            )
+            .build();
+        let depth_image_alloc_info = vk_mem::AllocationCreateInfo {
+            usage: vk_mem::MemoryUsage::GpuOnly,
+            ..vk_mem::AllocationCreateInfo::default()
+        };
+        let depth_image = resource::AllocatedImage::new(
+            allocator,
+            &depth_image_create_info,
+            &depth_image_alloc_info,
+        )
+        .context("イメージを作成できない")?;
+        debug_names.name_object(*depth_image, &format!("depth image {}", i));
+        let depth_image_attachment = resource::ImageView::new(device, unsafe {
+            device
+                .create_image_view(
+                    &ash::vk::ImageViewCreateInfo::builder()
+                        .image(*depth_image)
+                        .view_type(ash::vk::ImageViewType::TYPE_2D)
+                        .format(ash::vk::Format::D16_UNORM)
+                        .subresource_range(
+                            ash::vk::ImageSubresourceRange::builder()
+                                .aspect_mask(ash::vk::ImageAspectFlags::DEPTH)
+                                .level_count(1)
+                                .layer_count(1)
+                                .build(),
+                        )
+                        .build(),
+                    None,
+                )
+                .context("イメージビューを作成できない")?
+        });
+        debug_names.name_object(*depth_image_attachment, &format!("depth attachment {}", i));
+
+        let attachments_raw = [*color_image_attachment, *depth_image_attachment];
+        let framebuffer = resource::Framebuffer::new(device, unsafe {
+            device
+                .create_framebuffer(
+                    &ash::vk::FramebufferCreateInfo::builder()
+                        .render_pass(render_pass)
+                        .attachments(&attachments_raw)
+                        .width(swapchain.extent.width)
+                        .height(swapchain.extent.height)
+                        .layers(1)
+                        .build(),
+                    None,
+                )
+                .context("フレームバッファを作成できない")?
+        });
+        debug_names.name_object(*framebuffer, &format!("framebuffer {}", i));
+
+        framebuffers.push(FrameBuffer {
+            framebuffer: framebuffer,
+            color_image_attachment: color_image_attachment,
+            depth_image_attachment: depth_image_attachment,
+            depth_image: depth_image,
+            color_image: color_image,
+            offscreen_color_image: offscreen_color_image,
+        });
+    }
+    Ok(framebuffers)
+}
+
+//  フィールドは宣言した順に破棄されるので、フレームバッファ、イメージビュー、イメージの順に並べる
+//  イメージビューとイメージは読まないが、フレームバッファと同時に破棄するために持つ
+#[allow(dead_code)]
+struct FrameBuffer<'a> {
+    pub framebuffer: resource::Framebuffer<'a>,
+    pub color_image_attachment: resource::ImageView<'a>,
+    pub depth_image_attachment: resource::ImageView<'a>,
+    pub depth_image: resource::AllocatedImage<'a>,
+    //  描画先のイメージ
+    pub color_image: ash::vk::Image,
+    //  スワップチェーンのイメージはスワップチェーンが破棄するので、
+    //  オフスクリーンのイメージを作った場合だけ所有する
+    pub offscreen_color_image: Option<resource::AllocatedImage<'a>>,
+}
+
+struct Semaphores<'a> {
+    pub fence: resource::Fence<'a>,
+    pub image_acquired_semaphore: resource::Semaphore<'a>,
+    pub draw_complete_semaphore: resource::Semaphore<'a>,
+    pub image_ownership_semaphore: resource::Semaphore<'a>,
+}
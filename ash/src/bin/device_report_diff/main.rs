@@ -0,0 +1,80 @@
+//  02_list_devices --format json で保存した 2 つのレポートを比べる
+//  device_report_diff [--all] [--device <index>] <left.json> <right.json>
+//  diff と同じく、違いが無ければ 0 、あれば 1 、比べられなければ 2 で終わる
+use vk_sample_common::report;
+
+fn usage() {
+    eprintln!("使い方: device_report_diff [--all] [--device <index>] <left.json> <right.json>");
+}
+
+fn load(path: &str, device_index: usize) -> Result<serde_json::Value, String> {
+    let text =
+        std::fs::read_to_string(path).map_err(|e| format!("{} を読む事ができない: {}", path, e))?;
+    let report: serde_json::Value = serde_json::from_str(&text)
+        .map_err(|e| format!("{} はデバイスのレポートではない: {}", path, e))?;
+    report["devices"]
+        .as_array()
+        .and_then(|devices| devices.get(device_index))
+        .cloned()
+        .ok_or_else(|| format!("{} に {} 番目のデバイスが無い", path, device_index))
+}
+
+fn main() {
+    let mut all = false;
+    let mut device_index = 0;
+    let mut paths = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--all" | "-a" => all = true,
+            "--device" | "-d" => match args.next().and_then(|index| index.parse().ok()) {
+                Some(index) => device_index = index,
+                None => {
+                    usage();
+                    std::process::exit(2);
+                }
+            },
+            _ => paths.push(arg),
+        }
+    }
+    if paths.len() != 2 {
+        usage();
+        std::process::exit(2);
+    }
+
+    let left = match load(&paths[0], device_index) {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    let right = match load(&paths[1], device_index) {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+
+    println!(
+        "- {} ({}, ドライバ {})",
+        paths[0],
+        left["name"].as_str().unwrap_or(""),
+        left["driver_version"].as_str().unwrap_or("")
+    );
+    println!(
+        "+ {} ({}, ドライバ {})",
+        paths[1],
+        right["name"].as_str().unwrap_or(""),
+        right["driver_version"].as_str().unwrap_or("")
+    );
+
+    let differences = report::diff_reports(&left, &right, all);
+    if differences.is_empty() {
+        println!("違いは無い");
+        return;
+    }
+    report::print_differences(&differences);
+    std::process::exit(1);
+}
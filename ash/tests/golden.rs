@@ -0,0 +1,220 @@
+//  サンプルをヘッドレスで描画し、リファレンス画像と比較する
+//
+//  GPU による差が出ないよう、ソフトウェアラスタライザ (lavapipe 等) を使って実行する
+//  描画するテストは golden フィーチャーを有効にした場合だけ実行する
+//  VK_ICD_FILENAMES=/usr/share/vulkan/icd.d/lvp_icd.x86_64.json cargo test -p vulkan_samples_2019_rust_ash --features golden
+//
+//  リファレンス画像が無い場合や描画結果を意図して変えた場合は UPDATE_GOLDEN=1 を付けて実行すると
+//  tests/golden/ 以下のリファレンス画像を書き換える
+use vk_sample_common::screenshot;
+
+//  各チャンネルの差がこの値以下なら同じ画素とみなす
+const CHANNEL_TOLERANCE: u8 = 2;
+//  異なる画素の割合がこの値以下なら同じ画像とみなす
+const PIXEL_TOLERANCE: f64 = 0.001;
+
+const WIDTH: u32 = 320;
+const HEIGHT: u32 = 240;
+
+struct Image {
+    width: u32,
+    height: u32,
+    rgba: Vec<u8>,
+}
+
+fn repository_dir() -> std::path::PathBuf {
+    [env!("CARGO_MANIFEST_DIR"), ".."].iter().collect()
+}
+
+fn golden_path(name: &str) -> std::path::PathBuf {
+    [
+        env!("CARGO_MANIFEST_DIR"),
+        "tests",
+        "golden",
+        &format!("{}.png", name),
+    ]
+    .iter()
+    .collect()
+}
+
+fn output_path(name: &str, suffix: &str) -> std::path::PathBuf {
+    [
+        env!("CARGO_TARGET_TMPDIR"),
+        &format!("{}{}.png", name, suffix),
+    ]
+    .iter()
+    .collect()
+}
+
+fn read_png(path: &std::path::Path) -> Result<Image, String> {
+    let file =
+        std::fs::File::open(path).map_err(|e| format!("{} を開けない: {}", path.display(), e))?;
+    let (info, mut reader) = png::Decoder::new(file)
+        .read_info()
+        .map_err(|e| format!("{} を読めない: {}", path.display(), e))?;
+    if info.color_type != png::ColorType::RGBA || info.bit_depth != png::BitDepth::Eight {
+        return Err(format!("{} は 8bit の RGBA ではない", path.display()));
+    }
+    let mut rgba = vec![0u8; info.buffer_size()];
+    reader
+        .next_frame(&mut rgba)
+        .map_err(|e| format!("{} を読めない: {}", path.display(), e))?;
+    Ok(Image {
+        width: info.width,
+        height: info.height,
+        rgba: rgba,
+    })
+}
+
+//  異なる画素の数を数え、異なる画素を赤、同じ画素を暗い灰色にした差分画像を作る
+fn compare(expected: &Image, actual: &Image) -> (usize, Vec<u8>) {
+    let mut mismatched = 0;
+    let mut diff = Vec::<u8>::with_capacity(actual.rgba.len());
+    for (e, a) in expected.rgba.chunks(4).zip(actual.rgba.chunks(4)) {
+        let same = e
+            .iter()
+            .zip(a.iter())
+            .all(|(e, a)| (*e as i32 - *a as i32).abs() <= CHANNEL_TOLERANCE as i32);
+        if same {
+            let gray = ((a[0] as u32 + a[1] as u32 + a[2] as u32) / 12) as u8;
+            diff.extend_from_slice(&[gray, gray, gray, 255]);
+        } else {
+            mismatched += 1;
+            diff.extend_from_slice(&[255, 0, 0, 255]);
+        }
+    }
+    (mismatched, diff)
+}
+
+//  サンプルを実行して最初のフレームを読み出す
+fn render(name: &str, bin: &str, args: &[&str]) -> Image {
+    let output = output_path(name, "");
+    let _ = std::fs::remove_file(&output);
+    let shader_dir = repository_dir().join("shaders");
+    let status = std::process::Command::new(bin)
+        .arg("--headless")
+        .arg("--screenshot")
+        .arg(&output)
+        .arg("--shader")
+        .arg(format!("{}/", shader_dir.display()))
+        .arg("--width")
+        .arg(WIDTH.to_string())
+        .arg("--height")
+        .arg(HEIGHT.to_string())
+        .args(args)
+        .status()
+        .expect("サンプルを実行できない");
+    assert!(status.success(), "{} が異常終了した", name);
+    read_png(&output).expect("描画結果を保存できていない")
+}
+
+fn check_golden(name: &str, bin: &str, args: &[&str]) {
+    let actual = render(name, bin, args);
+    let golden = golden_path(name);
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(golden.parent().unwrap()).unwrap();
+        screenshot::write_png(
+            golden.to_str().unwrap(),
+            actual.width,
+            actual.height,
+            &actual.rgba,
+        )
+        .unwrap();
+        return;
+    }
+
+    let expected = match read_png(&golden) {
+        Ok(t) => t,
+        Err(e) => panic!("{} (UPDATE_GOLDEN=1 で作成できる)", e),
+    };
+    assert_eq!(
+        (expected.width, expected.height),
+        (actual.width, actual.height),
+        "{} の大きさがリファレンス画像と異なる",
+        name
+    );
+
+    let (mismatched, diff) = compare(&expected, &actual);
+    let total = (actual.width * actual.height) as usize;
+    if mismatched as f64 > total as f64 * PIXEL_TOLERANCE {
+        let diff_path = output_path(name, ".diff");
+        screenshot::write_png(
+            diff_path.to_str().unwrap(),
+            actual.width,
+            actual.height,
+            &diff,
+        )
+        .unwrap();
+        panic!(
+            "{} の {} / {} 画素がリファレンス画像と異なる (描画結果: {}, 差分: {})",
+            name,
+            mismatched,
+            total,
+            output_path(name, "").display(),
+            diff_path.display()
+        );
+    }
+}
+
+#[test]
+#[cfg_attr(not(feature = "golden"), ignore)]
+fn a15_draw_triangle() {
+    check_golden("a15_draw", env!("CARGO_BIN_EXE_a15_draw"), &[]);
+}
+
+#[test]
+#[cfg_attr(not(feature = "golden"), ignore)]
+fn a15_draw_collada_mesh() {
+    let mesh: std::path::PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "mesh", "quad.dae"]
+        .iter()
+        .collect();
+    check_golden(
+        "a15_draw_quad_dae",
+        env!("CARGO_BIN_EXE_a15_draw"),
+        &["--mesh", mesh.to_str().unwrap()],
+    );
+}
+
+#[test]
+#[cfg_attr(not(feature = "golden"), ignore)]
+fn a15_draw_obj_mesh() {
+    let mesh: std::path::PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "mesh", "quad.obj"]
+        .iter()
+        .collect();
+    check_golden(
+        "a15_draw_quad_obj",
+        env!("CARGO_BIN_EXE_a15_draw"),
+        &["--mesh", mesh.to_str().unwrap()],
+    );
+}
+
+#[test]
+#[cfg_attr(not(feature = "golden"), ignore)]
+fn a15_draw_gltf_mesh() {
+    let mesh: std::path::PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "mesh", "quad.gltf"]
+        .iter()
+        .collect();
+    check_golden(
+        "a15_draw_quad_gltf",
+        env!("CARGO_BIN_EXE_a15_draw"),
+        &["--mesh", mesh.to_str().unwrap()],
+    );
+}
+
+#[test]
+fn compare_tolerates_small_differences() {
+    let expected = Image {
+        width: 2,
+        height: 1,
+        rgba: vec![100, 100, 100, 255, 0, 0, 0, 255],
+    };
+    let actual = Image {
+        width: 2,
+        height: 1,
+        rgba: vec![102, 99, 100, 255, 0, 0, 200, 255],
+    };
+    let (mismatched, diff) = compare(&expected, &actual);
+    assert_eq!(mismatched, 1);
+    assert_eq!(&diff[4..8], &[255, 0, 0, 255]);
+}
@@ -0,0 +1,32 @@
+//  オフセットや大きさをアラインメントの倍数に揃える
+//  アラインメントが 0 か 1 の場合はそのまま返す
+
+pub fn align_up(value: u64, alignment: u64) -> u64 {
+    if alignment <= 1 {
+        value
+    } else {
+        (value + alignment - 1) / alignment * alignment
+    }
+}
+
+pub fn align_down(value: u64, alignment: u64) -> u64 {
+    if alignment <= 1 {
+        value
+    } else {
+        value / alignment * alignment
+    }
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+//  a と b のどちらの倍数でもある最小のアラインメント
+pub fn lcm(a: u64, b: u64) -> u64 {
+    let (a, b) = (a.max(1), b.max(1));
+    a / gcd(a, b) * b
+}
@@ -0,0 +1,489 @@
+//  vk-mem の代わりに使える Rust だけで書いたメモリアロケータ
+//  メモリタイプごとに大きなブロックを vkAllocateMemory で確保し、その中を TLSF で切り分ける
+//  大きなリソース、 dedicated を指定したもの、ドライバが専用のメモリを求めるものはブロックを共有せず単独で確保する
+use crate::align::align_up;
+use crate::error::{self, Error, ErrorContext};
+use crate::memory_type;
+use ash::version::{DeviceV1_0, DeviceV1_1, InstanceV1_0};
+use std::sync::Mutex;
+
+mod tlsf;
+
+pub use tlsf::{ResourceKind, Tlsf};
+
+//  ヒープが小さい場合はヒープの 1/8 をブロックの大きさにする
+const DEFAULT_BLOCK_SIZE: u64 = 64 * 1024 * 1024;
+
+#[derive(Clone, Copy, Default)]
+pub struct AllocatorCreateInfo {
+    //  0 の場合は DEFAULT_BLOCK_SIZE
+    pub block_size: u64,
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct AllocationCreateInfo {
+    //  必ず備えている必要があるフラグ
+    pub required_flags: ash::vk::MemoryPropertyFlags,
+    //  備えているメモリタイプがあればそちらを使うフラグ
+    pub preferred_flags: ash::vk::MemoryPropertyFlags,
+    //  ブロックを共有せず専用の VkDeviceMemory を確保する
+    pub dedicated: bool,
+}
+
+//  確保したメモリの範囲
+//  memory と offset をそのまま vkBindBufferMemory などに渡せる
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Allocation {
+    pub memory: ash::vk::DeviceMemory,
+    pub offset: u64,
+    pub size: u64,
+    pub memory_type_index: u32,
+    block_index: usize,
+}
+
+//  ドライバが専用のメモリを求めたリソース
+//  確保する時に VkMemoryDedicatedAllocateInfo で渡す
+#[derive(Clone, Copy)]
+enum DedicatedResource {
+    Buffer(ash::vk::Buffer),
+    Image(ash::vk::Image),
+}
+
+struct MemoryBlock {
+    memory: ash::vk::DeviceMemory,
+    tlsf: Tlsf,
+    dedicated: bool,
+    //  同じ VkDeviceMemory は 1 度しかマップできないので、ブロック全体をマップして数を数える
+    map_count: u32,
+    mapped: *mut u8,
+}
+
+//  mapped はブロックを持つアロケータの Mutex の中でしか触らない
+unsafe impl Send for MemoryBlock {}
+
+pub struct Allocator {
+    device: ash::Device,
+    memory_properties: ash::vk::PhysicalDeviceMemoryProperties,
+    buffer_image_granularity: u64,
+    non_coherent_atom_size: u64,
+    //  context はインスタンスを 1.1 で作るので、デバイスも 1.1 なら vkGet*MemoryRequirements2 を使える
+    memory_requirements2: bool,
+    block_size: u64,
+    //  メモリタイプごとのブロック
+    //  解放したブロックは None にして、 Allocation が持つ番号をずらさない
+    blocks: Mutex<Vec<Vec<Option<MemoryBlock>>>>,
+}
+
+impl Allocator {
+    pub fn new(
+        instance: &ash::Instance,
+        physical_device: ash::vk::PhysicalDevice,
+        device: &ash::Device,
+        create_info: &AllocatorCreateInfo,
+    ) -> Allocator {
+        let memory_properties =
+            unsafe { instance.get_physical_device_memory_properties(physical_device) };
+        let props = unsafe { instance.get_physical_device_properties(physical_device) };
+        Allocator {
+            device: device.clone(),
+            memory_properties: memory_properties,
+            buffer_image_granularity: props.limits.buffer_image_granularity.max(1),
+            non_coherent_atom_size: props.limits.non_coherent_atom_size.max(1),
+            memory_requirements2: props.api_version >= ash::vk::make_version(1, 1, 0),
+            block_size: if create_info.block_size == 0 {
+                DEFAULT_BLOCK_SIZE
+            } else {
+                create_info.block_size
+            },
+            blocks: Mutex::new(
+                (0..memory_properties.memory_type_count)
+                    .map(|_| vec![])
+                    .collect(),
+            ),
+        }
+    }
+
+    pub fn device(&self) -> &ash::Device {
+        &self.device
+    }
+
+    pub fn memory_properties(&self) -> &ash::vk::PhysicalDeviceMemoryProperties {
+        &self.memory_properties
+    }
+
+    fn block_size(&self, memory_type_index: u32) -> u64 {
+        let heap_index =
+            self.memory_properties.memory_types[memory_type_index as usize].heap_index as usize;
+        let heap_size = self.memory_properties.memory_heaps[heap_index].size;
+        std::cmp::min(self.block_size, heap_size / 8)
+    }
+
+    //  requirements を満たすメモリを確保する
+    //  kind はバッファや LINEAR のイメージなら Linear 、 OPTIMAL のイメージなら Optimal
+    //  メモリが足りない場合は条件を満たす次のメモリタイプで確保し直す
+    pub fn allocate(
+        &self,
+        requirements: &ash::vk::MemoryRequirements,
+        info: &AllocationCreateInfo,
+        kind: ResourceKind,
+    ) -> error::Result<Allocation> {
+        self.allocate_for(requirements, info, kind, None)
+    }
+
+    fn allocate_for(
+        &self,
+        requirements: &ash::vk::MemoryRequirements,
+        info: &AllocationCreateInfo,
+        kind: ResourceKind,
+        dedicated_resource: Option<DedicatedResource>,
+    ) -> error::Result<Allocation> {
+        let candidates = memory_type::memory_type_candidates(
+            &self.memory_properties,
+            &memory_type::MemoryTypeRequest {
+                type_bits: requirements.memory_type_bits,
+                required_flags: info.required_flags,
+                preferred_flags: info.preferred_flags,
+                size: requirements.size,
+            },
+            None,
+        )
+        .context("メモリタイプを選べない")?;
+        let mut result = ash::vk::Result::ERROR_OUT_OF_DEVICE_MEMORY;
+        for memory_type_index in candidates {
+            match self.allocate_from_type(
+                memory_type_index,
+                requirements,
+                info,
+                kind,
+                dedicated_resource,
+            ) {
+                Ok(allocation) => return Ok(allocation),
+                //  メモリが足りない場合だけ次のメモリタイプを試す
+                Err(e @ ash::vk::Result::ERROR_OUT_OF_DEVICE_MEMORY)
+                | Err(e @ ash::vk::Result::ERROR_OUT_OF_HOST_MEMORY) => {
+                    result = e;
+                    continue;
+                }
+                Err(e) => {
+                    result = e;
+                    break;
+                }
+            }
+        }
+        Err(result).context(format!(
+            "{} バイトのメモリを確保できない",
+            requirements.size
+        ))
+    }
+
+    fn allocate_from_type(
+        &self,
+        memory_type_index: u32,
+        requirements: &ash::vk::MemoryRequirements,
+        info: &AllocationCreateInfo,
+        kind: ResourceKind,
+        dedicated_resource: Option<DedicatedResource>,
+    ) -> Result<Allocation, ash::vk::Result> {
+        let block_size = self.block_size(memory_type_index);
+        let mut blocks = self.blocks.lock().unwrap();
+        let blocks = &mut blocks[memory_type_index as usize];
+
+        //  HOST_COHERENT でないメモリはフラッシュと無効化の範囲が nonCoherentAtomSize の倍数になるので、
+        //  隣の確保と同じアトムを共有しないよう位置と大きさを揃える
+        let flags = self.memory_properties.memory_types[memory_type_index as usize].property_flags;
+        let (size, alignment) = if flags.contains(ash::vk::MemoryPropertyFlags::HOST_VISIBLE)
+            && !flags.contains(ash::vk::MemoryPropertyFlags::HOST_COHERENT)
+        {
+            (
+                align_up(requirements.size, self.non_coherent_atom_size),
+                std::cmp::max(requirements.alignment, self.non_coherent_atom_size),
+            )
+        } else {
+            (requirements.size, requirements.alignment)
+        };
+
+        let dedicated =
+            info.dedicated || dedicated_resource.is_some() || requirements.size > block_size / 2;
+        if !dedicated {
+            for (block_index, block) in blocks.iter_mut().enumerate() {
+                if let Some(block) = block.as_mut().filter(|block| !block.dedicated) {
+                    if let Some(offset) = block.tlsf.allocate(size, alignment, kind) {
+                        return Ok(Allocation {
+                            memory: block.memory,
+                            offset: offset,
+                            size: requirements.size,
+                            memory_type_index: memory_type_index,
+                            block_index: block_index,
+                        });
+                    }
+                }
+            }
+        }
+
+        //  収まるブロックが無いので新しく確保する
+        //  専用のメモリはリソースの大きさちょうどで確保し、全体を 1 つの確保に使う
+        let (memory_size, size) = if dedicated {
+            (requirements.size, requirements.size)
+        } else {
+            (block_size, size)
+        };
+        let mut dedicated_info = match dedicated_resource {
+            Some(DedicatedResource::Buffer(buffer)) => {
+                ash::vk::MemoryDedicatedAllocateInfo::builder().buffer(buffer)
+            }
+            Some(DedicatedResource::Image(image)) => {
+                ash::vk::MemoryDedicatedAllocateInfo::builder().image(image)
+            }
+            None => ash::vk::MemoryDedicatedAllocateInfo::builder(),
+        };
+        let mut allocate_info = ash::vk::MemoryAllocateInfo::builder()
+            .allocation_size(memory_size)
+            .memory_type_index(memory_type_index);
+        if dedicated_resource.is_some() {
+            allocate_info = allocate_info.push_next(&mut dedicated_info);
+        }
+        let memory = unsafe { self.device.allocate_memory(&allocate_info, None)? };
+        let mut tlsf = Tlsf::new(memory_size, self.buffer_image_granularity);
+        let offset = tlsf
+            .allocate(size, alignment, kind)
+            .expect("新しいブロックには必ず収まる");
+        let block = MemoryBlock {
+            memory: memory,
+            tlsf: tlsf,
+            dedicated: dedicated,
+            map_count: 0,
+            mapped: std::ptr::null_mut(),
+        };
+        let block_index = match blocks.iter().position(|block| block.is_none()) {
+            Some(i) => {
+                blocks[i] = Some(block);
+                i
+            }
+            None => {
+                blocks.push(Some(block));
+                blocks.len() - 1
+            }
+        };
+        Ok(Allocation {
+            memory: memory,
+            offset: offset,
+            size: requirements.size,
+            memory_type_index: memory_type_index,
+            block_index: block_index,
+        })
+    }
+
+    //  空になったブロックは解放するが、メモリタイプごとに共有のブロックを 1 つは残す
+    pub fn free(&self, allocation: &Allocation) {
+        if allocation.memory == ash::vk::DeviceMemory::null() {
+            return;
+        }
+        let mut blocks = self.blocks.lock().unwrap();
+        let blocks = &mut blocks[allocation.memory_type_index as usize];
+        let shared_blocks = blocks
+            .iter()
+            .filter(|block| block.as_ref().map_or(false, |block| !block.dedicated))
+            .count();
+        let slot = &mut blocks[allocation.block_index];
+        let block = slot.as_mut().expect("解放済みのブロックのメモリを解放した");
+        block.tlsf.free(allocation.offset);
+        if block.tlsf.is_empty() && (block.dedicated || shared_blocks > 1) {
+            unsafe {
+                if block.map_count > 0 {
+                    self.device.unmap_memory(block.memory);
+                }
+                self.device.free_memory(block.memory, None);
+            }
+            *slot = None;
+        }
+    }
+
+    //  確保した範囲の先頭のポインタを返す
+    //  HOST_VISIBLE のメモリタイプから確保したものだけマップできる
+    pub fn map_memory(&self, allocation: &Allocation) -> error::Result<*mut u8> {
+        if !self.memory_properties.memory_types[allocation.memory_type_index as usize]
+            .property_flags
+            .contains(ash::vk::MemoryPropertyFlags::HOST_VISIBLE)
+        {
+            return Err(Error::from("HOST_VISIBLE ではないメモリはマップできない"));
+        }
+        let mut blocks = self.blocks.lock().unwrap();
+        let block = blocks[allocation.memory_type_index as usize][allocation.block_index]
+            .as_mut()
+            .context("解放済みのメモリはマップできない")?;
+        if block.map_count == 0 {
+            block.mapped = unsafe {
+                self.device.map_memory(
+                    block.memory,
+                    0,
+                    ash::vk::WHOLE_SIZE,
+                    ash::vk::MemoryMapFlags::empty(),
+                )
+            }
+            .context("メモリをマップできない")? as *mut u8;
+        }
+        block.map_count += 1;
+        Ok(unsafe { block.mapped.add(allocation.offset as usize) })
+    }
+
+    pub fn unmap_memory(&self, allocation: &Allocation) {
+        let mut blocks = self.blocks.lock().unwrap();
+        if let Some(block) = blocks[allocation.memory_type_index as usize][allocation.block_index]
+            .as_mut()
+            .filter(|block| block.map_count > 0)
+        {
+            block.map_count -= 1;
+            if block.map_count == 0 {
+                unsafe { self.device.unmap_memory(block.memory) };
+                block.mapped = std::ptr::null_mut();
+            }
+        }
+    }
+
+    //  メモリ要件と、ドライバが専用のメモリを求める (または勧める) 場合はそのリソース
+    fn buffer_requirements(
+        &self,
+        buffer: ash::vk::Buffer,
+    ) -> (ash::vk::MemoryRequirements, Option<DedicatedResource>) {
+        if !self.memory_requirements2 {
+            let requirements = unsafe { self.device.get_buffer_memory_requirements(buffer) };
+            return (requirements, None);
+        }
+        let mut dedicated = ash::vk::MemoryDedicatedRequirements::default();
+        let mut requirements = ash::vk::MemoryRequirements2::builder()
+            .push_next(&mut dedicated)
+            .build();
+        unsafe {
+            self.device.get_buffer_memory_requirements2(
+                &ash::vk::BufferMemoryRequirementsInfo2::builder()
+                    .buffer(buffer)
+                    .build(),
+                &mut requirements,
+            )
+        };
+        let dedicated_resource = if dedicated.requires_dedicated_allocation != ash::vk::FALSE
+            || dedicated.prefers_dedicated_allocation != ash::vk::FALSE
+        {
+            Some(DedicatedResource::Buffer(buffer))
+        } else {
+            None
+        };
+        (requirements.memory_requirements, dedicated_resource)
+    }
+
+    fn image_requirements(
+        &self,
+        image: ash::vk::Image,
+    ) -> (ash::vk::MemoryRequirements, Option<DedicatedResource>) {
+        if !self.memory_requirements2 {
+            let requirements = unsafe { self.device.get_image_memory_requirements(image) };
+            return (requirements, None);
+        }
+        let mut dedicated = ash::vk::MemoryDedicatedRequirements::default();
+        let mut requirements = ash::vk::MemoryRequirements2::builder()
+            .push_next(&mut dedicated)
+            .build();
+        unsafe {
+            self.device.get_image_memory_requirements2(
+                &ash::vk::ImageMemoryRequirementsInfo2::builder()
+                    .image(image)
+                    .build(),
+                &mut requirements,
+            )
+        };
+        let dedicated_resource = if dedicated.requires_dedicated_allocation != ash::vk::FALSE
+            || dedicated.prefers_dedicated_allocation != ash::vk::FALSE
+        {
+            Some(DedicatedResource::Image(image))
+        } else {
+            None
+        };
+        (requirements.memory_requirements, dedicated_resource)
+    }
+
+    //  バッファを作成し、メモリを確保して割り当てる
+    pub fn create_buffer(
+        &self,
+        create_info: &ash::vk::BufferCreateInfo,
+        info: &AllocationCreateInfo,
+    ) -> error::Result<(ash::vk::Buffer, Allocation)> {
+        let buffer = unsafe { self.device.create_buffer(create_info, None) }
+            .context("バッファを作成できない")?;
+        let (requirements, dedicated_resource) = self.buffer_requirements(buffer);
+        let allocation = match self.allocate_for(
+            &requirements,
+            info,
+            ResourceKind::Linear,
+            dedicated_resource,
+        ) {
+            Ok(t) => t,
+            Err(e) => {
+                unsafe { self.device.destroy_buffer(buffer, None) };
+                return Err(e);
+            }
+        };
+        if let Err(e) = unsafe {
+            self.device
+                .bind_buffer_memory(buffer, allocation.memory, allocation.offset)
+        } {
+            self.destroy_buffer(buffer, &allocation);
+            return Err(e).context("バッファにメモリを割り当てられない");
+        }
+        Ok((buffer, allocation))
+    }
+
+    //  イメージを作成し、メモリを確保して割り当てる
+    pub fn create_image(
+        &self,
+        create_info: &ash::vk::ImageCreateInfo,
+        info: &AllocationCreateInfo,
+    ) -> error::Result<(ash::vk::Image, Allocation)> {
+        let image = unsafe { self.device.create_image(create_info, None) }
+            .context("イメージを作成できない")?;
+        let (requirements, dedicated_resource) = self.image_requirements(image);
+        let kind = if create_info.tiling == ash::vk::ImageTiling::LINEAR {
+            ResourceKind::Linear
+        } else {
+            ResourceKind::Optimal
+        };
+        let allocation = match self.allocate_for(&requirements, info, kind, dedicated_resource) {
+            Ok(t) => t,
+            Err(e) => {
+                unsafe { self.device.destroy_image(image, None) };
+                return Err(e);
+            }
+        };
+        if let Err(e) = unsafe {
+            self.device
+                .bind_image_memory(image, allocation.memory, allocation.offset)
+        } {
+            self.destroy_image(image, &allocation);
+            return Err(e).context("イメージにメモリを割り当てられない");
+        }
+        Ok((image, allocation))
+    }
+
+    pub fn destroy_buffer(&self, buffer: ash::vk::Buffer, allocation: &Allocation) {
+        unsafe { self.device.destroy_buffer(buffer, None) };
+        self.free(allocation);
+    }
+
+    pub fn destroy_image(&self, image: ash::vk::Image, allocation: &Allocation) {
+        unsafe { self.device.destroy_image(image, None) };
+        self.free(allocation);
+    }
+}
+
+impl Drop for Allocator {
+    fn drop(&mut self) {
+        let blocks = self.blocks.get_mut().unwrap();
+        for block in blocks.iter_mut().flatten().filter_map(|block| block.take()) {
+            unsafe {
+                if block.map_count > 0 {
+                    self.device.unmap_memory(block.memory);
+                }
+                self.device.free_memory(block.memory, None);
+            }
+        }
+    }
+}
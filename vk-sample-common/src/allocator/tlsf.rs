@@ -0,0 +1,254 @@
+//  1 つの VkDeviceMemory の中の範囲を管理する TLSF (Two-Level Segregated Fit)
+//  デバイスには触れないので、オフセットと大きさだけでテストできる
+use crate::align::align_up;
+use std::collections::{BTreeMap, BTreeSet};
+
+//  第 2 レベルの分割数は 2^SL_BITS
+const SL_BITS: u32 = 4;
+const SL_COUNT: usize = 1 << SL_BITS;
+//  SL_COUNT 未満の大きさは第 1 レベルの 0 番にまとめる
+const FL_COUNT: usize = 64 - SL_BITS as usize + 1;
+
+//  bufferImageGranularity で隔てる必要があるリソースの種類
+//  バッファと LINEAR のイメージは Linear 、 OPTIMAL のイメージは Optimal
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResourceKind {
+    Linear,
+    Optimal,
+}
+
+#[derive(Clone, Copy)]
+struct Block {
+    size: u64,
+    //  空きブロックは None
+    kind: Option<ResourceKind>,
+}
+
+pub struct Tlsf {
+    size: u64,
+    granularity: u64,
+    //  オフセットの順に並べた全てのブロック
+    //  隣り合う空きブロックは常に結合しておく
+    blocks: BTreeMap<u64, Block>,
+    //  大きさの区分ごとの空きブロックのオフセット
+    free_lists: Vec<BTreeSet<u64>>,
+    //  空きブロックがある区分のビットマップ
+    fl_bitmap: u64,
+    sl_bitmaps: [u32; FL_COUNT],
+    allocated: u64,
+    allocation_count: usize,
+}
+
+//  大きさから第 1 レベルと第 2 レベルの番号を求める
+fn mapping(size: u64) -> (usize, usize) {
+    if size < SL_COUNT as u64 {
+        (0, size as usize)
+    } else {
+        let fl = 63 - size.leading_zeros();
+        let sl = (size >> (fl - SL_BITS)) as usize & (SL_COUNT - 1);
+        ((fl - SL_BITS + 1) as usize, sl)
+    }
+}
+
+impl Tlsf {
+    //  granularity は bufferImageGranularity で、 2 の累乗
+    pub fn new(size: u64, granularity: u64) -> Tlsf {
+        assert!(
+            granularity.is_power_of_two(),
+            "granularity は 2 の累乗でなければならない"
+        );
+        let mut tlsf = Tlsf {
+            size: size,
+            granularity: granularity,
+            blocks: BTreeMap::new(),
+            free_lists: vec![BTreeSet::new(); FL_COUNT * SL_COUNT],
+            fl_bitmap: 0,
+            sl_bitmaps: [0; FL_COUNT],
+            allocated: 0,
+            allocation_count: 0,
+        };
+        if size > 0 {
+            tlsf.insert_free(0, size);
+        }
+        tlsf
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    //  確保済みの大きさの合計 (アラインメントで空いた隙間は含まない)
+    pub fn allocated_size(&self) -> u64 {
+        self.allocated
+    }
+
+    pub fn allocation_count(&self) -> usize {
+        self.allocation_count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.allocation_count == 0
+    }
+
+    //  最も大きい空きブロックの大きさ
+    pub fn largest_free_block(&self) -> u64 {
+        self.blocks
+            .values()
+            .filter(|block| block.kind.is_none())
+            .map(|block| block.size)
+            .max()
+            .unwrap_or(0)
+    }
+
+    //  size バイトを alignment に揃えて確保し、オフセットを返す
+    //  収まる空きブロックが無い場合は None
+    pub fn allocate(&mut self, size: u64, alignment: u64, kind: ResourceKind) -> Option<u64> {
+        if size == 0 || size > self.size {
+            return None;
+        }
+        let (mut fl, mut sl) = mapping(size);
+        loop {
+            //  同じ第 1 レベルで sl 以上の区分、無ければより大きい第 1 レベルの最小の区分を探す
+            let sl_map = if sl < SL_COUNT {
+                self.sl_bitmaps[fl] & (!0u32 << sl)
+            } else {
+                0
+            };
+            if sl_map != 0 {
+                sl = sl_map.trailing_zeros() as usize;
+            } else {
+                let fl_map = if fl + 1 < FL_COUNT {
+                    self.fl_bitmap & (!0u64 << (fl + 1))
+                } else {
+                    0
+                };
+                if fl_map == 0 {
+                    return None;
+                }
+                fl = fl_map.trailing_zeros() as usize;
+                sl = self.sl_bitmaps[fl].trailing_zeros() as usize;
+            }
+
+            //  最初の区分には size より小さいブロックもあり、
+            //  アラインメントや granularity で収まらない事もあるので 1 つずつ確かめる
+            let candidates = self.free_lists[fl * SL_COUNT + sl]
+                .iter()
+                .copied()
+                .collect::<Vec<_>>();
+            for offset in candidates {
+                if let Some(start) = self.fit(offset, size, alignment, kind) {
+                    self.split(offset, start, size, kind);
+                    return Some(start);
+                }
+            }
+            sl += 1;
+        }
+    }
+
+    //  allocate で返したオフセットの範囲を解放し、前後の空きブロックと結合する
+    pub fn free(&mut self, offset: u64) {
+        let block = match self.blocks.get(&offset) {
+            Some(block) if block.kind.is_some() => *block,
+            _ => panic!("{} は確保されていない", offset),
+        };
+        self.blocks.remove(&offset);
+        self.allocated -= block.size;
+        self.allocation_count -= 1;
+
+        let mut start = offset;
+        let mut end = offset + block.size;
+        if let Some((&prev_offset, prev)) = self.blocks.range(..offset).next_back() {
+            if prev.kind.is_none() {
+                let prev_size = prev.size;
+                self.remove_free(prev_offset, prev_size);
+                start = prev_offset;
+            }
+        }
+        if let Some(next) = self.blocks.get(&end).copied() {
+            if next.kind.is_none() {
+                self.remove_free(end, next.size);
+                end += next.size;
+            }
+        }
+        self.insert_free(start, end - start);
+    }
+
+    //  空きブロック offset に size バイトを置ける場合は開始位置を返す
+    fn fit(&self, offset: u64, size: u64, alignment: u64, kind: ResourceKind) -> Option<u64> {
+        let block_end = offset + self.blocks[&offset].size;
+        let mut start = align_up(offset, alignment);
+        //  空きブロックは結合してあるので、直前のブロックは確保済みか存在しない
+        if let Some((&prev_offset, prev)) = self.blocks.range(..offset).next_back() {
+            if self.conflicts(prev.kind, kind, prev_offset + prev.size - 1, start) {
+                start = align_up(start, self.granularity);
+            }
+        }
+        let end = start.checked_add(size)?;
+        if end > block_end {
+            return None;
+        }
+        if let Some(next) = self.blocks.get(&block_end) {
+            if self.conflicts(next.kind, kind, end - 1, block_end) {
+                return None;
+            }
+        }
+        Some(start)
+    }
+
+    //  種類が異なるリソースが同じ granularity のページに入る場合は true
+    fn conflicts(&self, other: Option<ResourceKind>, kind: ResourceKind, a: u64, b: u64) -> bool {
+        match other {
+            Some(other) => other != kind && a / self.granularity == b / self.granularity,
+            None => false,
+        }
+    }
+
+    //  空きブロック offset から start..start+size を切り出し、前後の残りを空きブロックに戻す
+    fn split(&mut self, offset: u64, start: u64, size: u64, kind: ResourceKind) {
+        let block_size = self.blocks[&offset].size;
+        self.remove_free(offset, block_size);
+        if start > offset {
+            self.insert_free(offset, start - offset);
+        }
+        self.blocks.insert(
+            start,
+            Block {
+                size: size,
+                kind: Some(kind),
+            },
+        );
+        let end = start + size;
+        if end < offset + block_size {
+            self.insert_free(end, offset + block_size - end);
+        }
+        self.allocated += size;
+        self.allocation_count += 1;
+    }
+
+    fn insert_free(&mut self, offset: u64, size: u64) {
+        self.blocks.insert(
+            offset,
+            Block {
+                size: size,
+                kind: None,
+            },
+        );
+        let (fl, sl) = mapping(size);
+        self.free_lists[fl * SL_COUNT + sl].insert(offset);
+        self.fl_bitmap |= 1 << fl;
+        self.sl_bitmaps[fl] |= 1 << sl;
+    }
+
+    fn remove_free(&mut self, offset: u64, size: u64) {
+        self.blocks.remove(&offset);
+        let (fl, sl) = mapping(size);
+        let list = &mut self.free_lists[fl * SL_COUNT + sl];
+        list.remove(&offset);
+        if list.is_empty() {
+            self.sl_bitmaps[fl] &= !(1 << sl);
+            if self.sl_bitmaps[fl] == 0 {
+                self.fl_bitmap &= !(1 << fl);
+            }
+        }
+    }
+}
@@ -0,0 +1,261 @@
+//  メッシュを見るためのカメラ
+use crate::mesh::Aabb;
+use crate::PushConstants;
+
+//  視野角の既定値 (縦方向、ラジアン)
+const DEFAULT_FOV_Y: f32 = std::f32::consts::FRAC_PI_4;
+
+pub struct Camera {
+    pub eye: nalgebra_glm::Vec3,
+    pub target: nalgebra_glm::Vec3,
+    pub up: nalgebra_glm::Vec3,
+    pub fov_y: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl Camera {
+    //  バウンディングボックスを囲む球が画面に収まるよう、+Z 側から中心を見る
+    //  縦長の画面では横方向の視野角で距離を決める
+    pub fn framing(bounds: &Aabb, aspect: f32) -> Camera {
+        let radius = match bounds.radius() {
+            r if r > std::f32::EPSILON => r,
+            _ => 1.0,
+        };
+        let fov_y = DEFAULT_FOV_Y;
+        let fov_x = 2.0 * ((fov_y * 0.5).tan() * aspect).atan();
+        let distance = radius / (fov_y.min(fov_x) * 0.5).sin();
+        let target = bounds.center();
+        Camera {
+            eye: target + nalgebra_glm::vec3(0.0, 0.0, distance),
+            target: target,
+            up: nalgebra_glm::vec3(0.0, 1.0, 0.0),
+            fov_y: fov_y,
+            //  球の手前と奥に少し余裕を持たせる
+            near: (distance - radius) * 0.9,
+            far: (distance + radius) * 1.1,
+        }
+    }
+
+    pub fn view_matrix(&self) -> nalgebra_glm::Mat4 {
+        nalgebra_glm::look_at_rh(&self.eye, &self.target, &self.up)
+    }
+
+    //  Vulkan のクリップ空間 (y が下向き、深度が 0 から 1) への射影
+    //  perspective_rh_zo は y が上向きなので y を反転する
+    pub fn projection_matrix(&self, aspect: f32) -> nalgebra_glm::Mat4 {
+        let flip_y = nalgebra_glm::scale(
+            &nalgebra_glm::identity(),
+            &nalgebra_glm::vec3(1.0, -1.0, 1.0),
+        );
+        flip_y * nalgebra_glm::perspective_rh_zo(aspect, self.fov_y, self.near, self.far)
+    }
+
+    //  simple.vert の world_matrix はモデルの変換だけにして、ビューと射影は projection_matrix にまとめる
+    //  光源はカメラの位置に置く
+    pub fn push_constants(&self, aspect: f32) -> PushConstants {
+        PushConstants::new(
+            nalgebra_glm::identity(),
+            self.projection_matrix(aspect) * self.view_matrix(),
+            self.eye,
+            self.eye,
+        )
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CameraMode {
+    //  注視点の周りを回る
+    Orbit,
+    //  カメラの位置を中心に見回し、WASD で移動する
+    Fly,
+}
+
+//  ドラッグ 1 ピクセルあたりの回転 (ラジアン)
+const ROTATE_SPEED: f32 = 0.005;
+//  スクロール 1 段あたりの距離の倍率
+const ZOOM_SPEED: f32 = 0.9;
+//  真上と真下を向くと up と平行になるので、その手前で止める
+const PITCH_LIMIT: f32 = std::f32::consts::FRAC_PI_2 * 0.99;
+
+//  GLFW のイベントからカメラを動かす
+//  Orbit: 左ドラッグで回転、右か中ドラッグで注視点の移動、スクロールで拡大縮小
+//  Fly: 左ドラッグで見回し、WASD で前後左右、Q と E で上下に移動、Shift で速く動く
+//  Tab でモードを切り替え、R で最初の視点に戻す
+pub struct CameraController {
+    bounds: Aabb,
+    pub mode: CameraMode,
+    target: nalgebra_glm::Vec3,
+    distance: f32,
+    //  R で戻す最初の注視点と距離
+    home_target: nalgebra_glm::Vec3,
+    home_distance: f32,
+    yaw: f32,
+    pitch: f32,
+    cursor: Option<(f64, f64)>,
+    rotating: bool,
+    panning: bool,
+    //  前、後、左、右、下、上
+    moving: [bool; 6],
+    fast: bool,
+}
+
+impl CameraController {
+    pub fn new(bounds: &Aabb, aspect: f32) -> CameraController {
+        let camera = Camera::framing(bounds, aspect);
+        let distance = nalgebra_glm::length(&(camera.eye - camera.target));
+        CameraController {
+            bounds: *bounds,
+            mode: CameraMode::Orbit,
+            target: camera.target,
+            distance: distance,
+            home_target: camera.target,
+            home_distance: distance,
+            yaw: 0.0,
+            pitch: 0.0,
+            cursor: None,
+            rotating: false,
+            panning: false,
+            moving: [false; 6],
+            fast: false,
+        }
+    }
+
+    fn radius(&self) -> f32 {
+        match self.bounds.radius() {
+            r if r > std::f32::EPSILON => r,
+            _ => 1.0,
+        }
+    }
+
+    //  yaw と pitch が 0 のときは -Z 方向を向く
+    fn forward(&self) -> nalgebra_glm::Vec3 {
+        nalgebra_glm::vec3(
+            -self.yaw.sin() * self.pitch.cos(),
+            self.pitch.sin(),
+            -self.yaw.cos() * self.pitch.cos(),
+        )
+    }
+
+    fn right(&self) -> nalgebra_glm::Vec3 {
+        nalgebra_glm::vec3(self.yaw.cos(), 0.0, -self.yaw.sin())
+    }
+
+    fn up(&self) -> nalgebra_glm::Vec3 {
+        nalgebra_glm::cross(&self.right(), &self.forward())
+    }
+
+    fn eye(&self) -> nalgebra_glm::Vec3 {
+        self.target - self.forward() * self.distance
+    }
+
+    pub fn handle_event(&mut self, event: &glfw::WindowEvent) {
+        match event {
+            glfw::WindowEvent::MouseButton(button, action, _) => {
+                let pressed = *action != glfw::Action::Release;
+                match *button {
+                    glfw::MouseButtonLeft => self.rotating = pressed,
+                    glfw::MouseButtonRight | glfw::MouseButtonMiddle => self.panning = pressed,
+                    _ => {}
+                }
+            }
+            glfw::WindowEvent::CursorPos(x, y) => {
+                if let Some((last_x, last_y)) = self.cursor {
+                    self.drag((*x - last_x) as f32, (*y - last_y) as f32);
+                }
+                self.cursor = Some((*x, *y));
+            }
+            glfw::WindowEvent::Scroll(_, y) => {
+                if self.mode == CameraMode::Orbit {
+                    let radius = self.radius();
+                    self.distance = (self.distance * ZOOM_SPEED.powf(*y as f32))
+                        .max(radius * 0.01)
+                        .min(radius * 100.0);
+                }
+            }
+            glfw::WindowEvent::Key(key, _, action, _) => {
+                let pressed = *action != glfw::Action::Release;
+                match *key {
+                    glfw::Key::W => self.moving[0] = pressed,
+                    glfw::Key::S => self.moving[1] = pressed,
+                    glfw::Key::A => self.moving[2] = pressed,
+                    glfw::Key::D => self.moving[3] = pressed,
+                    glfw::Key::Q => self.moving[4] = pressed,
+                    glfw::Key::E => self.moving[5] = pressed,
+                    glfw::Key::LeftShift | glfw::Key::RightShift => self.fast = pressed,
+                    glfw::Key::Tab if *action == glfw::Action::Press => {
+                        self.mode = match self.mode {
+                            CameraMode::Orbit => CameraMode::Fly,
+                            CameraMode::Fly => CameraMode::Orbit,
+                        };
+                    }
+                    glfw::Key::R if *action == glfw::Action::Press => {
+                        self.target = self.home_target;
+                        self.distance = self.home_distance;
+                        self.yaw = 0.0;
+                        self.pitch = 0.0;
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn drag(&mut self, dx: f32, dy: f32) {
+        if self.rotating {
+            //  Fly ではカメラの位置を保ったまま向きを変える
+            let eye = self.eye();
+            self.yaw -= dx * ROTATE_SPEED;
+            self.pitch = (self.pitch - dy * ROTATE_SPEED)
+                .max(-PITCH_LIMIT)
+                .min(PITCH_LIMIT);
+            if self.mode == CameraMode::Fly {
+                self.target = eye + self.forward() * self.distance;
+            }
+        } else if self.panning {
+            //  画面上のカーソルの移動量が注視点の位置での移動量と大体合うようにする
+            let scale = self.distance * (DEFAULT_FOV_Y * 0.5).tan() * 2.0 / 1000.0;
+            self.target = self.target - self.right() * (dx * scale) + self.up() * (dy * scale);
+        }
+    }
+
+    //  押されているキーに従って elapsed 秒分だけ移動する
+    pub fn update(&mut self, elapsed: f32) {
+        if self.mode != CameraMode::Fly {
+            return;
+        }
+        let axes = [
+            self.forward(),
+            -self.forward(),
+            -self.right(),
+            self.right(),
+            -nalgebra_glm::vec3(0.0, 1.0, 0.0),
+            nalgebra_glm::vec3(0.0, 1.0, 0.0),
+        ];
+        let mut direction = nalgebra_glm::vec3(0.0, 0.0, 0.0);
+        for (moving, axis) in self.moving.iter().zip(axes.iter()) {
+            if *moving {
+                direction = direction + axis;
+            }
+        }
+        //  既定では 1 秒でメッシュの半径だけ進む
+        let speed = self.radius() * if self.fast { 4.0 } else { 1.0 };
+        self.target = self.target + direction * (speed * elapsed);
+    }
+
+    //  近い面と遠い面はカメラからメッシュを囲む球までの距離で決める
+    pub fn camera(&self) -> Camera {
+        let eye = self.eye();
+        let radius = self.radius();
+        let center_distance = nalgebra_glm::length(&(eye - self.bounds.center()));
+        Camera {
+            eye: eye,
+            target: self.target,
+            up: nalgebra_glm::vec3(0.0, 1.0, 0.0),
+            fov_y: DEFAULT_FOV_Y,
+            near: ((center_distance - radius) * 0.9).max(radius * 0.001),
+            far: (center_distance + radius) * 1.1,
+        }
+    }
+}
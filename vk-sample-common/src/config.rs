@@ -1,41 +1,282 @@
 use clap::{App, Arg, ArgMatches};
+use std::collections::{BTreeMap, HashMap};
+
+//  --format で選ぶ出力の形式
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
 
 pub struct Configs {
     pub prog_name: String,
     pub list: bool,
-    pub device_index: u32,
+    //  指定しない場合は条件を満たすデバイスから自動で選ぶ
+    pub device_index: Option<u32>,
     pub width: u32,
     pub height: u32,
     pub fullscreen: bool,
     pub validation: bool,
+    pub validation_fatal: bool,
     pub debug_mode: bool,
     pub shader_dir: String,
     pub mesh_file: String,
+    pub headless: bool,
+    pub screenshot: Option<String>,
+    //  終了時に GPU メモリの統計を JSON で書き出すファイル
+    pub memory_report: Option<String>,
+    pub format: OutputFormat,
+}
+
+//  設定できる項目
+//  キーは長いオプション名と同じで、設定ファイルではそのまま、環境変数では VK_SAMPLE_ に大文字で続ける
+const KEYS: &[&str] = &[
+    "list",
+    "device",
+    "validation",
+    "validation-fatal",
+    "width",
+    "height",
+    "fullscreen",
+    "debug",
+    "shader",
+    "mesh",
+    "headless",
+    "screenshot",
+    "memory-report",
+    "format",
+];
+//  値を取らないオプション
+const FLAGS: &[&str] = &[
+    "list",
+    "validation",
+    "validation-fatal",
+    "fullscreen",
+    "debug",
+    "headless",
+];
+const ENV_PREFIX: &str = "VK_SAMPLE_";
+const CONFIG_ENV: &str = "VK_SAMPLE_CONFIG";
+
+fn env_name(key: &str) -> String {
+    format!("{}{}", ENV_PREFIX, key.to_uppercase().replace('-', "_"))
+}
+
+//  キーごとの値と、その値をどこで指定したか
+type Settings = BTreeMap<&'static str, (String, String)>;
+
+fn find_key(name: &str) -> Option<&'static str> {
+    KEYS.iter().find(|key| **key == name).copied()
+}
+
+fn read_config_file(path: &str, settings: &mut Settings) -> Result<(), String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("設定ファイル {} を読む事ができない: {}", path, e))?;
+    let table = match text.parse::<toml::Value>() {
+        Ok(toml::Value::Table(table)) => table,
+        Ok(_) => return Err(format!("設定ファイル {} がテーブルではない", path)),
+        Err(e) => return Err(format!("設定ファイル {} を解析できない: {}", path, e)),
+    };
+    for (name, value) in table.iter() {
+        let key = find_key(name)
+            .ok_or_else(|| format!("設定ファイル {} に不明なキー {} がある", path, name))?;
+        let value = match value {
+            toml::Value::String(s) => s.clone(),
+            toml::Value::Integer(i) => i.to_string(),
+            toml::Value::Boolean(b) => b.to_string(),
+            _ => {
+                return Err(format!(
+                    "設定ファイル {} の {} の値は文字列、整数、真偽値のどれでもない",
+                    path, name
+                ))
+            }
+        };
+        settings.insert(key, (value, format!("設定ファイル {}", path)));
+    }
+    Ok(())
+}
+
+fn parse_bool(key: &str, value: &str, origin: &str) -> Result<bool, String> {
+    match value {
+        "true" | "1" => Ok(true),
+        "false" | "0" => Ok(false),
+        _ => Err(format!(
+            "{} の値 \"{}\" は true か false でなければならない ({})",
+            key, value, origin
+        )),
+    }
+}
+
+fn parse_u32(key: &str, value: &str, origin: &str, min: u32) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(n) if n >= min => Ok(n),
+        _ => Err(format!(
+            "{} の値 \"{}\" は {} 以上の整数でなければならない ({})",
+            key, value, min, origin
+        )),
+    }
 }
 
 impl Configs {
+    //  設定に誤りがある場合はその理由を表示して終了する
+    //  --print-config を指定した場合は最終的な設定を TOML で表示して終了する
     pub fn new(name: &'static str) -> Configs {
-        Configs::from(name, create_app(name).get_matches())
+        let matches = create_app(name).get_matches();
+        let env = std::env::vars().collect::<HashMap<_, _>>();
+        let config = match Configs::from(name, &matches, &env) {
+            Ok(t) => t,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+        };
+        if matches.is_present("print-config") {
+            print!("{}", config.to_toml());
+            std::process::exit(0);
+        }
+        config
     }
 
-    fn from<'a, 'b>(name: &'static str, args: ArgMatches<'a>) -> Configs {
-        Configs {
+    //  既定値、設定ファイル、環境変数、コマンドラインの順に後のものが優先される
+    //  args は create_app で解析したもの
+    pub fn from<'a>(
+        name: &'static str,
+        args: &ArgMatches<'a>,
+        env: &HashMap<String, String>,
+    ) -> Result<Configs, String> {
+        let mut settings = Settings::new();
+
+        if let Some(path) = args
+            .value_of("config")
+            .map(|path| path.to_string())
+            .or_else(|| env.get(CONFIG_ENV).cloned())
+        {
+            read_config_file(&path, &mut settings)?;
+        }
+
+        let mut env_names = env
+            .keys()
+            .filter(|name| name.starts_with(ENV_PREFIX) && name.as_str() != CONFIG_ENV)
+            .collect::<Vec<_>>();
+        env_names.sort();
+        for name in env_names {
+            let key = KEYS
+                .iter()
+                .find(|key| env_name(key) == *name)
+                .ok_or_else(|| format!("環境変数 {} に対応する設定は無い", name))?;
+            settings.insert(key, (env[name].clone(), format!("環境変数 {}", name)));
+        }
+
+        for key in KEYS.iter() {
+            if FLAGS.contains(key) {
+                if args.is_present(key) {
+                    settings.insert(key, (String::from("true"), format!("--{}", key)));
+                }
+            } else if let Some(value) = args.value_of(key) {
+                settings.insert(key, (value.to_string(), format!("--{}", key)));
+            }
+        }
+
+        let flag = |key: &str| match settings.get(key) {
+            Some((value, origin)) => parse_bool(key, value, origin),
+            None => Ok(false),
+        };
+        let number = |key: &str, default: u32, min: u32| match settings.get(key) {
+            Some((value, origin)) => parse_u32(key, value, origin, min),
+            None => Ok(default),
+        };
+        let string = |key: &str| settings.get(key).map(|(value, _)| value.clone());
+
+        let validation_fatal = flag("validation-fatal")?;
+        Ok(Configs {
             prog_name: String::from(name),
-            list: args.is_present("list"),
-            device_index: args.value_of("device").unwrap_or("0").parse().unwrap_or(0),
-            width: args.value_of("width").unwrap_or("0").parse().unwrap_or(0),
-            height: args.value_of("height").unwrap_or("0").parse().unwrap_or(0),
-            fullscreen: args.is_present("fullscreen"),
-            validation: args.is_present("validation"),
-            debug_mode: args.is_present("debug"),
-            shader_dir: args.value_of("shader").unwrap_or("").to_string(),
-            mesh_file: args.value_of("mesh").unwrap_or("").to_string(),
+            list: flag("list")?,
+            device_index: match settings.get("device") {
+                Some((value, origin)) => Some(parse_u32("device", value, origin, 0)?),
+                None => None,
+            },
+            width: number("width", 640, 1)?,
+            height: number("height", 480, 1)?,
+            fullscreen: flag("fullscreen")?,
+            validation: flag("validation")? || validation_fatal,
+            validation_fatal: validation_fatal,
+            debug_mode: flag("debug")?,
+            shader_dir: string("shader").unwrap_or_else(|| String::from("./shaders/")),
+            mesh_file: string("mesh").unwrap_or_default(),
+            headless: flag("headless")?,
+            screenshot: string("screenshot").filter(|path| !path.is_empty()),
+            memory_report: string("memory-report").filter(|path| !path.is_empty()),
+            format: match settings.get("format") {
+                None => OutputFormat::Text,
+                Some((value, _)) if value == "text" => OutputFormat::Text,
+                Some((value, _)) if value == "json" => OutputFormat::Json,
+                Some((value, origin)) => {
+                    return Err(format!(
+                        "format の値 \"{}\" は text か json でなければならない ({})",
+                        value, origin
+                    ))
+                }
+            },
+        })
+    }
+
+    //  設定ファイルとしてそのまま読める形で書き出す
+    pub fn to_toml(&self) -> String {
+        let string = |s: &str| serde_json::to_string(s).unwrap();
+        let mut lines = vec![
+            format!("list = {}", self.list),
+            match self.device_index {
+                Some(index) => format!("device = {}", index),
+                None => String::from("# device は指定されていないので自動で選ぶ"),
+            },
+            format!("validation = {}", self.validation),
+            format!("validation-fatal = {}", self.validation_fatal),
+            format!("width = {}", self.width),
+            format!("height = {}", self.height),
+            format!("fullscreen = {}", self.fullscreen),
+            format!("debug = {}", self.debug_mode),
+            format!("shader = {}", string(&self.shader_dir)),
+            format!("mesh = {}", string(&self.mesh_file)),
+            format!("headless = {}", self.headless),
+        ];
+        if let Some(path) = self.screenshot.as_ref() {
+            lines.push(format!("screenshot = {}", string(path)));
         }
+        if let Some(path) = self.memory_report.as_ref() {
+            lines.push(format!("memory-report = {}", string(path)));
+        }
+        lines.push(format!(
+            "format = {}",
+            string(match self.format {
+                OutputFormat::Text => "text",
+                OutputFormat::Json => "json",
+            })
+        ));
+        lines.join("\n") + "\n"
     }
 }
 
-fn create_app<'a, 'b, S: Into<String>>(name: S) -> App<'a, 'b> {
+pub fn create_app<'a, 'b, S: Into<String>>(name: S) -> App<'a, 'b> {
     App::new(name)
+        .after_help(
+            "Settings are read from the TOML file given by --config (or VK_SAMPLE_CONFIG), \
+             then from VK_SAMPLE_<KEY> environment variables (e.g. VK_SAMPLE_WIDTH=800, \
+             VK_SAMPLE_VALIDATION_FATAL=true), then from the command line; later sources win. \
+             Keys in the file are the long option names.",
+        )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .short("c")
+                .takes_value(true)
+                .value_name("PATH")
+                .help("read settings from a TOML file"),
+        )
+        .arg(
+            Arg::with_name("print-config")
+                .long("print-config")
+                .help("print the effective settings as TOML and exit"),
+        )
         .arg(
             Arg::with_name("list")
                 .long("list")
@@ -46,28 +287,32 @@ fn create_app<'a, 'b, S: Into<String>>(name: S) -> App<'a, 'b> {
             Arg::with_name("device")
                 .long("device")
                 .short("d")
-                .help("use specific device")
-                .default_value("0"),
+                .takes_value(true)
+                .help("use the device at this enumeration index instead of the highest scoring one"),
         )
         .arg(
             Arg::with_name("validation")
                 .long("validation")
                 .short("v")
-                .help("use VK_LAYER_LUNARG_standard_validation"),
+                .help("use VK_LAYER_KHRONOS_validation (or VK_LAYER_LUNARG_standard_validation) and print its messages"),
+        )
+        .arg(
+            Arg::with_name("validation-fatal")
+                .long("validation-fatal")
+                .help("enable validation and abort on the first validation error"),
         )
         .arg(
             Arg::with_name("width")
                 .long("width")
                 .short("w")
-                .help("window width")
-                .default_value("640"),
+                .takes_value(true)
+                .help("window width [default: 640]"),
         )
         .arg(
             Arg::with_name("height")
                 .long("height")
-                .short("h")
-                .help("window height")
-                .default_value("480"),
+                .takes_value(true)
+                .help("window height [default: 480]"),
         )
         .arg(
             Arg::with_name("fullscreen")
@@ -79,20 +324,46 @@ fn create_app<'a, 'b, S: Into<String>>(name: S) -> App<'a, 'b> {
             Arg::with_name("debug")
                 .long("debug")
                 .short("g")
-                .help("debug mode"),
+                .help("debug mode (name Vulkan objects and label command buffers)"),
         )
         .arg(
             Arg::with_name("shader")
                 .long("shader")
                 .short("s")
-                .help("shader directory")
-                .default_value("./shaders/"),
+                .takes_value(true)
+                .help("shader directory [default: ./shaders/]"),
         )
         .arg(
             Arg::with_name("mesh")
                 .long("mesh")
                 .short("m")
-                .help("mesh file")
-                .default_value("../mesh/sponza.dae"),
+                .takes_value(true)
+                .help("mesh file to draw instead of a triangle (.dae, .obj, .gltf, .glb)"),
+        )
+        .arg(
+            Arg::with_name("headless")
+                .long("headless")
+                .help("render to an offscreen image without a window"),
+        )
+        .arg(
+            Arg::with_name("screenshot")
+                .long("screenshot")
+                .takes_value(true)
+                .value_name("PATH")
+                .help("save the first rendered frame as a PNG image"),
+        )
+        .arg(
+            Arg::with_name("memory-report")
+                .long("memory-report")
+                .takes_value(true)
+                .value_name("PATH")
+                .help("write GPU memory statistics and heap budgets as JSON at exit"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(&["text", "json"])
+                .help("output format of the device report [default: text]"),
         )
 }
@@ -0,0 +1,320 @@
+use crate::config::Configs;
+use crate::debug;
+use crate::error::{self, Error, ErrorContext};
+use crate::selection;
+use ash::version::{DeviceV1_0, EntryV1_0, InstanceV1_0};
+use ash::vk::Handle;
+use std::sync::mpsc::Receiver;
+
+pub fn create_instance(
+    entry: &ash::Entry,
+    config: &Configs,
+    extensions: &[std::ffi::CString],
+) -> error::Result<ash::Instance> {
+    let app_name = std::ffi::CString::new(config.prog_name.as_str()).unwrap();
+    let app_info = ash::vk::ApplicationInfo::builder()
+        .application_name(app_name.as_c_str())
+        .application_version(ash::vk::make_version(1, 0, 0))
+        .engine_name(unsafe {
+            std::ffi::CStr::from_ptr("sample_engine\0".as_ptr() as *const std::os::raw::c_char)
+        })
+        .engine_version(ash::vk::make_version(1, 0, 0))
+        .api_version(ash::vk::make_version(1, 1, 0))
+        .build();
+
+    let debug_utils = debug::debug_utils_enabled(entry, config);
+    let mut ext_raw = extensions
+        .iter()
+        .map(|item| item.as_ptr())
+        .collect::<Vec<_>>();
+    if debug_utils {
+        ext_raw.push(ash::extensions::ext::DebugUtils::name().as_ptr());
+    }
+    let layer_names = debug::validation_layers(config.validation, debug::available_layers(entry))
+        .iter()
+        .map(|layer| std::ffi::CString::new(*layer).unwrap())
+        .collect::<Vec<_>>();
+    let layers = layer_names
+        .iter()
+        .map(|item| item.as_ptr())
+        .collect::<Vec<_>>();
+
+    let mut messenger_create_info = debug::messenger_create_info(config);
+    let mut create_info = ash::vk::InstanceCreateInfo::builder()
+        .application_info(&app_info)
+        .enabled_layer_names(&layers)
+        .enabled_extension_names(ext_raw.as_slice());
+    if debug_utils {
+        create_info = create_info.push_next(&mut messenger_create_info);
+    }
+
+    unsafe { entry.create_instance(&create_info, None) }.context("インスタンスを作成できない")
+}
+
+pub fn required_instance_extensions(glfw: &glfw::Glfw) -> Vec<std::ffi::CString> {
+    glfw.get_required_instance_extensions()
+        .unwrap_or_default()
+        .iter()
+        .map(|item| std::ffi::CString::new(item.as_str()).unwrap())
+        .collect()
+}
+
+pub fn create_window(
+    glfw: &mut glfw::Glfw,
+    config: &Configs,
+) -> error::Result<(glfw::Window, Receiver<(f64, glfw::WindowEvent)>)> {
+    glfw.window_hint(glfw::WindowHint::ClientApi(glfw::ClientApiHint::NoApi));
+
+    glfw.with_primary_monitor(|glfw, m| {
+        glfw.create_window(
+            config.width,
+            config.height,
+            config.prog_name.as_str(),
+            if config.fullscreen {
+                m.map_or(glfw::WindowMode::Windowed, |m| {
+                    glfw::WindowMode::FullScreen(m)
+                })
+            } else {
+                glfw::WindowMode::Windowed
+            },
+        )
+    })
+    .context("ウィンドウを作成できない")
+}
+
+pub fn create_surface(
+    instance: &ash::Instance,
+    window: &glfw::Window,
+) -> error::Result<ash::vk::SurfaceKHR> {
+    let mut raw_surface: vk_sys::SurfaceKHR = 0;
+    let result = window.create_window_surface(
+        instance.handle().as_raw() as vk_sys::Instance,
+        std::ptr::null(),
+        &mut raw_surface,
+    );
+    if result != 0 {
+        return Err(Error::Vulkan {
+            context: String::from("サーフェスを作成できない"),
+            result: ash::vk::Result::from_raw(result as i32),
+        });
+    }
+    Ok(ash::vk::SurfaceKHR::from_raw(raw_surface))
+}
+
+//  インスタンスの作成からデバイスの作成までをまとめたもの
+//  各サンプルはこれを作った後の手順だけを記述する
+//  ヘッドレスの場合 surface は null になる
+pub struct VulkanContext {
+    pub entry: ash::Entry,
+    pub instance: ash::Instance,
+    //  --validation を指定し、 VK_EXT_debug_utils が使える場合だけ作られる
+    pub debug_messenger: Option<debug::DebugMessenger>,
+    pub surface_loader: ash::extensions::khr::Surface,
+    pub surface: ash::vk::SurfaceKHR,
+    pub physical_device: ash::vk::PhysicalDevice,
+    pub device: ash::Device,
+    pub graphics_queue_index: u32,
+    pub present_queue_index: u32,
+    pub graphics_queue: ash::vk::Queue,
+    pub present_queue: ash::vk::Queue,
+    pub swapchain_loader: ash::extensions::khr::Swapchain,
+    pub debug_names: debug::DebugNames,
+}
+
+impl VulkanContext {
+    pub fn new(
+        glfw: &glfw::Glfw,
+        window: &glfw::Window,
+        config: &Configs,
+        dext: &[&std::ffi::CStr],
+    ) -> error::Result<VulkanContext> {
+        VulkanContext::with_requirements(
+            Some((glfw, window)),
+            config,
+            &selection::Requirements::new(dext),
+        )
+    }
+
+    //  ウィンドウを作らずオフスクリーンに描画する場合に使う
+    pub fn new_headless(
+        config: &Configs,
+        dext: &[&std::ffi::CStr],
+    ) -> error::Result<VulkanContext> {
+        VulkanContext::with_requirements(None, config, &selection::Requirements::new(dext))
+    }
+
+    //  requirements を満たすデバイスのうち --device で指定したもの、指定しない場合は最もスコアが高いものを使う
+    //  window が None の場合はヘッドレスになる
+    pub fn with_requirements(
+        window: Option<(&glfw::Glfw, &glfw::Window)>,
+        config: &Configs,
+        requirements: &selection::Requirements,
+    ) -> error::Result<VulkanContext> {
+        let extensions = match window {
+            Some((glfw, _)) => required_instance_extensions(glfw),
+            None => vec![],
+        };
+        let entry = ash::Entry::new().context("Vulkan をロードできない")?;
+        let instance = create_instance(&entry, config, &extensions)?;
+
+        //  以降で失敗した場合も作成済みのオブジェクトを破棄する
+        let instance = scopeguard::guard(instance, |instance| unsafe {
+            instance.destroy_instance(None);
+        });
+        let debug_messenger = debug::DebugMessenger::new(&entry, &instance, config)?;
+        let debug_messenger = scopeguard::guard(debug_messenger, |debug_messenger| unsafe {
+            if let Some(debug_messenger) = debug_messenger {
+                debug_messenger.destroy();
+            }
+        });
+
+        let surface_loader = ash::extensions::khr::Surface::new(&entry, &*instance);
+        let surface = match window {
+            Some((_, window)) => create_surface(&instance, window)?,
+            None => ash::vk::SurfaceKHR::null(),
+        };
+        let surface = scopeguard::guard(surface, |surface| unsafe {
+            if surface != ash::vk::SurfaceKHR::null() {
+                surface_loader.destroy_surface(surface, None);
+            }
+        });
+
+        //  ウィンドウがある場合はそのサーフェスに表示できるデバイスだけを候補にする
+        let presentable = |device, i| unsafe {
+            surface_loader
+                .get_physical_device_surface_support(device, i, *surface)
+                .unwrap_or(false)
+        };
+        let candidates = selection::evaluate_devices(
+            &instance,
+            requirements,
+            if *surface != ash::vk::SurfaceKHR::null() {
+                Some(&presentable)
+            } else {
+                None
+            },
+        )?;
+        selection::print_candidates(&candidates);
+        let selected = selection::select_device(&candidates, config.device_index)?;
+        println!("{}番目のデバイスを使う", selected);
+
+        let physical_device = candidates[selected].device;
+        let queue_props =
+            unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
+
+        let supported = (0..queue_props.len())
+            .map(|i| {
+                *surface != ash::vk::SurfaceKHR::null()
+                    && unsafe {
+                        surface_loader
+                            .get_physical_device_surface_support(
+                                physical_device,
+                                i as u32,
+                                *surface,
+                            )
+                            .unwrap_or(false)
+                    }
+            })
+            .collect::<Vec<_>>();
+        let graphics_queue_index = (0..queue_props.len()).find(|i| {
+            queue_props[*i]
+                .queue_flags
+                .intersects(ash::vk::QueueFlags::GRAPHICS)
+        });
+        //  グラフィクスキューで表示できる場合はそれを、できない場合は表示できる最初のキューファミリーを使う
+        //  ヘッドレスの場合は表示しないのでグラフィクスキューと同じにする
+        let present_queue_index = match graphics_queue_index {
+            Some(t) if *surface == ash::vk::SurfaceKHR::null() || supported[t] => Some(t),
+            _ => (0..supported.len()).find(|i| supported[*i]),
+        };
+        if graphics_queue_index.is_none() || present_queue_index.is_none() {
+            return Err(Error::from("必要なキューが備わっていない"));
+        }
+        let graphics_queue_index = graphics_queue_index.unwrap() as u32;
+        let present_queue_index = present_queue_index.unwrap() as u32;
+        let eq_queue = graphics_queue_index == present_queue_index;
+
+        let priority = [0.0];
+        let builder = |index| {
+            ash::vk::DeviceQueueCreateInfo::builder()
+                .queue_family_index(index)
+                .queue_priorities(&priority)
+                .build()
+        };
+        let queues = if eq_queue {
+            vec![builder(graphics_queue_index)]
+        } else {
+            vec![builder(graphics_queue_index), builder(present_queue_index)]
+        };
+
+        let dext_raw = requirements
+            .extensions
+            .iter()
+            .map(|item| item.as_ptr())
+            .collect::<Vec<_>>();
+        let features = selection::enabled_features(requirements)?;
+        let device = unsafe {
+            instance.create_device(
+                physical_device,
+                &ash::vk::DeviceCreateInfo::builder()
+                    .queue_create_infos(&queues)
+                    .enabled_extension_names(&dext_raw)
+                    .enabled_features(&features),
+                None,
+            )
+        }
+        .context("デバイスを作成できない")?;
+
+        let graphics_queue = unsafe { device.get_device_queue(graphics_queue_index, 0) };
+        let present_queue = if eq_queue {
+            graphics_queue
+        } else {
+            unsafe { device.get_device_queue(present_queue_index, 0) }
+        };
+
+        let swapchain_loader = ash::extensions::khr::Swapchain::new(&*instance, &device);
+        let debug_names = debug::DebugNames::new(&entry, &instance, &device, config);
+        debug_names.name_object(device.handle(), "device");
+        debug_names.name_object(graphics_queue, "graphics queue");
+        if !eq_queue {
+            debug_names.name_object(present_queue, "present queue");
+        }
+        if *surface != ash::vk::SurfaceKHR::null() {
+            debug_names.name_object(*surface, "window surface");
+        }
+        let surface = scopeguard::ScopeGuard::into_inner(surface);
+        let debug_messenger = scopeguard::ScopeGuard::into_inner(debug_messenger);
+        let instance = scopeguard::ScopeGuard::into_inner(instance);
+
+        Ok(VulkanContext {
+            entry: entry,
+            instance: instance,
+            debug_messenger: debug_messenger,
+            surface_loader: surface_loader,
+            surface: surface,
+            physical_device: physical_device,
+            device: device,
+            graphics_queue_index: graphics_queue_index,
+            present_queue_index: present_queue_index,
+            graphics_queue: graphics_queue,
+            present_queue: present_queue,
+            swapchain_loader: swapchain_loader,
+            debug_names: debug_names,
+        })
+    }
+}
+
+impl Drop for VulkanContext {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_device(None);
+            if self.surface != ash::vk::SurfaceKHR::null() {
+                self.surface_loader.destroy_surface(self.surface, None);
+            }
+            if let Some(debug_messenger) = self.debug_messenger.as_ref() {
+                debug_messenger.destroy();
+            }
+            self.instance.destroy_instance(None);
+        }
+    }
+}
@@ -0,0 +1,243 @@
+//  検証レイヤーの選択と VK_EXT_debug_utils のメッセンジャー
+use crate::config::Configs;
+use crate::error::{self, ErrorContext};
+use ash::version::EntryV1_0;
+
+pub const KHRONOS_VALIDATION: &str = "VK_LAYER_KHRONOS_validation";
+//  古い SDK にしか無い検証レイヤー
+pub const LUNARG_STANDARD_VALIDATION: &str = "VK_LAYER_LUNARG_standard_validation";
+
+//  利用できる検証レイヤーのうち Khronos のものを優先して返す
+//  どちらも無い場合は None を返す
+pub fn validation_layer<S: AsRef<str>, I: IntoIterator<Item = S>>(
+    available: I,
+) -> Option<&'static str> {
+    let available = available
+        .into_iter()
+        .map(|name| name.as_ref().to_string())
+        .collect::<Vec<_>>();
+    [KHRONOS_VALIDATION, LUNARG_STANDARD_VALIDATION]
+        .iter()
+        .find(|layer| available.iter().any(|name| name == *layer))
+        .copied()
+}
+
+//  インスタンスで有効にするレイヤー
+//  validation が true で検証レイヤーが無い場合は警告を出して検証せずに実行する
+pub fn validation_layers<S: AsRef<str>, I: IntoIterator<Item = S>>(
+    validation: bool,
+    available: I,
+) -> Vec<&'static str> {
+    if !validation {
+        return vec![];
+    }
+    match validation_layer(available) {
+        Some(layer) => vec![layer],
+        None => {
+            eprintln!("検証レイヤーが見つからないので検証せずに実行する");
+            vec![]
+        }
+    }
+}
+
+//  vulkano で列挙した検証レイヤーから validation_layers と同じ規則で選ぶ
+#[cfg(feature = "vulkano")]
+pub fn vulkano_validation_layers(validation: bool) -> Vec<&'static str> {
+    if !validation {
+        return vec![];
+    }
+    validation_layers(
+        validation,
+        vulkano::instance::layers_list()
+            .into_iter()
+            .flatten()
+            .map(|layer| layer.name().to_string()),
+    )
+}
+
+pub fn available_layers(entry: &ash::Entry) -> Vec<String> {
+    entry
+        .enumerate_instance_layer_properties()
+        .unwrap_or_default()
+        .iter()
+        .map(|layer| {
+            unsafe { std::ffi::CStr::from_ptr(layer.layer_name.as_ptr()) }
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect()
+}
+
+//  --validation か --debug が指定され、ローダーが VK_EXT_debug_utils を提供している場合に使う
+pub fn debug_utils_enabled(entry: &ash::Entry, config: &Configs) -> bool {
+    (config.validation || config.debug_mode)
+        && entry
+            .enumerate_instance_extension_properties()
+            .unwrap_or_default()
+            .iter()
+            .any(|extension| unsafe {
+                std::ffi::CStr::from_ptr(extension.extension_name.as_ptr())
+                    == ash::extensions::ext::DebugUtils::name()
+            })
+}
+
+//  --validation-fatal を指定した場合にこのアドレスを p_user_data に渡す
+static VALIDATION_FATAL: bool = true;
+
+//  エラーと警告は標準エラー出力に、それ以外は標準出力に書く
+//  p_user_data が true の bool を指す場合はエラーで異常終了する
+//  (コールバックから panic でドライバの中を巻き戻すことはできないので abort する)
+unsafe extern "system" fn debug_callback(
+    message_severity: ash::vk::DebugUtilsMessageSeverityFlagsEXT,
+    message_types: ash::vk::DebugUtilsMessageTypeFlagsEXT,
+    p_callback_data: *const ash::vk::DebugUtilsMessengerCallbackDataEXT,
+    p_user_data: *mut std::os::raw::c_void,
+) -> ash::vk::Bool32 {
+    let message = if p_callback_data.is_null() || (*p_callback_data).p_message.is_null() {
+        std::borrow::Cow::from("")
+    } else {
+        std::ffi::CStr::from_ptr((*p_callback_data).p_message).to_string_lossy()
+    };
+    if message_severity.contains(ash::vk::DebugUtilsMessageSeverityFlagsEXT::ERROR) {
+        eprintln!("[エラー] {:?}: {}", message_types, message);
+        if !p_user_data.is_null() && *(p_user_data as *const bool) {
+            eprintln!("検証レイヤーがエラーを報告したので終了する");
+            std::process::abort();
+        }
+    } else if message_severity.contains(ash::vk::DebugUtilsMessageSeverityFlagsEXT::WARNING) {
+        eprintln!("[警告] {:?}: {}", message_types, message);
+    } else if message_severity.contains(ash::vk::DebugUtilsMessageSeverityFlagsEXT::INFO) {
+        println!("[情報] {:?}: {}", message_types, message);
+    } else {
+        println!("[詳細] {:?}: {}", message_types, message);
+    }
+    ash::vk::FALSE
+}
+
+//  インスタンスの作成と破棄の間のメッセージも受け取れるよう、 InstanceCreateInfo にも繋げる
+//  情報と詳細のメッセージは --debug を指定した場合だけ受け取る
+pub fn messenger_create_info(config: &Configs) -> ash::vk::DebugUtilsMessengerCreateInfoEXT {
+    let severity = if config.debug_mode {
+        ash::vk::DebugUtilsMessageSeverityFlagsEXT::all()
+    } else {
+        ash::vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
+            | ash::vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
+    };
+    ash::vk::DebugUtilsMessengerCreateInfoEXT::builder()
+        .message_severity(severity)
+        .message_type(ash::vk::DebugUtilsMessageTypeFlagsEXT::all())
+        .pfn_user_callback(Some(debug_callback))
+        .user_data(if config.validation_fatal {
+            &VALIDATION_FATAL as *const bool as *mut std::os::raw::c_void
+        } else {
+            std::ptr::null_mut()
+        })
+        .build()
+}
+
+//  インスタンスより先に destroy で破棄する
+pub struct DebugMessenger {
+    pub loader: ash::extensions::ext::DebugUtils,
+    pub messenger: ash::vk::DebugUtilsMessengerEXT,
+}
+
+impl DebugMessenger {
+    //  --validation を指定していないか VK_EXT_debug_utils が無い場合は None を返す
+    pub fn new(
+        entry: &ash::Entry,
+        instance: &ash::Instance,
+        config: &Configs,
+    ) -> error::Result<Option<DebugMessenger>> {
+        if !config.validation || !debug_utils_enabled(entry, config) {
+            return Ok(None);
+        }
+        let loader = ash::extensions::ext::DebugUtils::new(entry, instance);
+        let messenger =
+            unsafe { loader.create_debug_utils_messenger(&messenger_create_info(config), None) }
+                .context("デバッグメッセンジャーを作成できない")?;
+        Ok(Some(DebugMessenger {
+            loader: loader,
+            messenger: messenger,
+        }))
+    }
+
+    pub unsafe fn destroy(&self) {
+        self.loader
+            .destroy_debug_utils_messenger(self.messenger, None);
+    }
+}
+
+//  --debug を指定した場合に Vulkan のオブジェクトに名前を付け、コマンドバッファの区間にラベルを付ける
+//  検証レイヤーのメッセージや RenderDoc のキャプチャにハンドルの値の代わりに名前が出る
+//  指定していない場合や VK_EXT_debug_utils が無い場合は何もしない
+pub struct DebugNames {
+    loader: Option<ash::extensions::ext::DebugUtils>,
+    device: ash::vk::Device,
+}
+
+impl DebugNames {
+    pub fn new(
+        entry: &ash::Entry,
+        instance: &ash::Instance,
+        device: &ash::Device,
+        config: &Configs,
+    ) -> DebugNames {
+        DebugNames {
+            loader: if config.debug_mode && debug_utils_enabled(entry, config) {
+                Some(ash::extensions::ext::DebugUtils::new(entry, instance))
+            } else {
+                None
+            },
+            device: device.handle(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.loader.is_some()
+    }
+
+    pub fn name_object<T: ash::vk::Handle>(&self, handle: T, name: &str) {
+        if let Some(loader) = self.loader.as_ref() {
+            let name = to_cstring(name);
+            unsafe {
+                //  名前を付けられなくても描画には影響しないので失敗は無視する
+                let _ = loader.debug_utils_set_object_name(
+                    self.device,
+                    &ash::vk::DebugUtilsObjectNameInfoEXT::builder()
+                        .object_type(T::TYPE)
+                        .object_handle(handle.as_raw())
+                        .object_name(&name)
+                        .build(),
+                );
+            }
+        }
+    }
+
+    //  end_label と対にして使う
+    pub fn begin_label(&self, command_buffer: ash::vk::CommandBuffer, name: &str) {
+        if let Some(loader) = self.loader.as_ref() {
+            let name = to_cstring(name);
+            unsafe {
+                loader.cmd_begin_debug_utils_label(
+                    command_buffer,
+                    &ash::vk::DebugUtilsLabelEXT::builder()
+                        .label_name(&name)
+                        .build(),
+                );
+            }
+        }
+    }
+
+    pub fn end_label(&self, command_buffer: ash::vk::CommandBuffer) {
+        if let Some(loader) = self.loader.as_ref() {
+            unsafe {
+                loader.cmd_end_debug_utils_label(command_buffer);
+            }
+        }
+    }
+}
+
+//  途中に NUL がある場合はそこまでを名前にする
+fn to_cstring(name: &str) -> std::ffi::CString {
+    std::ffi::CString::new(name.split('\0').next().unwrap_or("")).unwrap()
+}
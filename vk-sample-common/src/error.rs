@@ -0,0 +1,219 @@
+//  サンプル共通のエラー
+//  何をしようとして失敗したか (context) と、原因になったエラーを持つ
+//  main から返すと原因を辿って表示し、終了コード 1 で終わる
+use std::fmt;
+
+pub enum Error {
+    Vulkan {
+        context: String,
+        result: ash::vk::Result,
+    },
+    Loader {
+        context: String,
+        source: ash::LoadingError,
+    },
+    Glfw {
+        context: String,
+        source: glfw::InitError,
+    },
+    Io {
+        context: String,
+        source: std::io::Error,
+    },
+    //  vk_mem のエラーは std::error::Error を実装していないので、表示する時に context に続けて書く
+    #[cfg(feature = "vk-mem")]
+    Allocator {
+        context: String,
+        source: vk_mem::Error,
+    },
+    //  vulkano のエラーは種類が多いので、まとめて原因として持つ
+    #[cfg(feature = "vulkano")]
+    Vulkano {
+        context: String,
+        source: Box<dyn std::error::Error>,
+    },
+    //  原因になったエラーが無いもの
+    Message(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Vulkan { context, .. }
+            | Error::Loader { context, .. }
+            | Error::Glfw { context, .. }
+            | Error::Io { context, .. } => write!(f, "{}", context),
+            #[cfg(feature = "vk-mem")]
+            Error::Allocator { context, source } => write!(f, "{}: {}", context, source),
+            #[cfg(feature = "vulkano")]
+            Error::Vulkano { context, .. } => write!(f, "{}", context),
+            Error::Message(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+//  main から返した場合は Debug で表示されるので、原因を 1 行ずつ並べる
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)?;
+        let mut source = std::error::Error::source(self);
+        if source.is_some() {
+            write!(f, "\n\n原因:")?;
+        }
+        while let Some(cause) = source {
+            write!(f, "\n    {}", cause)?;
+            source = cause.source();
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Vulkan { result, .. } => Some(result),
+            Error::Loader { source, .. } => Some(source),
+            Error::Glfw { source, .. } => Some(source),
+            Error::Io { source, .. } => Some(source),
+            #[cfg(feature = "vk-mem")]
+            Error::Allocator { .. } => None,
+            #[cfg(feature = "vulkano")]
+            Error::Vulkano { source, .. } => Some(source.as_ref()),
+            Error::Message(_) => None,
+        }
+    }
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Error {
+        Error::Message(message)
+    }
+}
+
+impl From<&str> for Error {
+    fn from(message: &str) -> Error {
+        Error::Message(message.to_string())
+    }
+}
+
+//  原因になったエラーに、何をしようとしていたかを付けて Error にする
+pub trait ErrorContext<T> {
+    fn context<C: Into<String>>(self, context: C) -> Result<T>;
+}
+
+impl<T> ErrorContext<T> for std::result::Result<T, ash::vk::Result> {
+    fn context<C: Into<String>>(self, context: C) -> Result<T> {
+        self.map_err(|result| Error::Vulkan {
+            context: context.into(),
+            result: result,
+        })
+    }
+}
+
+impl<T> ErrorContext<T> for std::result::Result<T, ash::InstanceError> {
+    fn context<C: Into<String>>(self, context: C) -> Result<T> {
+        self.map_err(|e| match e {
+            ash::InstanceError::VkError(result) => Error::Vulkan {
+                context: context.into(),
+                result: result,
+            },
+            ash::InstanceError::LoadError(names) => Error::Message(format!(
+                "{}: {} を読み込めない",
+                context.into(),
+                names.join(", ")
+            )),
+        })
+    }
+}
+
+impl<T> ErrorContext<T> for std::result::Result<T, ash::LoadingError> {
+    fn context<C: Into<String>>(self, context: C) -> Result<T> {
+        self.map_err(|source| Error::Loader {
+            context: context.into(),
+            source: source,
+        })
+    }
+}
+
+impl<T> ErrorContext<T> for std::result::Result<T, glfw::InitError> {
+    fn context<C: Into<String>>(self, context: C) -> Result<T> {
+        self.map_err(|source| Error::Glfw {
+            context: context.into(),
+            source: source,
+        })
+    }
+}
+
+impl<T> ErrorContext<T> for std::result::Result<T, std::io::Error> {
+    fn context<C: Into<String>>(self, context: C) -> Result<T> {
+        self.map_err(|source| Error::Io {
+            context: context.into(),
+            source: source,
+        })
+    }
+}
+
+#[cfg(feature = "vk-mem")]
+impl<T> ErrorContext<T> for std::result::Result<T, vk_mem::Error> {
+    fn context<C: Into<String>>(self, context: C) -> Result<T> {
+        self.map_err(|source| Error::Allocator {
+            context: context.into(),
+            source: source,
+        })
+    }
+}
+
+#[cfg(feature = "vulkano")]
+macro_rules! vulkano_error_context {
+    ($($error:ty),* $(,)?) => {
+        $(
+            impl<T> ErrorContext<T> for std::result::Result<T, $error> {
+                fn context<C: Into<String>>(self, context: C) -> Result<T> {
+                    self.map_err(|source| Error::Vulkano {
+                        context: context.into(),
+                        source: Box::new(source),
+                    })
+                }
+            }
+        )*
+    };
+}
+
+//  vulkano のサンプルで使うエラー
+#[cfg(feature = "vulkano")]
+vulkano_error_context!(
+    vulkano::instance::InstanceCreationError,
+    vulkano::device::DeviceCreationError,
+    vulkano::swapchain::CapabilitiesError,
+    vulkano::swapchain::SwapchainCreationError,
+    vulkano::framebuffer::RenderPassCreationError,
+);
+
+//  名前に NUL が含まれる理由は context の後ろに書く
+impl<T> ErrorContext<T> for std::result::Result<T, std::ffi::NulError> {
+    fn context<C: Into<String>>(self, context: C) -> Result<T> {
+        self.map_err(|e| Error::Message(format!("{}: {}", context.into(), e)))
+    }
+}
+
+//  メモリタイプを選べない理由は context の後ろに書く
+impl<T> ErrorContext<T> for std::result::Result<T, crate::memory_type::MemoryTypeError> {
+    fn context<C: Into<String>>(self, context: C) -> Result<T> {
+        self.map_err(|e| Error::Message(format!("{}: {}", context.into(), e)))
+    }
+}
+
+//  String のエラーは原因として context の後ろに書く
+impl<T> ErrorContext<T> for std::result::Result<T, String> {
+    fn context<C: Into<String>>(self, context: C) -> Result<T> {
+        self.map_err(|message| Error::Message(format!("{}: {}", context.into(), message)))
+    }
+}
+
+impl<T> ErrorContext<T> for Option<T> {
+    fn context<C: Into<String>>(self, context: C) -> Result<T> {
+        self.ok_or_else(|| Error::Message(context.into()))
+    }
+}
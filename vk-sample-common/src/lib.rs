@@ -1,10 +1,31 @@
+#[macro_use(defer)]
+extern crate scopeguard;
+
+pub mod align;
+pub mod allocator;
+pub mod camera;
 pub mod config;
+pub mod context;
+pub mod debug;
+pub mod error;
+#[cfg(feature = "vk-mem")]
+pub mod memory_report;
+pub mod memory_type;
+pub mod mesh;
+pub mod report;
+pub mod resource;
+pub mod selection;
+pub mod screenshot;
+pub mod upload;
 
+#[derive(Clone, Copy)]
 #[repr(C, packed)]
 pub struct Vertex {
     pub position: nalgebra_glm::Vec3,
     pub normal: nalgebra_glm::Vec3,
-    pub tangent: nalgebra_glm::Vec3,
+    //  xyz は接線、 w は従法線の向き (MikkTSpace と glTF の TANGENT と同じ)
+    //  従法線は w * cross(normal, tangent.xyz) になる
+    pub tangent: nalgebra_glm::Vec4,
     pub texcoord: nalgebra_glm::Vec2,
 }
 
@@ -14,6 +35,45 @@ impl Default for Vertex {
     }
 }
 
+//  simple.vert と simple.frag の push_constant ブロックに対応する
+//  vec3 は 16 バイト境界に配置されるので間を詰める
+#[repr(C)]
+pub struct PushConstants {
+    pub world_matrix: nalgebra_glm::Mat4,
+    pub projection_matrix: nalgebra_glm::Mat4,
+    pub eye: nalgebra_glm::Vec3,
+    eye_padding: f32,
+    pub lightpos: nalgebra_glm::Vec3,
+    lightpos_padding: f32,
+}
+
+impl PushConstants {
+    pub fn new(
+        world_matrix: nalgebra_glm::Mat4,
+        projection_matrix: nalgebra_glm::Mat4,
+        eye: nalgebra_glm::Vec3,
+        lightpos: nalgebra_glm::Vec3,
+    ) -> PushConstants {
+        PushConstants {
+            world_matrix: world_matrix,
+            projection_matrix: projection_matrix,
+            eye: eye,
+            eye_padding: 0.0,
+            lightpos: lightpos,
+            lightpos_padding: 0.0,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            std::slice::from_raw_parts(
+                self as *const PushConstants as *const u8,
+                std::mem::size_of::<PushConstants>(),
+            )
+        }
+    }
+}
+
 pub unsafe fn from_slice<'a, T, U>(src: &'a [U]) -> &'a [T] {
     std::slice::from_raw_parts::<T>(
         src.as_ptr() as *const T,
@@ -27,8 +87,10 @@ macro_rules! offset_of {
     ($base:path, $field:ident) => {{
         #[allow(unused_unsafe)]
         unsafe {
-            let b: $base = std::mem::zeroed();
-            (&b.$field as *const _ as isize) - (&b as *const _ as isize)
+            //  packed な構造体のフィールドへの参照は作れないのでポインタのまま扱う
+            let b = std::mem::MaybeUninit::<$base>::uninit();
+            let base = b.as_ptr();
+            (std::ptr::addr_of!((*base).$field) as isize) - (base as isize)
         }
     }};
 }
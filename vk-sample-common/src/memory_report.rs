@@ -0,0 +1,211 @@
+//  vk-mem の統計と VK_EXT_memory_budget のヒープの予算を JSON にまとめる
+//  テキストで表示する場合も同じ JSON から書き出すので、どちらの形式にも同じ項目が入る
+//  終了時の JSON を実行ごとに比べると、同じシーンで使用量が増えていないかを確かめられる
+use crate::context::VulkanContext;
+use crate::error::{self, ErrorContext};
+use crate::memory_type::HeapBudget;
+use crate::report;
+use ash::version::{InstanceV1_0, InstanceV1_1};
+use serde_json::json;
+
+const MEMORY_BUDGET_EXTENSION: &str = "VK_EXT_memory_budget";
+
+//  VK_EXT_memory_budget で取得したヒープごとの予算と使用量
+//  拡張が無いか、デバイスが Vulkan 1.1 に対応していない場合は None
+//  使用量はこのプロセスだけでなく、他のプロセスが使っている分も含む
+pub fn heap_budgets(
+    instance: &ash::Instance,
+    physical_device: ash::vk::PhysicalDevice,
+) -> Option<Vec<HeapBudget>> {
+    let props = unsafe { instance.get_physical_device_properties(physical_device) };
+    if props.api_version < ash::vk::make_version(1, 1, 0) {
+        return None;
+    }
+    let extensions =
+        unsafe { instance.enumerate_device_extension_properties(physical_device) }.ok()?;
+    if !extensions
+        .iter()
+        .any(|ext| report::c_str(&ext.extension_name) == MEMORY_BUDGET_EXTENSION)
+    {
+        return None;
+    }
+
+    let mut budget = ash::vk::PhysicalDeviceMemoryBudgetPropertiesEXT::default();
+    let mut memory_properties = ash::vk::PhysicalDeviceMemoryProperties2::builder()
+        .push_next(&mut budget)
+        .build();
+    unsafe {
+        instance.get_physical_device_memory_properties2(physical_device, &mut memory_properties)
+    };
+    let heap_count = memory_properties.memory_properties.memory_heap_count as usize;
+    Some(
+        (0..heap_count)
+            .map(|i| HeapBudget {
+                budget: budget.heap_budget[i],
+                usage: budget.heap_usage[i],
+            })
+            .collect(),
+    )
+}
+
+fn stat_info(info: &vk_mem::ffi::VmaStatInfo) -> serde_json::Map<String, serde_json::Value> {
+    let mut map = serde_json::Map::new();
+    map.insert("blocks".to_string(), json!(info.blockCount));
+    map.insert("allocations".to_string(), json!(info.allocationCount));
+    map.insert("used_bytes".to_string(), json!(info.usedBytes));
+    map.insert("unused_bytes".to_string(), json!(info.unusedBytes));
+    map.insert(
+        "largest_allocation".to_string(),
+        json!(if info.allocationCount > 0 {
+            info.allocationSizeMax
+        } else {
+            0
+        }),
+    );
+    map
+}
+
+//  デバイスに触れずに統計と予算から JSON を作る
+//  budgets が None の場合、ヒープの budget と usage は null になる
+pub fn report_from_stats(
+    memory_properties: &ash::vk::PhysicalDeviceMemoryProperties,
+    stats: &vk_mem::ffi::VmaStats,
+    budgets: Option<&[HeapBudget]>,
+) -> serde_json::Value {
+    let memory_heaps = memory_properties.memory_heaps
+        [..memory_properties.memory_heap_count as usize]
+        .iter()
+        .enumerate()
+        .map(|(i, heap)| {
+            let mut map = stat_info(&stats.memoryHeap[i]);
+            map.insert("index".to_string(), json!(i));
+            map.insert("size".to_string(), json!(heap.size));
+            map.insert(
+                "flags".to_string(),
+                json!(report::memory_heap_flag_names(heap.flags)),
+            );
+            let budget = budgets.and_then(|budgets| budgets.get(i));
+            map.insert(
+                "budget".to_string(),
+                json!(budget.map(|budget| budget.budget)),
+            );
+            map.insert(
+                "usage".to_string(),
+                json!(budget.map(|budget| budget.usage)),
+            );
+            serde_json::Value::Object(map)
+        })
+        .collect::<Vec<_>>();
+    let memory_types = memory_properties.memory_types
+        [..memory_properties.memory_type_count as usize]
+        .iter()
+        .enumerate()
+        .map(|(i, memory_type)| {
+            let mut map = stat_info(&stats.memoryType[i]);
+            map.insert("index".to_string(), json!(i));
+            map.insert("heap_index".to_string(), json!(memory_type.heap_index));
+            map.insert(
+                "property_flags".to_string(),
+                json!(report::memory_property_flag_names(
+                    memory_type.property_flags
+                )),
+            );
+            serde_json::Value::Object(map)
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "memory_budget": budgets.is_some(),
+        "total": stat_info(&stats.total),
+        "memory_heaps": memory_heaps,
+        "memory_types": memory_types,
+    })
+}
+
+//  アロケータの現在の統計とヒープの予算をまとめる
+pub fn memory_report(
+    context: &VulkanContext,
+    allocator: &vk_mem::Allocator,
+) -> error::Result<serde_json::Value> {
+    let stats = allocator
+        .calculate_stats()
+        .context("アロケータの統計を取得できない")?;
+    let memory_properties = unsafe {
+        context
+            .instance
+            .get_physical_device_memory_properties(context.physical_device)
+    };
+    let budgets = heap_budgets(&context.instance, context.physical_device);
+    Ok(report_from_stats(
+        &memory_properties,
+        &stats,
+        budgets.as_deref(),
+    ))
+}
+
+pub fn write_json(path: &str, report: &serde_json::Value) -> error::Result<()> {
+    //  Value の {:#} は to_string_pretty と同じ形で、失敗しない
+    std::fs::write(path, format!("{:#}\n", report)).context(format!("{} に書き込めない", path))
+}
+
+fn bytes(value: &serde_json::Value) -> String {
+    let bytes = value.as_u64().unwrap_or(0) as f64;
+    if bytes >= 1024.0 * 1024.0 * 1024.0 {
+        format!("{:.1}GiB", bytes / (1024.0 * 1024.0 * 1024.0))
+    } else if bytes >= 1024.0 * 1024.0 {
+        format!("{:.1}MiB", bytes / (1024.0 * 1024.0))
+    } else if bytes >= 1024.0 {
+        format!("{:.1}KiB", bytes / 1024.0)
+    } else {
+        format!("{}B", bytes)
+    }
+}
+
+fn stat_text(stat: &serde_json::Value) -> String {
+    format!(
+        "ブロック {} 、割り当て {} 、使用 {} 、未使用 {}",
+        stat["blocks"],
+        stat["allocations"],
+        bytes(&stat["used_bytes"]),
+        bytes(&stat["unused_bytes"])
+    )
+}
+
+//  ヒープごとに、ブロックを持つメモリタイプをまとめて表示する
+pub fn print_text(report: &serde_json::Value) {
+    println!("GPU メモリ");
+    println!("  合計: {}", stat_text(&report["total"]));
+    if report["memory_budget"].as_bool() != Some(true) {
+        println!("  VK_EXT_memory_budget が使えないのでヒープの予算は分からない");
+    }
+    for heap in report["memory_heaps"].as_array().into_iter().flatten() {
+        println!(
+            "  ヒープ {} ({} 、 {}): {}",
+            heap["index"],
+            bytes(&heap["size"]),
+            report::join_flags(&heap["flags"]),
+            stat_text(heap)
+        );
+        if !heap["budget"].is_null() {
+            println!(
+                "    予算 {} のうち {} を使用中 (他のプロセスの分も含む)",
+                bytes(&heap["budget"]),
+                bytes(&heap["usage"])
+            );
+        }
+        for memory_type in report["memory_types"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|memory_type| memory_type["heap_index"] == heap["index"])
+            .filter(|memory_type| memory_type["blocks"].as_u64().unwrap_or(0) > 0)
+        {
+            println!(
+                "    タイプ {} ({}): {}",
+                memory_type["index"],
+                report::join_flags(&memory_type["property_flags"]),
+                stat_text(memory_type)
+            );
+        }
+    }
+}
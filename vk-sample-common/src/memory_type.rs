@@ -0,0 +1,152 @@
+//  メモリ要件とプロパティフラグからメモリタイプを選ぶ
+//  条件を満たすメモリタイプが無い場合は、どの条件で候補が無くなったかをエラーで返す
+use std::fmt;
+
+#[derive(Clone, Copy, Default)]
+pub struct MemoryTypeRequest {
+    //  VkMemoryRequirements の memoryTypeBits
+    pub type_bits: u32,
+    //  必ず備えている必要があるフラグ
+    pub required_flags: ash::vk::MemoryPropertyFlags,
+    //  備えているメモリタイプがあればそちらを使うフラグ
+    pub preferred_flags: ash::vk::MemoryPropertyFlags,
+    //  確保する大きさ
+    //  ヒープの大きさと残りの予算がこれに満たないメモリタイプは使わない
+    pub size: u64,
+}
+
+//  VK_EXT_memory_budget で取得できるヒープごとの予算と使用量
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HeapBudget {
+    pub budget: u64,
+    pub usage: u64,
+}
+
+impl HeapBudget {
+    pub fn available(&self) -> u64 {
+        self.budget.saturating_sub(self.usage)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MemoryTypeError {
+    //  memoryTypeBits に含まれるメモリタイプが無い
+    NoCompatibleType {
+        type_bits: u32,
+    },
+    //  required_flags を備えたメモリタイプが無い
+    MissingRequiredFlags {
+        required_flags: ash::vk::MemoryPropertyFlags,
+    },
+    //  size より大きいヒープが無い
+    HeapTooSmall {
+        size: u64,
+    },
+    //  ヒープの残りの予算が size に足りない
+    OverBudget {
+        size: u64,
+    },
+}
+
+impl fmt::Display for MemoryTypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MemoryTypeError::NoCompatibleType { type_bits } => write!(
+                f,
+                "memoryTypeBits {:#x} に含まれるメモリタイプが無い",
+                type_bits
+            ),
+            MemoryTypeError::MissingRequiredFlags { required_flags } => {
+                write!(f, "{:?} を備えたメモリタイプが無い", required_flags)
+            }
+            MemoryTypeError::HeapTooSmall { size } => {
+                write!(f, "{} バイトを確保できる大きさのヒープが無い", size)
+            }
+            MemoryTypeError::OverBudget { size } => {
+                write!(f, "{} バイトを確保できる予算が残っているヒープが無い", size)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MemoryTypeError {}
+
+fn memory_types(
+    memory_properties: &ash::vk::PhysicalDeviceMemoryProperties,
+) -> &[ash::vk::MemoryType] {
+    &memory_properties.memory_types[..memory_properties.memory_type_count as usize]
+}
+
+//  条件を満たすメモリタイプを良い順に全て返す
+//  先頭のメモリタイプで確保に失敗した場合は次のメモリタイプで確保し直せる
+//  budgets を渡した場合はヒープの残りの予算も確かめる
+pub fn memory_type_candidates(
+    memory_properties: &ash::vk::PhysicalDeviceMemoryProperties,
+    request: &MemoryTypeRequest,
+    budgets: Option<&[HeapBudget]>,
+) -> Result<Vec<u32>, MemoryTypeError> {
+    let compatible = memory_types(memory_properties)
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| (request.type_bits & (1 << i)) != 0)
+        .collect::<Vec<_>>();
+    if compatible.is_empty() {
+        return Err(MemoryTypeError::NoCompatibleType {
+            type_bits: request.type_bits,
+        });
+    }
+
+    let with_flags = compatible
+        .into_iter()
+        .filter(|(_, memory_type)| memory_type.property_flags.contains(request.required_flags))
+        .collect::<Vec<_>>();
+    if with_flags.is_empty() {
+        return Err(MemoryTypeError::MissingRequiredFlags {
+            required_flags: request.required_flags,
+        });
+    }
+
+    let large_enough = with_flags
+        .into_iter()
+        .filter(|(_, memory_type)| {
+            memory_properties.memory_heaps[memory_type.heap_index as usize].size >= request.size
+        })
+        .collect::<Vec<_>>();
+    if large_enough.is_empty() {
+        return Err(MemoryTypeError::HeapTooSmall { size: request.size });
+    }
+
+    let mut candidates = large_enough
+        .into_iter()
+        .filter(|(_, memory_type)| {
+            budgets
+                .and_then(|budgets| budgets.get(memory_type.heap_index as usize))
+                .map_or(true, |budget| budget.available() >= request.size)
+        })
+        .collect::<Vec<_>>();
+    if candidates.is_empty() {
+        return Err(MemoryTypeError::OverBudget { size: request.size });
+    }
+
+    //  preferred_flags を多く備えたものを優先し、同じなら求めていないフラグが少ないものを優先する
+    //  求めていないフラグが少ない方が、 DEVICE_LOCAL が必要なリソースのために BAR などを残せる
+    let wanted = request.required_flags | request.preferred_flags;
+    candidates.sort_by_key(|(i, memory_type)| {
+        let flags = memory_type.property_flags;
+        (
+            std::cmp::Reverse((flags & request.preferred_flags).as_raw().count_ones()),
+            (flags & !wanted).as_raw().count_ones(),
+            *i,
+        )
+    });
+    Ok(candidates.into_iter().map(|(i, _)| i as u32).collect())
+}
+
+//  最も良いメモリタイプを返す
+pub fn select_memory_type(
+    memory_properties: &ash::vk::PhysicalDeviceMemoryProperties,
+    request: &MemoryTypeRequest,
+    budgets: Option<&[HeapBudget]>,
+) -> Result<u32, MemoryTypeError> {
+    memory_type_candidates(memory_properties, request, budgets).map(|candidates| candidates[0])
+}
@@ -0,0 +1,443 @@
+//  COLLADA (.dae) の読み込み
+//  visual_scene のノードから参照されるジオメトリを、ノードの変換を適用して 1 つのメッシュにまとめる
+use super::{apply_transform, generate_normals, Mesh};
+use crate::Vertex;
+use std::collections::HashMap;
+
+pub fn load(path: &str) -> Result<Mesh, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{} を読めない: {}", path, e))?;
+    parse(&text).map_err(|e| format!("{}: {}", path, e))
+}
+
+pub fn parse(text: &str) -> Result<Mesh, String> {
+    let document =
+        roxmltree::Document::parse(text).map_err(|e| format!("XML として読めない: {}", e))?;
+    let root = document.root_element();
+    if !root.has_tag_name("COLLADA") {
+        return Err(String::from("COLLADA のファイルではない"));
+    }
+
+    let geometries = root
+        .descendants()
+        .filter(|n| n.has_tag_name("geometry"))
+        .filter_map(|n| n.attribute("id").map(|id| (id, n)))
+        .collect::<HashMap<_, _>>();
+
+    //  シーンが無い場合は全てのジオメトリをそのまま使う
+    let up_axis = up_axis_transform(root);
+    let mut instances = Vec::<(&str, nalgebra_glm::Mat4)>::new();
+    if let Some(scene) = visual_scene(root) {
+        for node in scene.children().filter(|n| n.has_tag_name("node")) {
+            collect_instances(node, &up_axis, &mut instances)?;
+        }
+    } else {
+        for geometry in root.descendants().filter(|n| n.has_tag_name("geometry")) {
+            if let Some(id) = geometry.attribute("id") {
+                instances.push((id, up_axis));
+            }
+        }
+    }
+
+    let mut mesh = Mesh::default();
+    for (id, transform) in instances {
+        let geometry = geometries
+            .get(id)
+            .ok_or_else(|| format!("ジオメトリ {} が無い", id))?;
+        if let Some(geometry_mesh) = geometry.children().find(|n| n.has_tag_name("mesh")) {
+            for primitive in read_mesh(geometry_mesh)? {
+                mesh.append(apply_transform(primitive, &transform));
+            }
+        }
+    }
+    if mesh.indices.is_empty() {
+        return Err(String::from("三角形を含むジオメトリが無い"));
+    }
+    Ok(mesh)
+}
+
+//  Y 軸が上になるように回転する
+fn up_axis_transform(root: roxmltree::Node) -> nalgebra_glm::Mat4 {
+    let up_axis = root
+        .children()
+        .find(|n| n.has_tag_name("asset"))
+        .and_then(|asset| asset.children().find(|n| n.has_tag_name("up_axis")))
+        .and_then(|n| n.text())
+        .map(|t| t.trim());
+    let identity: nalgebra_glm::Mat4 = nalgebra_glm::identity();
+    match up_axis {
+        Some("Z_UP") => nalgebra_glm::rotate(
+            &identity,
+            -std::f32::consts::FRAC_PI_2,
+            &nalgebra_glm::vec3(1.0, 0.0, 0.0),
+        ),
+        Some("X_UP") => nalgebra_glm::rotate(
+            &identity,
+            std::f32::consts::FRAC_PI_2,
+            &nalgebra_glm::vec3(0.0, 0.0, 1.0),
+        ),
+        _ => identity,
+    }
+}
+
+//  scene の instance_visual_scene が指すシーン、無ければ最初のシーン
+fn visual_scene<'a, 'input>(
+    root: roxmltree::Node<'a, 'input>,
+) -> Option<roxmltree::Node<'a, 'input>> {
+    let scenes = root
+        .descendants()
+        .filter(|n| n.has_tag_name("visual_scene"))
+        .collect::<Vec<_>>();
+    let url = root
+        .children()
+        .find(|n| n.has_tag_name("scene"))
+        .and_then(|scene| {
+            scene
+                .children()
+                .find(|n| n.has_tag_name("instance_visual_scene"))
+        })
+        .and_then(|n| n.attribute("url"))
+        .map(|url| url.trim_start_matches('#'));
+    url.and_then(|url| {
+        scenes
+            .iter()
+            .find(|n| n.attribute("id") == Some(url))
+            .cloned()
+    })
+    .or_else(|| scenes.first().cloned())
+}
+
+fn collect_instances<'a>(
+    node: roxmltree::Node<'a, '_>,
+    parent: &nalgebra_glm::Mat4,
+    instances: &mut Vec<(&'a str, nalgebra_glm::Mat4)>,
+) -> Result<(), String> {
+    let mut transform = *parent;
+    for child in node.children().filter(|n| n.is_element()) {
+        let name = child.tag_name().name();
+        match name {
+            "matrix" | "translate" | "rotate" | "scale" => {
+                transform = transform * local_transform(name, &parse_floats(child)?)?;
+            }
+            "instance_geometry" => {
+                if let Some(url) = child.attribute("url") {
+                    instances.push((url.trim_start_matches('#'), transform));
+                }
+            }
+            "node" => collect_instances(child, &transform, instances)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn local_transform(name: &str, values: &[f32]) -> Result<nalgebra_glm::Mat4, String> {
+    let identity: nalgebra_glm::Mat4 = nalgebra_glm::identity();
+    let expected = match name {
+        "matrix" => 16,
+        "rotate" => 4,
+        _ => 3,
+    };
+    if values.len() != expected {
+        return Err(format!("{} の要素数が {} ではない", name, expected));
+    }
+    Ok(match name {
+        //  COLLADA の行列は行優先で並んでいる
+        "matrix" => nalgebra_glm::make_mat4(values).transpose(),
+        "translate" => nalgebra_glm::translate(
+            &identity,
+            &nalgebra_glm::vec3(values[0], values[1], values[2]),
+        ),
+        "rotate" => nalgebra_glm::rotate(
+            &identity,
+            values[3].to_radians(),
+            &nalgebra_glm::vec3(values[0], values[1], values[2]),
+        ),
+        _ => nalgebra_glm::scale(
+            &identity,
+            &nalgebra_glm::vec3(values[0], values[1], values[2]),
+        ),
+    })
+}
+
+fn parse_floats(node: roxmltree::Node) -> Result<Vec<f32>, String> {
+    node.text()
+        .unwrap_or("")
+        .split_whitespace()
+        .map(|v| {
+            v.parse::<f32>()
+                .map_err(|_| format!("{} は数値ではない", v))
+        })
+        .collect()
+}
+
+fn parse_indices(node: roxmltree::Node) -> Result<Vec<usize>, String> {
+    node.text()
+        .unwrap_or("")
+        .split_whitespace()
+        .map(|v| {
+            v.parse::<usize>()
+                .map_err(|_| format!("{} はインデックスではない", v))
+        })
+        .collect()
+}
+
+//  accessor の stride と offset に従って float_array を読む
+struct Source {
+    data: Vec<f32>,
+    offset: usize,
+    stride: usize,
+    count: usize,
+}
+
+impl Source {
+    fn read(node: roxmltree::Node) -> Result<Source, String> {
+        let data = match node.children().find(|n| n.has_tag_name("float_array")) {
+            Some(array) => parse_floats(array)?,
+            None => vec![],
+        };
+        let accessor = node
+            .descendants()
+            .find(|n| n.has_tag_name("accessor"))
+            .ok_or_else(|| String::from("accessor が無い"))?;
+        let attribute = |name, default| {
+            accessor
+                .attribute(name)
+                .map_or(Ok(default), |v| v.parse::<usize>())
+                .map_err(|_| format!("accessor の {} が不正", name))
+        };
+        Ok(Source {
+            data: data,
+            offset: attribute("offset", 0)?,
+            stride: attribute("stride", 1)?,
+            count: attribute("count", usize::max_value())?,
+        })
+    }
+
+    fn get(&self, index: usize, components: usize) -> Result<&[f32], String> {
+        let begin = self.offset + index * self.stride;
+        if index >= self.count || begin + components > self.data.len() {
+            return Err(format!("インデックス {} が範囲外", index));
+        }
+        Ok(&self.data[begin..begin + components])
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Input {
+    offset: usize,
+    source: usize,
+}
+
+//  mesh に含まれる三角形、ポリゴンをプリミティブごとにメッシュにする
+fn read_mesh(node: roxmltree::Node) -> Result<Vec<Mesh>, String> {
+    let mut source_ids = HashMap::<&str, usize>::new();
+    let mut sources = Vec::<Source>::new();
+    for source in node.children().filter(|n| n.has_tag_name("source")) {
+        if let Some(id) = source.attribute("id") {
+            source_ids.insert(id, sources.len());
+            sources.push(Source::read(source)?);
+        }
+    }
+    let source_index = |url: &str| {
+        source_ids
+            .get(url.trim_start_matches('#'))
+            .cloned()
+            .ok_or_else(|| format!("ソース {} が無い", url))
+    };
+
+    //  vertices は VERTEX の入力から参照される
+    let mut vertices_inputs = HashMap::<&str, Vec<(&str, usize)>>::new();
+    for vertices in node.children().filter(|n| n.has_tag_name("vertices")) {
+        let mut inputs = vec![];
+        for input in vertices.children().filter(|n| n.has_tag_name("input")) {
+            let semantic = input.attribute("semantic").unwrap_or("");
+            let source = source_index(input.attribute("source").unwrap_or(""))?;
+            inputs.push((semantic, source));
+        }
+        if let Some(id) = vertices.attribute("id") {
+            vertices_inputs.insert(id, inputs);
+        }
+    }
+
+    let mut meshes = vec![];
+    for primitive in node.children().filter(|n| n.is_element()) {
+        let name = primitive.tag_name().name();
+        if !["triangles", "polylist", "polygons", "trifans", "tristrips"].contains(&name) {
+            continue;
+        }
+
+        let mut position = None;
+        let mut normal = None;
+        let mut texcoord = None;
+        let mut tangent = None;
+        let mut binormal = None;
+        let mut stride = 0;
+        for input in primitive.children().filter(|n| n.has_tag_name("input")) {
+            let offset = input
+                .attribute("offset")
+                .unwrap_or("0")
+                .parse::<usize>()
+                .map_err(|_| String::from("input の offset が不正"))?;
+            stride = std::cmp::max(stride, offset + 1);
+            let semantic = input.attribute("semantic").unwrap_or("");
+            let url = input.attribute("source").unwrap_or("");
+            let mut bound = vec![];
+            if semantic == "VERTEX" {
+                let inputs = vertices_inputs
+                    .get(url.trim_start_matches('#'))
+                    .ok_or_else(|| format!("頂点 {} が無い", url))?;
+                for (semantic, source) in inputs {
+                    bound.push((*semantic, *source));
+                }
+            } else {
+                bound.push((semantic, source_index(url)?));
+            }
+            //  同じ意味の入力が複数ある場合は最初のものを使う
+            for (semantic, source) in bound {
+                let input = Some(Input {
+                    offset: offset,
+                    source: source,
+                });
+                match semantic {
+                    "POSITION" => position = position.or(input),
+                    "NORMAL" => normal = normal.or(input),
+                    "TEXCOORD" => texcoord = texcoord.or(input),
+                    "TEXTANGENT" | "TANGENT" => tangent = tangent.or(input),
+                    "TEXBINORMAL" | "BINORMAL" => binormal = binormal.or(input),
+                    _ => {}
+                }
+            }
+        }
+        let position = position.ok_or_else(|| format!("{} に POSITION が無い", name))?;
+
+        let polygons = primitive
+            .children()
+            .filter(|n| n.has_tag_name("p"))
+            .map(|p| parse_indices(p))
+            .collect::<Result<Vec<_>, _>>()?;
+        let corners = triangulate(primitive, name, &polygons, stride)?;
+
+        //  同じインデックスの組を指す頂点は 1 つにまとめる
+        let mut mesh = Mesh::default();
+        let mut vertex_ids = HashMap::<Vec<usize>, u32>::new();
+        for corner in corners {
+            let index = |input: Option<Input>| input.map(|i| corner[i.offset]);
+            let key = vec![
+                corner[position.offset],
+                index(normal).map_or(usize::max_value(), |i| i),
+                index(texcoord).map_or(usize::max_value(), |i| i),
+                index(tangent).map_or(usize::max_value(), |i| i),
+                index(binormal).map_or(usize::max_value(), |i| i),
+            ];
+            if let Some(id) = vertex_ids.get(&key) {
+                mesh.indices.push(*id);
+                continue;
+            }
+            let mut vertex = Vertex::default();
+            let p = sources[position.source].get(corner[position.offset], 3)?;
+            vertex.position = nalgebra_glm::vec3(p[0], p[1], p[2]);
+            if let Some(input) = normal {
+                let n = sources[input.source].get(corner[input.offset], 3)?;
+                vertex.normal = nalgebra_glm::vec3(n[0], n[1], n[2]);
+            }
+            if let Some(input) = texcoord {
+                let t = sources[input.source].get(corner[input.offset], 2)?;
+                vertex.texcoord = nalgebra_glm::vec2(t[0], t[1]);
+            }
+            if let Some(input) = tangent {
+                let t = sources[input.source].get(corner[input.offset], 3)?;
+                let t = nalgebra_glm::vec3(t[0], t[1], t[2]);
+                //  従法線がある場合は cross(normal, tangent) との向きから w を決める
+                let mut handedness = 1.0;
+                if let Some(input) = binormal {
+                    let b = sources[input.source].get(corner[input.offset], 3)?;
+                    let b = nalgebra_glm::vec3(b[0], b[1], b[2]);
+                    let normal = vertex.normal;
+                    if nalgebra_glm::dot(&nalgebra_glm::cross(&normal, &t), &b) < 0.0 {
+                        handedness = -1.0;
+                    }
+                }
+                vertex.tangent = nalgebra_glm::vec4(t.x, t.y, t.z, handedness);
+            }
+            let id = mesh.vertices.len() as u32;
+            vertex_ids.insert(key, id);
+            mesh.vertices.push(vertex);
+            mesh.indices.push(id);
+        }
+
+        if mesh.indices.is_empty() {
+            continue;
+        }
+        if normal.is_none() {
+            generate_normals(&mut mesh.vertices, &mesh.indices);
+        }
+        if tangent.is_none() {
+            mesh.generate_tangents();
+        }
+        meshes.push(mesh);
+    }
+    Ok(meshes)
+}
+
+//  プリミティブを三角形の頂点ごとのインデックスの組の並びにする
+//  多角形は最初の頂点を中心とした扇形に分割する
+fn triangulate<'a>(
+    primitive: roxmltree::Node,
+    name: &str,
+    polygons: &'a [Vec<usize>],
+    stride: usize,
+) -> Result<Vec<&'a [usize]>, String> {
+    if stride == 0 {
+        return Err(format!("{} に入力が無い", name));
+    }
+    let split = |p: &'a Vec<usize>| p.chunks_exact(stride).collect::<Vec<_>>();
+    let fan = |polygon: &[&'a [usize]], corners: &mut Vec<&'a [usize]>| {
+        for i in 2..polygon.len() {
+            corners.extend_from_slice(&[polygon[0], polygon[i - 1], polygon[i]]);
+        }
+    };
+
+    let mut corners = vec![];
+    match name {
+        "triangles" => {
+            for p in polygons {
+                let vertices = split(p);
+                corners.extend(vertices.chunks_exact(3).flatten());
+            }
+        }
+        "polylist" => {
+            let vcount = primitive
+                .children()
+                .find(|n| n.has_tag_name("vcount"))
+                .map_or(Ok(vec![]), |n| parse_indices(n))?;
+            let vertices = polygons.iter().flat_map(|p| split(p)).collect::<Vec<_>>();
+            let mut begin = 0;
+            for count in vcount {
+                if begin + count > vertices.len() {
+                    return Err(String::from("vcount と p の数が合わない"));
+                }
+                fan(&vertices[begin..begin + count], &mut corners);
+                begin += count;
+            }
+        }
+        "polygons" | "trifans" => {
+            for p in polygons {
+                fan(&split(p), &mut corners);
+            }
+        }
+        _ => {
+            //  tristrips は三角形ごとに向きが入れ替わる
+            for p in polygons {
+                let vertices = split(p);
+                for i in 2..vertices.len() {
+                    if i % 2 == 0 {
+                        corners.extend_from_slice(&[vertices[i - 2], vertices[i - 1], vertices[i]]);
+                    } else {
+                        corners.extend_from_slice(&[vertices[i - 1], vertices[i - 2], vertices[i]]);
+                    }
+                }
+            }
+        }
+    }
+    Ok(corners)
+}
@@ -0,0 +1,622 @@
+//  glTF 2.0 (.gltf, .glb) の読み込み
+//  シーンのノードの階層をたどり、ノードの変換を適用したプリミティブを 1 つのメッシュにまとめる
+use super::{apply_transform, generate_normals, Group, Material, Mesh};
+use crate::Vertex;
+use serde_json::Value;
+
+//  GLB のヘッダとチャンクの種類
+const GLB_MAGIC: u32 = 0x4654_6C67;
+const CHUNK_JSON: u32 = 0x4E4F_534A;
+const CHUNK_BIN: u32 = 0x004E_4942;
+
+//  accessor の componentType
+const BYTE: u64 = 5120;
+const UNSIGNED_BYTE: u64 = 5121;
+const SHORT: u64 = 5122;
+const UNSIGNED_SHORT: u64 = 5123;
+const UNSIGNED_INT: u64 = 5125;
+const FLOAT: u64 = 5126;
+
+//  primitive の mode
+const TRIANGLES: u64 = 4;
+const TRIANGLE_STRIP: u64 = 5;
+const TRIANGLE_FAN: u64 = 6;
+
+//  読み込みで扱える拡張
+//  extensionsRequired にこれ以外の拡張がある場合は、アクセサの値の意味が変わるのでエラーにする
+const SUPPORTED_EXTENSIONS: &[&str] = &[];
+
+pub fn load(path: &str) -> Result<Mesh, String> {
+    let data = std::fs::read(path).map_err(|e| format!("{} を読めない: {}", path, e))?;
+    let dir = std::path::Path::new(path)
+        .parent()
+        .unwrap_or(std::path::Path::new(""));
+    parse(&data, dir).map_err(|e| format!("{}: {}", path, e))
+}
+
+//  外部のバッファとテクスチャのパスは dir からの相対パスとして扱う
+pub fn parse(data: &[u8], dir: &std::path::Path) -> Result<Mesh, String> {
+    let (json, bin) = if data.len() >= 4 && read_u32(data, 0) == GLB_MAGIC {
+        split_glb(data)?
+    } else {
+        (data, None)
+    };
+    let document =
+        serde_json::from_slice::<Value>(json).map_err(|e| format!("JSON として読めない: {}", e))?;
+    let version = document
+        .get("asset")
+        .and_then(|asset| asset.get("version"))
+        .and_then(Value::as_str)
+        .unwrap_or("");
+    if !version.starts_with("2.") {
+        return Err(format!("glTF {} には対応していない", version));
+    }
+    let unsupported = array(&document, "extensionsRequired")
+        .iter()
+        .filter_map(Value::as_str)
+        .filter(|name| !SUPPORTED_EXTENSIONS.contains(name))
+        .collect::<Vec<_>>();
+    if !unsupported.is_empty() {
+        return Err(format!(
+            "必須の拡張 {} には対応していない",
+            unsupported.join(", ")
+        ));
+    }
+
+    let gltf = Gltf {
+        buffers: read_buffers(&document, bin, dir)?,
+        document: &document,
+        dir: dir,
+    };
+
+    //  シーンが無い場合はどのノードの子でもないノードを全て使う
+    let nodes = array(&document, "nodes");
+    let scene = get_usize(&document, "scene")
+        .or_else(|| Some(0).filter(|_| !array(&document, "scenes").is_empty()))
+        .map(|i| {
+            array(&document, "scenes")
+                .get(i)
+                .ok_or_else(|| format!("シーン {} が無い", i))
+        })
+        .transpose()?;
+    let roots = match scene {
+        Some(scene) => array(scene, "nodes")
+            .iter()
+            .filter_map(Value::as_u64)
+            .map(|i| i as usize)
+            .collect::<Vec<_>>(),
+        None => {
+            let children = nodes
+                .iter()
+                .flat_map(|node| array(node, "children").iter().filter_map(Value::as_u64))
+                .map(|i| i as usize)
+                .collect::<std::collections::HashSet<_>>();
+            (0..nodes.len()).filter(|i| !children.contains(i)).collect()
+        }
+    };
+
+    let mut mesh = Mesh::default();
+    let identity: nalgebra_glm::Mat4 = nalgebra_glm::identity();
+    for root in roots {
+        gltf.visit(root, &identity, 0, &mut mesh)?;
+    }
+    if mesh.indices.is_empty() {
+        return Err(String::from("三角形を含むメッシュが無い"));
+    }
+    mesh.materials = gltf.read_materials()?;
+    Ok(mesh)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+//  GLB を JSON のチャンクとバイナリのチャンクに分ける
+fn split_glb(data: &[u8]) -> Result<(&[u8], Option<&[u8]>), String> {
+    if data.len() < 12 || read_u32(data, 4) != 2 {
+        return Err(String::from("GLB のヘッダが不正"));
+    }
+    let length = std::cmp::min(read_u32(data, 8) as usize, data.len());
+    let mut json = None;
+    let mut bin = None;
+    let mut offset = 12;
+    while offset + 8 <= length {
+        let chunk_length = read_u32(data, offset) as usize;
+        let chunk_type = read_u32(data, offset + 4);
+        let begin = offset + 8;
+        if begin + chunk_length > length {
+            return Err(String::from("GLB のチャンクが途中で切れている"));
+        }
+        let chunk = &data[begin..begin + chunk_length];
+        match chunk_type {
+            CHUNK_JSON if json.is_none() => json = Some(chunk),
+            CHUNK_BIN if bin.is_none() => bin = Some(chunk),
+            _ => {}
+        }
+        //  チャンクは 4 バイト境界に揃えられている
+        offset = begin + ((chunk_length + 3) & !3);
+    }
+    Ok((
+        json.ok_or_else(|| String::from("GLB に JSON のチャンクが無い"))?,
+        bin,
+    ))
+}
+
+//  data URI、外部ファイル、GLB のバイナリチャンクのいずれかからバッファを読む
+fn read_buffers(
+    document: &Value,
+    bin: Option<&[u8]>,
+    dir: &std::path::Path,
+) -> Result<Vec<Vec<u8>>, String> {
+    let mut buffers = vec![];
+    for (i, buffer) in array(document, "buffers").iter().enumerate() {
+        let data = match buffer.get("uri").and_then(Value::as_str) {
+            Some(uri) if uri.starts_with("data:") => {
+                let encoded = uri
+                    .splitn(2, ',')
+                    .nth(1)
+                    .ok_or_else(|| format!("バッファ {} の data URI が不正", i))?;
+                base64::decode(encoded)
+                    .map_err(|e| format!("バッファ {} を復号できない: {}", i, e))?
+            }
+            Some(uri) => {
+                let path = dir.join(decode_uri(uri));
+                std::fs::read(&path).map_err(|e| format!("{} を読めない: {}", path.display(), e))?
+            }
+            None => bin
+                .ok_or_else(|| format!("バッファ {} の URI もバイナリチャンクも無い", i))?
+                .to_vec(),
+        };
+        let length = get_usize(buffer, "byteLength").unwrap_or(0);
+        if data.len() < length {
+            return Err(format!("バッファ {} が byteLength より短い", i));
+        }
+        buffers.push(data);
+    }
+    Ok(buffers)
+}
+
+//  URI の %XX をデコードする
+fn decode_uri(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::<u8>::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+            if let Ok(byte) = u8::from_str_radix(hex, 16) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn array<'a>(value: &'a Value, key: &str) -> &'a [Value] {
+    value
+        .get(key)
+        .and_then(Value::as_array)
+        .map(|a| a.as_slice())
+        .unwrap_or(&[])
+}
+
+fn get_usize(value: &Value, key: &str) -> Option<usize> {
+    value.get(key).and_then(Value::as_u64).map(|v| v as usize)
+}
+
+fn floats(value: &Value, count: usize) -> Result<Vec<f32>, String> {
+    let values = value
+        .as_array()
+        .map(|a| {
+            a.iter()
+                .filter_map(Value::as_f64)
+                .map(|v| v as f32)
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    if values.len() != count {
+        return Err(format!("{} 個の数値が必要", count));
+    }
+    Ok(values)
+}
+
+struct Gltf<'a> {
+    document: &'a Value,
+    buffers: Vec<Vec<u8>>,
+    dir: &'a std::path::Path,
+}
+
+impl Gltf<'_> {
+    fn visit(
+        &self,
+        index: usize,
+        parent: &nalgebra_glm::Mat4,
+        depth: usize,
+        mesh: &mut Mesh,
+    ) -> Result<(), String> {
+        let nodes = array(self.document, "nodes");
+        //  循環している場合に止まるように、ノードの数より深くはたどらない
+        if depth > nodes.len() {
+            return Err(String::from("ノードの階層が循環している"));
+        }
+        let node = nodes
+            .get(index)
+            .ok_or_else(|| format!("ノード {} が無い", index))?;
+        let transform = parent * node_transform(node)?;
+
+        if let Some(mesh_index) = get_usize(node, "mesh") {
+            let node_mesh = array(self.document, "meshes")
+                .get(mesh_index)
+                .ok_or_else(|| format!("メッシュ {} が無い", mesh_index))?;
+            let name = node_mesh
+                .get("name")
+                .and_then(Value::as_str)
+                .unwrap_or("")
+                .to_string();
+            for primitive in array(node_mesh, "primitives") {
+                if let Some(primitive_mesh) = self.read_primitive(primitive)? {
+                    let first_index = mesh.indices.len() as u32;
+                    mesh.append(apply_transform(primitive_mesh, &transform));
+                    mesh.groups.push(Group {
+                        name: name.clone(),
+                        material: get_usize(primitive, "material"),
+                        first_index: first_index,
+                        index_count: mesh.indices.len() as u32 - first_index,
+                    });
+                }
+            }
+        }
+
+        for child in array(node, "children").iter().filter_map(Value::as_u64) {
+            self.visit(child as usize, &transform, depth + 1, mesh)?;
+        }
+        Ok(())
+    }
+
+    //  三角形以外のプリミティブは None を返す
+    fn read_primitive(&self, primitive: &Value) -> Result<Option<Mesh>, String> {
+        let mode = primitive
+            .get("mode")
+            .and_then(Value::as_u64)
+            .unwrap_or(TRIANGLES);
+        if ![TRIANGLES, TRIANGLE_STRIP, TRIANGLE_FAN].contains(&mode) {
+            return Ok(None);
+        }
+
+        let attributes = primitive
+            .get("attributes")
+            .ok_or_else(|| String::from("プリミティブに attributes が無い"))?;
+        let attribute = |name: &str, components: usize| {
+            get_usize(attributes, name)
+                .map(|i| {
+                    let (values, c) = self.read_accessor(i)?;
+                    if c != components {
+                        return Err(format!("{} の要素数が {} ではない", name, components));
+                    }
+                    Ok(values)
+                })
+                .transpose()
+        };
+        let positions = attribute("POSITION", 3)?.ok_or_else(|| String::from("POSITION が無い"))?;
+        let normals = attribute("NORMAL", 3)?;
+        //  TANGENT の w は従法線の向きで、 Vertex の tangent にそのまま入れる
+        let tangents = attribute("TANGENT", 4)?;
+        let texcoords = attribute("TEXCOORD_0", 2)?;
+
+        let count = positions.len() / 3;
+        let check = |values: &Option<Vec<f64>>, components: usize, name: &str| match values {
+            Some(v) if v.len() != count * components => {
+                Err(format!("{} の数が POSITION と合わない", name))
+            }
+            _ => Ok(()),
+        };
+        check(&normals, 3, "NORMAL")?;
+        check(&tangents, 4, "TANGENT")?;
+        check(&texcoords, 2, "TEXCOORD_0")?;
+
+        let mut mesh = Mesh::default();
+        for i in 0..count {
+            let mut vertex = Vertex::default();
+            let p = &positions[i * 3..i * 3 + 3];
+            vertex.position = nalgebra_glm::vec3(p[0] as f32, p[1] as f32, p[2] as f32);
+            if let Some(normals) = normals.as_ref() {
+                let n = &normals[i * 3..i * 3 + 3];
+                vertex.normal = nalgebra_glm::vec3(n[0] as f32, n[1] as f32, n[2] as f32);
+            }
+            if let Some(tangents) = tangents.as_ref() {
+                let t = &tangents[i * 4..i * 4 + 4];
+                vertex.tangent =
+                    nalgebra_glm::vec4(t[0] as f32, t[1] as f32, t[2] as f32, t[3] as f32);
+            }
+            if let Some(texcoords) = texcoords.as_ref() {
+                let t = &texcoords[i * 2..i * 2 + 2];
+                vertex.texcoord = nalgebra_glm::vec2(t[0] as f32, t[1] as f32);
+            }
+            mesh.vertices.push(vertex);
+        }
+
+        let indices = match get_usize(primitive, "indices") {
+            Some(i) => {
+                let (values, _) = self.read_accessor(i)?;
+                let indices = values.iter().map(|v| *v as u32).collect::<Vec<_>>();
+                if indices.iter().any(|i| *i as usize >= count) {
+                    return Err(String::from("インデックスが頂点の数を超えている"));
+                }
+                indices
+            }
+            None => (0..count as u32).collect(),
+        };
+        mesh.indices = match mode {
+            TRIANGLES => indices[..indices.len() / 3 * 3].to_vec(),
+            TRIANGLE_STRIP => (2..indices.len())
+                .flat_map(|i| {
+                    if i % 2 == 0 {
+                        vec![indices[i - 2], indices[i - 1], indices[i]]
+                    } else {
+                        vec![indices[i - 1], indices[i - 2], indices[i]]
+                    }
+                })
+                .collect(),
+            _ => (2..indices.len())
+                .flat_map(|i| vec![indices[0], indices[i - 1], indices[i]])
+                .collect(),
+        };
+        if mesh.indices.is_empty() {
+            return Ok(None);
+        }
+
+        if normals.is_none() {
+            generate_normals(&mut mesh.vertices, &mesh.indices);
+        }
+        if tangents.is_none() {
+            mesh.generate_tangents();
+        }
+        Ok(Some(mesh))
+    }
+
+    //  accessor の値を要素数と共に返す
+    //  整数は正規化の指定があれば 0.0 から 1.0 (符号付きなら -1.0 から 1.0) にする
+    fn read_accessor(&self, index: usize) -> Result<(Vec<f64>, usize), String> {
+        let accessor = array(self.document, "accessors")
+            .get(index)
+            .ok_or_else(|| format!("accessor {} が無い", index))?;
+        let count = get_usize(accessor, "count")
+            .ok_or_else(|| format!("accessor {} に count が無い", index))?;
+        let components = match accessor.get("type").and_then(Value::as_str) {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") | Some("MAT2") => 4,
+            Some("MAT3") => 9,
+            Some("MAT4") => 16,
+            _ => return Err(format!("accessor {} の type が不正", index)),
+        };
+        let component_type = accessor
+            .get("componentType")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| format!("accessor {} に componentType が無い", index))?;
+        let normalized = accessor
+            .get("normalized")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+
+        //  bufferView が無い場合は全て 0 として扱う
+        let mut values = vec![0.0; count * components];
+        if let Some(view) = get_usize(accessor, "bufferView") {
+            self.read_view(
+                view,
+                get_usize(accessor, "byteOffset").unwrap_or(0),
+                component_type,
+                normalized,
+                components,
+                &mut values,
+            )?;
+        }
+
+        //  sparse は一部の要素だけを置き換える
+        if let Some(sparse) = accessor.get("sparse") {
+            let sparse_count = get_usize(sparse, "count").unwrap_or(0);
+            let sparse_indices = sparse
+                .get("indices")
+                .ok_or_else(|| format!("accessor {} の sparse に indices が無い", index))?;
+            let sparse_values = sparse
+                .get("values")
+                .ok_or_else(|| format!("accessor {} の sparse に values が無い", index))?;
+            let mut indices = vec![0.0; sparse_count];
+            self.read_view(
+                get_usize(sparse_indices, "bufferView").unwrap_or(usize::max_value()),
+                get_usize(sparse_indices, "byteOffset").unwrap_or(0),
+                sparse_indices
+                    .get("componentType")
+                    .and_then(Value::as_u64)
+                    .unwrap_or(0),
+                false,
+                1,
+                &mut indices,
+            )?;
+            let mut replaced = vec![0.0; sparse_count * components];
+            self.read_view(
+                get_usize(sparse_values, "bufferView").unwrap_or(usize::max_value()),
+                get_usize(sparse_values, "byteOffset").unwrap_or(0),
+                component_type,
+                normalized,
+                components,
+                &mut replaced,
+            )?;
+            for (i, target) in indices.iter().enumerate() {
+                let target = *target as usize;
+                if target >= count {
+                    return Err(format!(
+                        "accessor {} の sparse のインデックスが範囲外",
+                        index
+                    ));
+                }
+                values[target * components..(target + 1) * components]
+                    .copy_from_slice(&replaced[i * components..(i + 1) * components]);
+            }
+        }
+        Ok((values, components))
+    }
+
+    //  bufferView の byteStride に従って要素を読み、out を埋める
+    fn read_view(
+        &self,
+        index: usize,
+        offset: usize,
+        component_type: u64,
+        normalized: bool,
+        components: usize,
+        out: &mut [f64],
+    ) -> Result<(), String> {
+        let view = array(self.document, "bufferViews")
+            .get(index)
+            .ok_or_else(|| format!("bufferView {} が無い", index))?;
+        let buffer = get_usize(view, "buffer")
+            .and_then(|i| self.buffers.get(i))
+            .ok_or_else(|| format!("bufferView {} のバッファが無い", index))?;
+        let view_offset = get_usize(view, "byteOffset").unwrap_or(0);
+        let view_length = get_usize(view, "byteLength")
+            .ok_or_else(|| format!("bufferView {} に byteLength が無い", index))?;
+        if view_offset + view_length > buffer.len() {
+            return Err(format!("bufferView {} がバッファの範囲外", index));
+        }
+        let data = &buffer[view_offset..view_offset + view_length];
+
+        let component_size = match component_type {
+            BYTE | UNSIGNED_BYTE => 1,
+            SHORT | UNSIGNED_SHORT => 2,
+            UNSIGNED_INT | FLOAT => 4,
+            _ => {
+                return Err(format!(
+                    "componentType {} には対応していない",
+                    component_type
+                ))
+            }
+        };
+        let element_size = component_size * components;
+        let stride = get_usize(view, "byteStride").unwrap_or(element_size);
+        let count = out.len() / components;
+        if count > 0 && offset + stride * (count - 1) + element_size > data.len() {
+            return Err(format!("bufferView {} の範囲を超えて読もうとした", index));
+        }
+
+        for i in 0..count {
+            for c in 0..components {
+                let at = offset + i * stride + c * component_size;
+                let b = &data[at..at + component_size];
+                out[i * components + c] = match component_type {
+                    BYTE => {
+                        let v = b[0] as i8 as f64;
+                        if normalized {
+                            (v / 127.0).max(-1.0)
+                        } else {
+                            v
+                        }
+                    }
+                    UNSIGNED_BYTE => {
+                        let v = b[0] as f64;
+                        if normalized {
+                            v / 255.0
+                        } else {
+                            v
+                        }
+                    }
+                    SHORT => {
+                        let v = i16::from_le_bytes([b[0], b[1]]) as f64;
+                        if normalized {
+                            (v / 32767.0).max(-1.0)
+                        } else {
+                            v
+                        }
+                    }
+                    UNSIGNED_SHORT => {
+                        let v = u16::from_le_bytes([b[0], b[1]]) as f64;
+                        if normalized {
+                            v / 65535.0
+                        } else {
+                            v
+                        }
+                    }
+                    UNSIGNED_INT => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    _ => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                };
+            }
+        }
+        Ok(())
+    }
+
+    fn read_materials(&self) -> Result<Vec<Material>, String> {
+        let mut materials = vec![];
+        for (i, material) in array(self.document, "materials").iter().enumerate() {
+            let pbr = material.get("pbrMetallicRoughness");
+            let base_color = match pbr.and_then(|p| p.get("baseColorFactor")) {
+                Some(v) => floats(v, 4).map_err(|e| format!("マテリアル {}: {}", i, e))?,
+                None => vec![1.0, 1.0, 1.0, 1.0],
+            };
+            materials.push(Material {
+                name: material
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .map(|n| n.to_string())
+                    .unwrap_or_else(|| format!("material{}", i)),
+                diffuse: nalgebra_glm::vec3(base_color[0], base_color[1], base_color[2]),
+                opacity: base_color[3],
+                diffuse_texture: self.texture_path(pbr.and_then(|p| p.get("baseColorTexture"))),
+                normal_texture: self.texture_path(material.get("normalTexture")),
+                ..Material::default()
+            });
+        }
+        Ok(materials)
+    }
+
+    //  バッファに埋め込まれた画像はパスを持たないので None になる
+    fn texture_path(&self, info: Option<&Value>) -> Option<String> {
+        let texture = array(self.document, "textures").get(get_usize(info?, "index")?)?;
+        let image = array(self.document, "images").get(get_usize(texture, "source")?)?;
+        let uri = image.get("uri").and_then(Value::as_str)?;
+        if uri.starts_with("data:") {
+            None
+        } else {
+            Some(self.dir.join(decode_uri(uri)).display().to_string())
+        }
+    }
+}
+
+//  matrix か、translation, rotation, scale の組で表された変換
+fn node_transform(node: &Value) -> Result<nalgebra_glm::Mat4, String> {
+    //  glTF の行列は列優先で並んでいる
+    if let Some(matrix) = node.get("matrix") {
+        return Ok(nalgebra_glm::make_mat4(&floats(matrix, 16)?));
+    }
+    let identity: nalgebra_glm::Mat4 = nalgebra_glm::identity();
+    let translation = match node.get("translation") {
+        Some(v) => floats(v, 3)?,
+        None => vec![0.0, 0.0, 0.0],
+    };
+    let rotation = match node.get("rotation") {
+        Some(v) => floats(v, 4)?,
+        None => vec![0.0, 0.0, 0.0, 1.0],
+    };
+    let scale = match node.get("scale") {
+        Some(v) => floats(v, 3)?,
+        None => vec![1.0, 1.0, 1.0],
+    };
+    Ok(nalgebra_glm::translate(
+        &identity,
+        &nalgebra_glm::vec3(translation[0], translation[1], translation[2]),
+    ) * nalgebra_glm::quat_to_mat4(&nalgebra_glm::quat(
+        rotation[0],
+        rotation[1],
+        rotation[2],
+        rotation[3],
+    )) * nalgebra_glm::scale(&identity, &nalgebra_glm::vec3(scale[0], scale[1], scale[2])))
+}
@@ -0,0 +1,274 @@
+use crate::Vertex;
+
+pub mod collada;
+pub mod gltf;
+pub mod obj;
+mod tangent;
+
+//  インデックスで三角形を表したメッシュ
+//  groups と materials はファイルの形式が持っている場合だけ埋まる
+#[derive(Default)]
+pub struct Mesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    pub groups: Vec<Group>,
+    pub materials: Vec<Material>,
+}
+
+//  同じマテリアルで描画するインデックスの範囲
+pub struct Group {
+    pub name: String,
+    pub material: Option<usize>,
+    pub first_index: u32,
+    pub index_count: u32,
+}
+
+pub struct Material {
+    pub name: String,
+    pub ambient: nalgebra_glm::Vec3,
+    pub diffuse: nalgebra_glm::Vec3,
+    pub specular: nalgebra_glm::Vec3,
+    pub shininess: f32,
+    pub opacity: f32,
+    //  テクスチャのパスはメッシュのファイルからの相対パスを解決したもの
+    pub diffuse_texture: Option<String>,
+    pub normal_texture: Option<String>,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            name: String::new(),
+            ambient: nalgebra_glm::vec3(0.0, 0.0, 0.0),
+            diffuse: nalgebra_glm::vec3(0.8, 0.8, 0.8),
+            specular: nalgebra_glm::vec3(0.0, 0.0, 0.0),
+            shininess: 0.0,
+            opacity: 1.0,
+            diffuse_texture: None,
+            normal_texture: None,
+        }
+    }
+}
+
+//  軸に沿ったバウンディングボックス
+#[derive(Clone, Copy)]
+pub struct Aabb {
+    pub min: nalgebra_glm::Vec3,
+    pub max: nalgebra_glm::Vec3,
+}
+
+impl Aabb {
+    pub fn center(&self) -> nalgebra_glm::Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn size(&self) -> nalgebra_glm::Vec3 {
+        self.max - self.min
+    }
+
+    //  ボックスを囲む球の半径
+    pub fn radius(&self) -> f32 {
+        nalgebra_glm::length(&self.size()) * 0.5
+    }
+}
+
+//  インデックスバッファに書き込む形式のインデックス
+//  頂点が 65536 個以下なら 16 ビットにする
+pub enum Indices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl Indices {
+    pub fn len(&self) -> usize {
+        match self {
+            Indices::U16(i) => i.len(),
+            Indices::U32(i) => i.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        let (ptr, size) = match self {
+            Indices::U16(i) => (i.as_ptr() as *const u8, i.len() * 2),
+            Indices::U32(i) => (i.as_ptr() as *const u8, i.len() * 4),
+        };
+        unsafe { std::slice::from_raw_parts(ptr, size) }
+    }
+}
+
+impl Mesh {
+    pub fn indices(&self) -> Indices {
+        if self.vertices.len() <= u16::max_value() as usize + 1 {
+            Indices::U16(self.indices.iter().map(|i| *i as u16).collect())
+        } else {
+            Indices::U32(self.indices.clone())
+        }
+    }
+
+    //  頂点が無い場合は None を返す
+    pub fn bounds(&self) -> Option<Aabb> {
+        let first = self.vertices.first()?.position;
+        Some(self.vertices.iter().fold(
+            Aabb {
+                min: first,
+                max: first,
+            },
+            |b, v| {
+                let p = v.position;
+                Aabb {
+                    min: nalgebra_glm::vec3(b.min.x.min(p.x), b.min.y.min(p.y), b.min.z.min(p.z)),
+                    max: nalgebra_glm::vec3(b.max.x.max(p.x), b.max.y.max(p.y), b.max.z.max(p.z)),
+                }
+            },
+        ))
+    }
+
+    //  値が全く同じ頂点を 1 つにまとめてインデックスを付け直す
+    pub fn weld(&mut self) {
+        let mut ids = std::collections::HashMap::<[u32; 12], u32>::new();
+        let mut remap = Vec::<u32>::with_capacity(self.vertices.len());
+        let mut welded = Vec::<Vertex>::with_capacity(self.vertices.len());
+        for v in self.vertices.iter() {
+            let id = *ids.entry(vertex_bits(v)).or_insert_with(|| {
+                welded.push(*v);
+                (welded.len() - 1) as u32
+            });
+            remap.push(id);
+        }
+        for i in self.indices.iter_mut() {
+            *i = remap[*i as usize];
+        }
+        self.vertices = welded;
+    }
+
+    //  テクスチャ座標から MikkTSpace と互換の接線を作る
+    //  テクスチャ座標が裏返っている面と共有している頂点は複製するので、頂点とインデックスが増えることがある
+    pub fn generate_tangents(&mut self) {
+        tangent::generate_tangents(&mut self.vertices, &mut self.indices);
+    }
+
+    //  other の頂点とインデックスを後ろに繋げる
+    pub fn append(&mut self, other: Mesh) {
+        let base = self.vertices.len() as u32;
+        let first_index = self.indices.len() as u32;
+        let first_material = self.materials.len();
+        self.vertices.extend(other.vertices);
+        self.indices.extend(other.indices.iter().map(|i| i + base));
+        self.groups.extend(other.groups.into_iter().map(|g| Group {
+            material: g.material.map(|m| m + first_material),
+            first_index: g.first_index + first_index,
+            ..g
+        }));
+        self.materials.extend(other.materials);
+    }
+}
+
+//  拡張子からファイルの形式を判断してメッシュを読み、同じ頂点をまとめる
+pub fn load(path: &str) -> Result<Mesh, String> {
+    let extension = std::path::Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    let mut mesh = match extension.as_ref().map(|e| e.as_str()) {
+        Some("dae") => collada::load(path),
+        Some("gltf") | Some("glb") => gltf::load(path),
+        Some("obj") => obj::load(path),
+        _ => Err(format!("{} は対応していない形式のファイル", path)),
+    }?;
+    mesh.weld();
+    Ok(mesh)
+}
+
+fn vertex_bits(v: &Vertex) -> [u32; 12] {
+    let p = v.position;
+    let n = v.normal;
+    let t = v.tangent;
+    let uv = v.texcoord;
+    [
+        p.x.to_bits(),
+        p.y.to_bits(),
+        p.z.to_bits(),
+        n.x.to_bits(),
+        n.y.to_bits(),
+        n.z.to_bits(),
+        t.x.to_bits(),
+        t.y.to_bits(),
+        t.z.to_bits(),
+        t.w.to_bits(),
+        uv.x.to_bits(),
+        uv.y.to_bits(),
+    ]
+}
+
+//  ノードの変換を頂点に適用する
+pub(crate) fn apply_transform(mut mesh: Mesh, transform: &nalgebra_glm::Mat4) -> Mesh {
+    let linear = nalgebra_glm::mat4_to_mat3(transform);
+    let mirrored = linear.determinant() < 0.0;
+    let normal_matrix = linear
+        .try_inverse()
+        .map(|m| m.transpose())
+        .unwrap_or(linear);
+    for v in mesh.vertices.iter_mut() {
+        let position = v.position;
+        v.position =
+            (transform * nalgebra_glm::vec4(position.x, position.y, position.z, 1.0)).xyz();
+        v.normal = nalgebra_glm::normalize(&(normal_matrix * v.normal));
+        let tangent = v.tangent;
+        let direction = nalgebra_glm::normalize(&(linear * tangent.xyz()));
+        //  裏返す変換では cross(normal, tangent) の向きも裏返るので、従法線を保つよう w を反転する
+        let handedness = if mirrored { -tangent.w } else { tangent.w };
+        v.tangent = nalgebra_glm::vec4(direction.x, direction.y, direction.z, handedness);
+    }
+    //  裏返す変換の場合は三角形の向きを戻す
+    if mirrored {
+        for triangle in mesh.indices.chunks_exact_mut(3) {
+            triangle.swap(1, 2);
+        }
+    }
+    mesh
+}
+
+//  法線が無いメッシュのために、面の法線を面積で重み付けして頂点ごとに足し合わせる
+pub(crate) fn generate_normals(vertices: &mut [Vertex], indices: &[u32]) {
+    for v in vertices.iter_mut() {
+        v.normal = nalgebra_glm::vec3(0.0, 0.0, 0.0);
+    }
+    for triangle in indices.chunks_exact(3) {
+        let p0 = vertices[triangle[0] as usize].position;
+        let p1 = vertices[triangle[1] as usize].position;
+        let p2 = vertices[triangle[2] as usize].position;
+        let face_normal = nalgebra_glm::cross(&(p1 - p0), &(p2 - p0));
+        for i in triangle {
+            let v = &mut vertices[*i as usize];
+            v.normal = v.normal + face_normal;
+        }
+    }
+    for v in vertices.iter_mut() {
+        v.normal = normalize_or(v.normal, nalgebra_glm::vec3(0.0, 0.0, 1.0));
+    }
+}
+
+fn perpendicular(normal: &nalgebra_glm::Vec3) -> nalgebra_glm::Vec3 {
+    let axis = if normal.x.abs() < 0.9 {
+        nalgebra_glm::vec3(1.0, 0.0, 0.0)
+    } else {
+        nalgebra_glm::vec3(0.0, 1.0, 0.0)
+    };
+    normalize_or(
+        axis - normal * nalgebra_glm::dot(normal, &axis),
+        nalgebra_glm::vec3(1.0, 0.0, 0.0),
+    )
+}
+
+fn normalize_or(v: nalgebra_glm::Vec3, fallback: nalgebra_glm::Vec3) -> nalgebra_glm::Vec3 {
+    let length = nalgebra_glm::length(&v);
+    if length > std::f32::EPSILON {
+        v / length
+    } else {
+        fallback
+    }
+}
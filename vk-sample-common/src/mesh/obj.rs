@@ -0,0 +1,261 @@
+//  Wavefront OBJ (.obj) と MTL (.mtl) の読み込み
+//  g, o, usemtl が変わるごとにグループを分ける
+use super::{generate_normals, Group, Material, Mesh};
+use crate::Vertex;
+use std::collections::HashMap;
+
+pub fn load(path: &str) -> Result<Mesh, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{} を読めない: {}", path, e))?;
+    let dir = std::path::Path::new(path)
+        .parent()
+        .unwrap_or(std::path::Path::new(""));
+    parse(&text, dir).map_err(|e| format!("{}: {}", path, e))
+}
+
+//  mtllib とテクスチャのパスは dir からの相対パスとして扱う
+pub fn parse(text: &str, dir: &std::path::Path) -> Result<Mesh, String> {
+    let mut positions = Vec::<nalgebra_glm::Vec3>::new();
+    let mut texcoords = Vec::<nalgebra_glm::Vec2>::new();
+    let mut normals = Vec::<nalgebra_glm::Vec3>::new();
+
+    let mut mesh = Mesh::default();
+    let mut material_ids = HashMap::<String, usize>::new();
+    //  同じ位置、テクスチャ座標、法線の組を指す頂点は 1 つにまとめる
+    let mut vertex_ids = HashMap::<(usize, Option<usize>, Option<usize>), u32>::new();
+    let mut has_normal = Vec::<bool>::new();
+
+    let mut group_name = String::new();
+    let mut material = None;
+    let mut group_changed = true;
+
+    for (line_number, line) in text.lines().enumerate() {
+        let error = |message: String| format!("{} 行目: {}", line_number + 1, message);
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(t) => t,
+            None => continue,
+        };
+        let args = tokens.collect::<Vec<_>>();
+        match keyword {
+            "v" => {
+                let v = parse_floats(&args, 3).map_err(error)?;
+                positions.push(nalgebra_glm::vec3(v[0], v[1], v[2]));
+            }
+            "vt" => {
+                //  v を省略した 1 次元のテクスチャ座標もある
+                let v = parse_floats(&args, 1).map_err(error)?;
+                texcoords.push(nalgebra_glm::vec2(v[0], *v.get(1).unwrap_or(&0.0)));
+            }
+            "vn" => {
+                let v = parse_floats(&args, 3).map_err(error)?;
+                normals.push(nalgebra_glm::vec3(v[0], v[1], v[2]));
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(error(String::from("面の頂点が 3 個未満")));
+                }
+                if group_changed {
+                    mesh.groups.push(Group {
+                        name: group_name.clone(),
+                        material: material,
+                        first_index: mesh.indices.len() as u32,
+                        index_count: 0,
+                    });
+                    group_changed = false;
+                }
+
+                let mut face = Vec::<u32>::with_capacity(args.len());
+                for arg in args {
+                    let key =
+                        parse_face_vertex(arg, positions.len(), texcoords.len(), normals.len())
+                            .map_err(error)?;
+                    let id = match vertex_ids.get(&key) {
+                        Some(id) => *id,
+                        None => {
+                            let mut vertex = Vertex::default();
+                            vertex.position = positions[key.0];
+                            if let Some(i) = key.1 {
+                                vertex.texcoord = texcoords[i];
+                            }
+                            if let Some(i) = key.2 {
+                                vertex.normal = normals[i];
+                            }
+                            let id = mesh.vertices.len() as u32;
+                            mesh.vertices.push(vertex);
+                            has_normal.push(key.2.is_some());
+                            vertex_ids.insert(key, id);
+                            id
+                        }
+                    };
+                    face.push(id);
+                }
+                //  多角形は最初の頂点を中心とした扇形に分割する
+                for i in 2..face.len() {
+                    mesh.indices
+                        .extend_from_slice(&[face[0], face[i - 1], face[i]]);
+                }
+                let group = mesh.groups.last_mut().unwrap();
+                group.index_count = mesh.indices.len() as u32 - group.first_index;
+            }
+            "g" | "o" => {
+                group_name = args.join(" ");
+                group_changed = true;
+            }
+            "usemtl" => {
+                let name = args.join(" ");
+                material = match material_ids.get(&name) {
+                    Some(id) => Some(*id),
+                    None => {
+                        //  MTL に無いマテリアルは既定の値で作る
+                        let id = mesh.materials.len();
+                        mesh.materials.push(Material {
+                            name: name.clone(),
+                            ..Material::default()
+                        });
+                        material_ids.insert(name, id);
+                        Some(id)
+                    }
+                };
+                group_changed = true;
+            }
+            "mtllib" => {
+                for file in args {
+                    let path = dir.join(file);
+                    //  .mtl を付けずに配布される OBJ も多いので、読めない場合は警告だけ出して
+                    //  usemtl で既定の値のマテリアルを作る
+                    let text = match std::fs::read_to_string(&path) {
+                        Ok(t) => t,
+                        Err(e) => {
+                            eprintln!(
+                                "{} を読めないので既定のマテリアルを使う: {}",
+                                path.display(),
+                                e
+                            );
+                            continue;
+                        }
+                    };
+                    let materials =
+                        parse_mtl(&text, path.parent().unwrap_or(std::path::Path::new("")))
+                            .map_err(|e| format!("{}: {}", path.display(), e))?;
+                    for m in materials {
+                        if !material_ids.contains_key(&m.name) {
+                            material_ids.insert(m.name.clone(), mesh.materials.len());
+                            mesh.materials.push(m);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if mesh.indices.is_empty() {
+        return Err(String::from("面が無い"));
+    }
+
+    //  法線が無い頂点だけ面の法線から作った値で埋める
+    if has_normal.iter().any(|h| !h) {
+        let mut generated = mesh.vertices.clone();
+        generate_normals(&mut generated, &mesh.indices);
+        for (i, v) in mesh.vertices.iter_mut().enumerate() {
+            if !has_normal[i] {
+                v.normal = generated[i].normal;
+            }
+        }
+    }
+    mesh.generate_tangents();
+    Ok(mesh)
+}
+
+fn parse_floats(args: &[&str], min: usize) -> Result<Vec<f32>, String> {
+    if args.len() < min {
+        return Err(format!("値が {} 個未満", min));
+    }
+    args.iter()
+        .map(|v| {
+            v.parse::<f32>()
+                .map_err(|_| format!("{} は数値ではない", v))
+        })
+        .collect()
+}
+
+//  1 から始まるインデックスを 0 から始まるインデックスにする
+//  負のインデックスはそれまでに定義された要素の末尾から数える
+fn resolve_index(value: &str, count: usize) -> Result<usize, String> {
+    let index = value
+        .parse::<i64>()
+        .map_err(|_| format!("{} はインデックスではない", value))?;
+    let resolved = if index < 0 {
+        count as i64 + index
+    } else {
+        index - 1
+    };
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(format!("インデックス {} が範囲外", value));
+    }
+    Ok(resolved as usize)
+}
+
+//  v, v/vt, v//vn, v/vt/vn のいずれかの形式
+fn parse_face_vertex(
+    arg: &str,
+    position_count: usize,
+    texcoord_count: usize,
+    normal_count: usize,
+) -> Result<(usize, Option<usize>, Option<usize>), String> {
+    let mut parts = arg.split('/');
+    let position = resolve_index(parts.next().unwrap_or(""), position_count)?;
+    let optional = |part: Option<&str>, count| match part {
+        Some(p) if !p.is_empty() => resolve_index(p, count).map(Some),
+        _ => Ok(None),
+    };
+    let texcoord = optional(parts.next(), texcoord_count)?;
+    let normal = optional(parts.next(), normal_count)?;
+    Ok((position, texcoord, normal))
+}
+
+pub fn parse_mtl(text: &str, dir: &std::path::Path) -> Result<Vec<Material>, String> {
+    let mut materials = Vec::<Material>::new();
+    for (line_number, line) in text.lines().enumerate() {
+        let error = |message: String| format!("{} 行目: {}", line_number + 1, message);
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(t) => t,
+            None => continue,
+        };
+        let args = tokens.collect::<Vec<_>>();
+        if keyword == "newmtl" {
+            materials.push(Material {
+                name: args.join(" "),
+                ..Material::default()
+            });
+            continue;
+        }
+        let material = match materials.last_mut() {
+            Some(m) => m,
+            None => return Err(error(String::from("newmtl より前にマテリアルの値がある"))),
+        };
+        //  テクスチャのオプション (-bm 等) は読み飛ばしてファイル名だけ使う
+        let texture = || args.last().map(|file| dir.join(file).display().to_string());
+        match keyword {
+            "Ka" | "Kd" | "Ks" => {
+                let v = parse_floats(&args, 3).map_err(error)?;
+                let color = nalgebra_glm::vec3(v[0], v[1], v[2]);
+                match keyword {
+                    "Ka" => material.ambient = color,
+                    "Kd" => material.diffuse = color,
+                    _ => material.specular = color,
+                }
+            }
+            "Ns" => material.shininess = parse_floats(&args, 1).map_err(error)?[0],
+            "d" => material.opacity = parse_floats(&args, 1).map_err(error)?[0],
+            "Tr" => material.opacity = 1.0 - parse_floats(&args, 1).map_err(error)?[0],
+            "map_Kd" => material.diffuse_texture = texture(),
+            "map_Bump" | "map_bump" | "bump" | "norm" => material.normal_texture = texture(),
+            _ => {}
+        }
+    }
+    Ok(materials)
+}
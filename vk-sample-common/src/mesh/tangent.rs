@@ -0,0 +1,163 @@
+//  MikkTSpace と同じ規則で接線を作る
+//  - 面の接線はテクスチャ座標の u が増える向き (dP/du) の単位ベクトル
+//  - 頂点の法線と直交させた面の接線を、その頂点での面の角度で重み付けして足し合わせる
+//  - 位置、法線、テクスチャ座標が全て同じ頂点はインデックスが違っても同じ接線にする
+//  - テクスチャ座標が裏返っている面とそうでない面の接線は足し合わせず、共有している頂点は複製する
+//  - 接線の w は従法線の向きで、テクスチャ座標が表向きなら 1 、裏返っていれば -1
+//    シェーダは従法線を w * cross(normal, tangent.xyz) として求める
+use super::{normalize_or, perpendicular};
+use crate::Vertex;
+use std::collections::HashMap;
+
+//  頂点の値のビット列と面の向き
+type CornerKey = ([u32; 8], bool);
+
+fn corner_key(v: &Vertex, orientation: bool) -> CornerKey {
+    let p = v.position;
+    let n = v.normal;
+    let t = v.texcoord;
+    (
+        [
+            p.x.to_bits(),
+            p.y.to_bits(),
+            p.z.to_bits(),
+            n.x.to_bits(),
+            n.y.to_bits(),
+            n.z.to_bits(),
+            t.x.to_bits(),
+            t.y.to_bits(),
+        ],
+        orientation,
+    )
+}
+
+struct Face {
+    //  テクスチャ座標が潰れている面は接線を持たない
+    tangent: Option<nalgebra_glm::Vec3>,
+    //  テクスチャ座標が表向きなら true
+    orientation: bool,
+}
+
+fn face_tangent(vertices: &[Vertex], triangle: &[u32]) -> Face {
+    let v0 = vertices[triangle[0] as usize];
+    let v1 = vertices[triangle[1] as usize];
+    let v2 = vertices[triangle[2] as usize];
+    let d1 = v1.position - v0.position;
+    let d2 = v2.position - v0.position;
+    let t1 = v1.texcoord - v0.texcoord;
+    let t2 = v2.texcoord - v0.texcoord;
+    let signed_area = t1.x * t2.y - t1.y * t2.x;
+    let orientation = signed_area >= 0.0;
+    if signed_area.abs() <= std::f32::EPSILON {
+        return Face {
+            tangent: None,
+            orientation: orientation,
+        };
+    }
+    //  d1 = dP/du * t1.x + dP/dv * t1.y 、 d2 = dP/du * t2.x + dP/dv * t2.y を dP/du について解く
+    //  テクスチャ座標が裏返っていても u が増える向きのまま
+    let tangent = (d1 * t2.y - d2 * t1.y) / signed_area;
+    let length = nalgebra_glm::length(&tangent);
+    if length <= std::f32::EPSILON {
+        return Face {
+            tangent: None,
+            orientation: orientation,
+        };
+    }
+    Face {
+        tangent: Some(tangent / length),
+        orientation: orientation,
+    }
+}
+
+//  法線と直交する平面に射影した 2 辺のなす角
+fn corner_angle(
+    position: nalgebra_glm::Vec3,
+    previous: nalgebra_glm::Vec3,
+    next: nalgebra_glm::Vec3,
+    normal: &nalgebra_glm::Vec3,
+) -> f32 {
+    let zero = nalgebra_glm::vec3(0.0, 0.0, 0.0);
+    let project = |edge: nalgebra_glm::Vec3| {
+        normalize_or(edge - normal * nalgebra_glm::dot(normal, &edge), zero)
+    };
+    let cos = nalgebra_glm::dot(&project(previous - position), &project(next - position));
+    cos.max(-1.0).min(1.0).acos()
+}
+
+fn with_handedness(direction: nalgebra_glm::Vec3, orientation: bool) -> nalgebra_glm::Vec4 {
+    let w = if orientation { 1.0 } else { -1.0 };
+    nalgebra_glm::vec4(direction.x, direction.y, direction.z, w)
+}
+
+//  面の向きによって頂点を複製した場合は vertices に追加して indices を書き換える
+pub(crate) fn generate_tangents(vertices: &mut Vec<Vertex>, indices: &mut [u32]) {
+    let triangle_count = indices.len() / 3;
+    let faces = indices
+        .chunks_exact(3)
+        .map(|triangle| face_tangent(vertices, triangle))
+        .collect::<Vec<_>>();
+
+    let mut sums = HashMap::<CornerKey, nalgebra_glm::Vec3>::new();
+    for (triangle, face) in indices.chunks_exact(3).zip(faces.iter()) {
+        let tangent = match face.tangent {
+            Some(t) => t,
+            None => continue,
+        };
+        for corner in 0..3 {
+            let v = vertices[triangle[corner] as usize];
+            let normal = normalize_or(v.normal, nalgebra_glm::vec3(0.0, 0.0, 1.0));
+            let projected = tangent - normal * nalgebra_glm::dot(&normal, &tangent);
+            if nalgebra_glm::length(&projected) <= std::f32::EPSILON {
+                continue;
+            }
+            let weight = corner_angle(
+                v.position,
+                vertices[triangle[(corner + 2) % 3] as usize].position,
+                vertices[triangle[(corner + 1) % 3] as usize].position,
+                &normal,
+            );
+            let sum = sums
+                .entry(corner_key(&v, face.orientation))
+                .or_insert(nalgebra_glm::vec3(0.0, 0.0, 0.0));
+            *sum = *sum + nalgebra_glm::normalize(&projected) * weight;
+        }
+    }
+
+    //  どの面からも接線が決まらない頂点は法線と直交する適当な向きにする
+    for v in vertices.iter_mut() {
+        let normal = normalize_or(v.normal, nalgebra_glm::vec3(0.0, 0.0, 1.0));
+        v.tangent = with_handedness(perpendicular(&normal), true);
+    }
+
+    let mut owner = vec![None; vertices.len()];
+    let mut split = HashMap::<(u32, bool), u32>::new();
+    for (i, face) in faces.iter().enumerate().take(triangle_count) {
+        for corner in 0..3 {
+            let index = indices[i * 3 + corner];
+            let id = match owner[index as usize] {
+                None => {
+                    owner[index as usize] = Some(face.orientation);
+                    index
+                }
+                Some(o) if o == face.orientation => index,
+                Some(_) => *split.entry((index, face.orientation)).or_insert_with(|| {
+                    vertices.push(vertices[index as usize]);
+                    (vertices.len() - 1) as u32
+                }),
+            };
+            indices[i * 3 + corner] = id;
+
+            let v = vertices[id as usize];
+            let normal = normalize_or(v.normal, nalgebra_glm::vec3(0.0, 0.0, 1.0));
+            let direction = match sums.get(&corner_key(&v, face.orientation)) {
+                Some(sum) => normalize_or(
+                    sum - normal * nalgebra_glm::dot(&normal, sum),
+                    perpendicular(&normal),
+                ),
+                None => perpendicular(&normal),
+            };
+            vertices[id as usize].tangent = with_handedness(direction, face.orientation);
+        }
+    }
+}
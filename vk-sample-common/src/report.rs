@@ -0,0 +1,886 @@
+//  物理デバイスの情報を JSON にまとめたもの
+//  テキストで表示する場合も同じ JSON から書き出すので、どちらの形式にも同じ項目が入る
+use ash::version::InstanceV1_0;
+use serde_json::json;
+
+//  構造体のフィールドをフィールド名をキーにしたオブジェクトにする
+//  $convert を渡すと値を変換してから入れる
+macro_rules! fields {
+    ($value:expr; $($field:ident),* $(,)?) => {
+        fields!($value, std::convert::identity; $($field),*)
+    };
+    ($value:expr, $convert:expr; $($field:ident),* $(,)?) => {{
+        let mut map = serde_json::Map::new();
+        $(
+            map.insert(
+                stringify!($field).to_string(),
+                serde_json::json!($convert($value.$field)),
+            );
+        )*
+        map
+    }};
+}
+
+//  PhysicalDeviceFeatures のフィールド名を $callback に渡す
+//  JSON にする場合と名前から有効にする場合で同じ一覧を使う
+macro_rules! feature_fields {
+    ($callback:ident!($($args:tt)*)) => {
+        $callback!(
+            $($args)*
+            robust_buffer_access,
+            full_draw_index_uint32,
+            image_cube_array,
+            independent_blend,
+            geometry_shader,
+            tessellation_shader,
+            sample_rate_shading,
+            dual_src_blend,
+            logic_op,
+            multi_draw_indirect,
+            draw_indirect_first_instance,
+            depth_clamp,
+            depth_bias_clamp,
+            fill_mode_non_solid,
+            depth_bounds,
+            wide_lines,
+            large_points,
+            alpha_to_one,
+            multi_viewport,
+            sampler_anisotropy,
+            texture_compression_etc2,
+            texture_compression_astc_ldr,
+            texture_compression_bc,
+            occlusion_query_precise,
+            pipeline_statistics_query,
+            vertex_pipeline_stores_and_atomics,
+            fragment_stores_and_atomics,
+            shader_tessellation_and_geometry_point_size,
+            shader_image_gather_extended,
+            shader_storage_image_extended_formats,
+            shader_storage_image_multisample,
+            shader_storage_image_read_without_format,
+            shader_storage_image_write_without_format,
+            shader_uniform_buffer_array_dynamic_indexing,
+            shader_sampled_image_array_dynamic_indexing,
+            shader_storage_buffer_array_dynamic_indexing,
+            shader_storage_image_array_dynamic_indexing,
+            shader_clip_distance,
+            shader_cull_distance,
+            shader_float64,
+            shader_int64,
+            shader_int16,
+            shader_resource_residency,
+            shader_resource_min_lod,
+            sparse_binding,
+            sparse_residency_buffer,
+            sparse_residency_image2_d,
+            sparse_residency_image3_d,
+            sparse_residency2_samples,
+            sparse_residency4_samples,
+            sparse_residency8_samples,
+            sparse_residency16_samples,
+            sparse_residency_aliased,
+            variable_multisample_rate,
+            inherited_queries,
+        )
+    };
+}
+
+//  描画に関係する項目
+//  差分を取る場合は --all を指定しない限りこれらだけを比べる
+pub const RENDERER_EXTENSIONS: &[&str] = &[
+    "VK_KHR_swapchain",
+    "VK_KHR_maintenance1",
+    "VK_KHR_dedicated_allocation",
+    "VK_KHR_get_memory_requirements2",
+    "VK_KHR_bind_memory2",
+    "VK_EXT_memory_budget",
+    "VK_KHR_portability_subset",
+];
+
+pub const RENDERER_FEATURES: &[&str] = &[
+    "full_draw_index_uint32",
+    "sampler_anisotropy",
+    "fill_mode_non_solid",
+    "wide_lines",
+    "large_points",
+    "depth_clamp",
+    "depth_bias_clamp",
+    "independent_blend",
+    "texture_compression_bc",
+    "texture_compression_etc2",
+    "texture_compression_astc_ldr",
+    "shader_clip_distance",
+];
+
+pub const RENDERER_LIMITS: &[&str] = &[
+    "max_image_dimension2_d",
+    "max_framebuffer_width",
+    "max_framebuffer_height",
+    "max_color_attachments",
+    "max_push_constants_size",
+    "max_bound_descriptor_sets",
+    "max_per_stage_descriptor_uniform_buffers",
+    "max_uniform_buffer_range",
+    "max_vertex_input_attributes",
+    "max_vertex_input_bindings",
+    "max_vertex_input_binding_stride",
+    "max_draw_indexed_index_value",
+    "max_memory_allocation_count",
+    "max_sampler_anisotropy",
+    "buffer_image_granularity",
+    "min_uniform_buffer_offset_alignment",
+    "optimal_buffer_copy_offset_alignment",
+    "optimal_buffer_copy_row_pitch_alignment",
+    "non_coherent_atom_size",
+    "framebuffer_color_sample_counts",
+    "framebuffer_depth_sample_counts",
+    "timestamp_period",
+];
+
+//  スワップチェーンとオフスクリーンのカラー、デプス、頂点属性のフォーマット
+pub const RENDERER_FORMATS: &[ash::vk::Format] = &[
+    ash::vk::Format::B8G8R8A8_UNORM,
+    ash::vk::Format::B8G8R8A8_SRGB,
+    ash::vk::Format::R8G8B8A8_UNORM,
+    ash::vk::Format::R8G8B8A8_SRGB,
+    ash::vk::Format::D16_UNORM,
+    ash::vk::Format::D32_SFLOAT,
+    ash::vk::Format::D24_UNORM_S8_UINT,
+    ash::vk::Format::R32G32B32_SFLOAT,
+    ash::vk::Format::R32G32_SFLOAT,
+];
+
+pub fn version(ver: u32) -> String {
+    format!(
+        "{}.{}.{}",
+        ash::vk::version_major(ver),
+        ash::vk::version_minor(ver),
+        ash::vk::version_patch(ver)
+    )
+}
+
+fn flag_names(raw: u32, names: &[(u32, &str)]) -> Vec<String> {
+    names
+        .iter()
+        .filter(|(flag, _)| raw & flag == *flag)
+        .map(|(_, name)| name.to_string())
+        .collect()
+}
+
+fn queue_flag_names(flags: ash::vk::QueueFlags) -> Vec<String> {
+    flag_names(
+        flags.as_raw(),
+        &[
+            (ash::vk::QueueFlags::GRAPHICS.as_raw(), "GRAPHICS"),
+            (ash::vk::QueueFlags::COMPUTE.as_raw(), "COMPUTE"),
+            (ash::vk::QueueFlags::TRANSFER.as_raw(), "TRANSFER"),
+            (
+                ash::vk::QueueFlags::SPARSE_BINDING.as_raw(),
+                "SPARSE_BINDING",
+            ),
+            (ash::vk::QueueFlags::PROTECTED.as_raw(), "PROTECTED"),
+        ],
+    )
+}
+
+pub(crate) fn memory_heap_flag_names(flags: ash::vk::MemoryHeapFlags) -> Vec<String> {
+    flag_names(
+        flags.as_raw(),
+        &[
+            (
+                ash::vk::MemoryHeapFlags::DEVICE_LOCAL.as_raw(),
+                "DEVICE_LOCAL",
+            ),
+            (
+                ash::vk::MemoryHeapFlags::MULTI_INSTANCE.as_raw(),
+                "MULTI_INSTANCE",
+            ),
+        ],
+    )
+}
+
+pub fn memory_property_flag_names(flags: ash::vk::MemoryPropertyFlags) -> Vec<String> {
+    flag_names(
+        flags.as_raw(),
+        &[
+            (
+                ash::vk::MemoryPropertyFlags::DEVICE_LOCAL.as_raw(),
+                "DEVICE_LOCAL",
+            ),
+            (
+                ash::vk::MemoryPropertyFlags::HOST_VISIBLE.as_raw(),
+                "HOST_VISIBLE",
+            ),
+            (
+                ash::vk::MemoryPropertyFlags::HOST_COHERENT.as_raw(),
+                "HOST_COHERENT",
+            ),
+            (
+                ash::vk::MemoryPropertyFlags::HOST_CACHED.as_raw(),
+                "HOST_CACHED",
+            ),
+            (
+                ash::vk::MemoryPropertyFlags::LAZILY_ALLOCATED.as_raw(),
+                "LAZILY_ALLOCATED",
+            ),
+            (
+                ash::vk::MemoryPropertyFlags::PROTECTED.as_raw(),
+                "PROTECTED",
+            ),
+            (
+                ash::vk::MemoryPropertyFlags::DEVICE_COHERENT_AMD.as_raw(),
+                "DEVICE_COHERENT_AMD",
+            ),
+            (
+                ash::vk::MemoryPropertyFlags::DEVICE_UNCACHED_AMD.as_raw(),
+                "DEVICE_UNCACHED_AMD",
+            ),
+        ],
+    )
+}
+
+pub fn format_feature_flag_names(flags: ash::vk::FormatFeatureFlags) -> Vec<String> {
+    flag_names(
+        flags.as_raw(),
+        &[
+            (
+                ash::vk::FormatFeatureFlags::SAMPLED_IMAGE.as_raw(),
+                "SAMPLED_IMAGE",
+            ),
+            (
+                ash::vk::FormatFeatureFlags::STORAGE_IMAGE.as_raw(),
+                "STORAGE_IMAGE",
+            ),
+            (
+                ash::vk::FormatFeatureFlags::UNIFORM_TEXEL_BUFFER.as_raw(),
+                "UNIFORM_TEXEL_BUFFER",
+            ),
+            (
+                ash::vk::FormatFeatureFlags::STORAGE_TEXEL_BUFFER.as_raw(),
+                "STORAGE_TEXEL_BUFFER",
+            ),
+            (
+                ash::vk::FormatFeatureFlags::VERTEX_BUFFER.as_raw(),
+                "VERTEX_BUFFER",
+            ),
+            (
+                ash::vk::FormatFeatureFlags::COLOR_ATTACHMENT.as_raw(),
+                "COLOR_ATTACHMENT",
+            ),
+            (
+                ash::vk::FormatFeatureFlags::COLOR_ATTACHMENT_BLEND.as_raw(),
+                "COLOR_ATTACHMENT_BLEND",
+            ),
+            (
+                ash::vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT.as_raw(),
+                "DEPTH_STENCIL_ATTACHMENT",
+            ),
+            (ash::vk::FormatFeatureFlags::BLIT_SRC.as_raw(), "BLIT_SRC"),
+            (ash::vk::FormatFeatureFlags::BLIT_DST.as_raw(), "BLIT_DST"),
+            (
+                ash::vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR.as_raw(),
+                "SAMPLED_IMAGE_FILTER_LINEAR",
+            ),
+            (
+                ash::vk::FormatFeatureFlags::TRANSFER_SRC.as_raw(),
+                "TRANSFER_SRC",
+            ),
+            (
+                ash::vk::FormatFeatureFlags::TRANSFER_DST.as_raw(),
+                "TRANSFER_DST",
+            ),
+        ],
+    )
+}
+
+//  サンプル数のフラグを [1, 2, 4, ...] の形にする
+fn sample_counts(flags: ash::vk::SampleCountFlags) -> Vec<u32> {
+    (0..7)
+        .map(|bit| 1_u32 << bit)
+        .filter(|count| flags.as_raw() & count != 0)
+        .collect()
+}
+
+pub(crate) fn c_str(name: &[std::os::raw::c_char]) -> String {
+    unsafe { std::ffi::CStr::from_ptr(name.as_ptr()) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+pub(crate) fn features(features: &ash::vk::PhysicalDeviceFeatures) -> serde_json::Value {
+    let enabled = |value: ash::vk::Bool32| value != ash::vk::FALSE;
+    serde_json::Value::Object(feature_fields!(fields!(features, enabled;)))
+}
+
+//  name の機能を有効にする
+//  そういう名前の機能が無い場合は false を返す
+pub(crate) fn enable_feature(features: &mut ash::vk::PhysicalDeviceFeatures, name: &str) -> bool {
+    macro_rules! enable {
+        ($($field:ident),* $(,)?) => {{
+            $(
+                if name == stringify!($field) {
+                    features.$field = ash::vk::TRUE;
+                    return true;
+                }
+            )*
+            false
+        }};
+    }
+    feature_fields!(enable!())
+}
+
+pub(crate) fn limits(limits: &ash::vk::PhysicalDeviceLimits) -> serde_json::Value {
+    let mut map = fields!(
+        limits;
+        max_image_dimension1_d,
+        max_image_dimension2_d,
+        max_image_dimension3_d,
+        max_image_dimension_cube,
+        max_image_array_layers,
+        max_texel_buffer_elements,
+        max_uniform_buffer_range,
+        max_storage_buffer_range,
+        max_push_constants_size,
+        max_memory_allocation_count,
+        max_sampler_allocation_count,
+        max_bound_descriptor_sets,
+        max_per_stage_descriptor_samplers,
+        max_per_stage_descriptor_uniform_buffers,
+        max_per_stage_descriptor_storage_buffers,
+        max_per_stage_descriptor_sampled_images,
+        max_per_stage_descriptor_storage_images,
+        max_per_stage_descriptor_input_attachments,
+        max_per_stage_resources,
+        max_descriptor_set_samplers,
+        max_descriptor_set_uniform_buffers,
+        max_descriptor_set_uniform_buffers_dynamic,
+        max_descriptor_set_storage_buffers,
+        max_descriptor_set_storage_buffers_dynamic,
+        max_descriptor_set_sampled_images,
+        max_descriptor_set_storage_images,
+        max_descriptor_set_input_attachments,
+        max_vertex_input_attributes,
+        max_vertex_input_bindings,
+        max_vertex_input_attribute_offset,
+        max_vertex_input_binding_stride,
+        max_vertex_output_components,
+        max_tessellation_generation_level,
+        max_tessellation_patch_size,
+        max_tessellation_control_per_vertex_input_components,
+        max_tessellation_control_per_vertex_output_components,
+        max_tessellation_control_per_patch_output_components,
+        max_tessellation_control_total_output_components,
+        max_tessellation_evaluation_input_components,
+        max_tessellation_evaluation_output_components,
+        max_geometry_shader_invocations,
+        max_geometry_input_components,
+        max_geometry_output_components,
+        max_geometry_output_vertices,
+        max_geometry_total_output_components,
+        max_fragment_input_components,
+        max_fragment_output_attachments,
+        max_fragment_dual_src_attachments,
+        max_fragment_combined_output_resources,
+        max_compute_shared_memory_size,
+        max_compute_work_group_invocations,
+        sub_pixel_precision_bits,
+        sub_texel_precision_bits,
+        mipmap_precision_bits,
+        max_draw_indexed_index_value,
+        max_draw_indirect_count,
+        max_viewports,
+        viewport_sub_pixel_bits,
+        max_texel_offset,
+        max_texel_gather_offset,
+        sub_pixel_interpolation_offset_bits,
+        max_framebuffer_width,
+        max_framebuffer_height,
+        max_framebuffer_layers,
+        max_color_attachments,
+        max_sample_mask_words,
+        max_clip_distances,
+        max_cull_distances,
+        max_combined_clip_and_cull_distances,
+        discrete_queue_priorities,
+    );
+    map.append(&mut fields!(
+        limits;
+        buffer_image_granularity,
+        sparse_address_space_size,
+        min_memory_map_alignment,
+        min_texel_buffer_offset_alignment,
+        min_uniform_buffer_offset_alignment,
+        min_storage_buffer_offset_alignment,
+        optimal_buffer_copy_offset_alignment,
+        optimal_buffer_copy_row_pitch_alignment,
+        non_coherent_atom_size,
+        min_texel_offset,
+        min_texel_gather_offset,
+        max_sampler_lod_bias,
+        max_sampler_anisotropy,
+        min_interpolation_offset,
+        max_interpolation_offset,
+        timestamp_period,
+        point_size_granularity,
+        line_width_granularity,
+        max_compute_work_group_count,
+        max_compute_work_group_size,
+        max_viewport_dimensions,
+        viewport_bounds_range,
+        point_size_range,
+        line_width_range,
+    ));
+    map.append(&mut fields!(
+        limits, sample_counts;
+        framebuffer_color_sample_counts,
+        framebuffer_depth_sample_counts,
+        framebuffer_stencil_sample_counts,
+        framebuffer_no_attachments_sample_counts,
+        sampled_image_color_sample_counts,
+        sampled_image_integer_sample_counts,
+        sampled_image_depth_sample_counts,
+        sampled_image_stencil_sample_counts,
+        storage_image_sample_counts,
+    ));
+    let enabled = |value: ash::vk::Bool32| value != ash::vk::FALSE;
+    map.append(&mut fields!(
+        limits, enabled;
+        timestamp_compute_and_graphics,
+        strict_lines,
+        standard_sample_locations,
+    ));
+    serde_json::Value::Object(map)
+}
+
+//  描画で使うフォーマットの対応状況をタイリングとバッファごとに調べる
+fn formats(instance: &ash::Instance, device: ash::vk::PhysicalDevice) -> serde_json::Value {
+    let mut map = serde_json::Map::new();
+    for format in RENDERER_FORMATS.iter() {
+        let props = unsafe { instance.get_physical_device_format_properties(device, *format) };
+        map.insert(
+            format!("{:?}", format),
+            json!({
+                "linear_tiling": format_feature_flag_names(props.linear_tiling_features),
+                "optimal_tiling": format_feature_flag_names(props.optimal_tiling_features),
+                "buffer": format_feature_flag_names(props.buffer_features),
+            }),
+        );
+    }
+    serde_json::Value::Object(map)
+}
+
+//  surface を渡すとキューファミリーが表示できるかどうかと、サーフェスのフォーマットと表示モードも入れる
+//  渡さない場合はそれらの項目を null にする
+pub fn device_report(
+    instance: &ash::Instance,
+    device: ash::vk::PhysicalDevice,
+    surface: Option<(&ash::extensions::khr::Surface, ash::vk::SurfaceKHR)>,
+) -> serde_json::Value {
+    let props = unsafe { instance.get_physical_device_properties(device) };
+    let device_features = unsafe { instance.get_physical_device_features(device) };
+    let memory = unsafe { instance.get_physical_device_memory_properties(device) };
+    let queue_families = unsafe { instance.get_physical_device_queue_family_properties(device) };
+    let extensions =
+        unsafe { instance.enumerate_device_extension_properties(device) }.unwrap_or_default();
+
+    let mut extension_map = serde_json::Map::new();
+    for ext in extensions.iter() {
+        extension_map.insert(c_str(&ext.extension_name), json!(ext.spec_version));
+    }
+
+    let memory_heaps = memory.memory_heaps[..memory.memory_heap_count as usize]
+        .iter()
+        .map(|heap| {
+            json!({
+                "size": heap.size,
+                "flags": memory_heap_flag_names(heap.flags),
+            })
+        })
+        .collect::<Vec<_>>();
+    let memory_types = memory.memory_types[..memory.memory_type_count as usize]
+        .iter()
+        .map(|memory_type| {
+            json!({
+                "heap_index": memory_type.heap_index,
+                "property_flags": memory_property_flag_names(memory_type.property_flags),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let queue_families = queue_families
+        .iter()
+        .enumerate()
+        .map(|(i, family)| {
+            let granularity = family.min_image_transfer_granularity;
+            json!({
+                "queue_count": family.queue_count,
+                "flags": queue_flag_names(family.queue_flags),
+                "timestamp_valid_bits": family.timestamp_valid_bits,
+                "min_image_transfer_granularity":
+                    [granularity.width, granularity.height, granularity.depth],
+                "present": surface.and_then(|(loader, surface)| unsafe {
+                    loader
+                        .get_physical_device_surface_support(device, i as u32, surface)
+                        .ok()
+                }),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let surface_formats = surface.and_then(|(loader, surface)| {
+        unsafe { loader.get_physical_device_surface_formats(device, surface) }
+            .ok()
+            .map(|formats| {
+                formats
+                    .iter()
+                    .map(|format| {
+                        json!({
+                            "format": format!("{:?}", format.format),
+                            "color_space": format!("{:?}", format.color_space),
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            })
+    });
+    let present_modes = surface.and_then(|(loader, surface)| {
+        unsafe { loader.get_physical_device_surface_present_modes(device, surface) }
+            .ok()
+            .map(|modes| {
+                modes
+                    .iter()
+                    .map(|mode| format!("{:?}", mode))
+                    .collect::<Vec<_>>()
+            })
+    });
+
+    json!({
+        "name": c_str(&props.device_name),
+        "device_type": format!("{:?}", props.device_type),
+        "api_version": version(props.api_version),
+        "driver_version": version(props.driver_version),
+        "vendor_id": props.vendor_id,
+        "device_id": props.device_id,
+        "extensions": extension_map,
+        "features": features(&device_features),
+        "limits": limits(&props.limits),
+        "memory_heaps": memory_heaps,
+        "memory_types": memory_types,
+        "formats": formats(instance, device),
+        "queue_families": queue_families,
+        "surface_formats": surface_formats,
+        "present_modes": present_modes,
+    })
+}
+
+pub(crate) fn device_type_name(device_type: &str) -> &'static str {
+    match device_type {
+        "INTEGRATED_GPU" => "統合GPU",
+        "DISCRETE_GPU" => "ディスクリートGPU",
+        "VIRTUAL_GPU" => "仮想GPU",
+        "CPU" => "CPU",
+        _ => "その他のデバイス",
+    }
+}
+
+//  配列は "a, b" の形に、それ以外は JSON のまま書く
+fn text_value(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Array(items) => {
+            items.iter().map(text_value).collect::<Vec<_>>().join(", ")
+        }
+        _ => value.to_string(),
+    }
+}
+
+pub(crate) fn join_flags(flags: &serde_json::Value) -> String {
+    match flags.as_array() {
+        Some(flags) if !flags.is_empty() => {
+            flags.iter().map(text_value).collect::<Vec<_>>().join(" | ")
+        }
+        _ => String::from("なし"),
+    }
+}
+
+//  device_report の結果をデバイスごとに字下げしたテキストで書き出す
+pub fn print_text(device: &serde_json::Value) {
+    println!(
+        "{}({})",
+        text_value(&device["name"]),
+        device_type_name(device["device_type"].as_str().unwrap_or(""))
+    );
+    println!("  APIバージョン");
+    println!("    {}", text_value(&device["api_version"]));
+    println!("  ドライババージョン");
+    println!("    {}", text_value(&device["driver_version"]));
+    println!("  ベンダーID");
+    println!("    {}", text_value(&device["vendor_id"]));
+    println!("  デバイスID");
+    println!("    {}", text_value(&device["device_id"]));
+
+    println!("  利用可能な拡張");
+    if let Some(extensions) = device["extensions"].as_object() {
+        for (name, spec_version) in extensions.iter() {
+            println!("    {} {}", name, spec_version);
+        }
+    }
+
+    println!("  機能");
+    if let Some(features) = device["features"].as_object() {
+        for (name, enabled) in features.iter() {
+            println!(
+                "    {}: {}",
+                name,
+                if enabled.as_bool() == Some(true) {
+                    "あり"
+                } else {
+                    "なし"
+                }
+            );
+        }
+    }
+
+    println!("  制限");
+    if let Some(limits) = device["limits"].as_object() {
+        for (name, value) in limits.iter() {
+            println!("    {}: {}", name, text_value(value));
+        }
+    }
+
+    println!("  メモリヒープ");
+    for (i, heap) in device["memory_heaps"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .enumerate()
+    {
+        println!(
+            "    {}: {} バイト ({})",
+            i,
+            heap["size"],
+            join_flags(&heap["flags"])
+        );
+    }
+
+    println!("  メモリタイプ");
+    for (i, memory_type) in device["memory_types"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .enumerate()
+    {
+        println!(
+            "    {}: ヒープ {} ({})",
+            i,
+            memory_type["heap_index"],
+            join_flags(&memory_type["property_flags"])
+        );
+    }
+
+    println!("  フォーマット");
+    if let Some(formats) = device["formats"].as_object() {
+        for (name, format) in formats.iter() {
+            println!("    {}", name);
+            println!("      リニア: {}", join_flags(&format["linear_tiling"]));
+            println!("      最適: {}", join_flags(&format["optimal_tiling"]));
+            println!("      バッファ: {}", join_flags(&format["buffer"]));
+        }
+    }
+
+    println!("  キューファミリー");
+    for (i, family) in device["queue_families"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .enumerate()
+    {
+        println!(
+            "    {}: {} 個 ({}) タイムスタンプ {} ビット 転送粒度 {}{}",
+            i,
+            family["queue_count"],
+            join_flags(&family["flags"]),
+            family["timestamp_valid_bits"],
+            text_value(&family["min_image_transfer_granularity"]),
+            match family["present"].as_bool() {
+                Some(true) => " 表示可能",
+                Some(false) => " 表示不可",
+                None => "",
+            }
+        );
+    }
+
+    if let Some(formats) = device["surface_formats"].as_array() {
+        println!("  サーフェスのフォーマット");
+        for format in formats.iter() {
+            println!(
+                "    {} {}",
+                text_value(&format["format"]),
+                text_value(&format["color_space"])
+            );
+        }
+    }
+    if let Some(modes) = device["present_modes"].as_array() {
+        println!("  表示モード");
+        for mode in modes.iter() {
+            println!("    {}", text_value(mode));
+        }
+    }
+}
+
+//  2 つのレポートで値が違う項目
+//  片方にしか無い項目は反対側が None になる
+pub struct Difference {
+    pub section: &'static str,
+    pub name: String,
+    pub left: Option<serde_json::Value>,
+    pub right: Option<serde_json::Value>,
+}
+
+//  オブジェクトのキーごとに値を比べる
+//  relevant に入っているキーだけを比べ、 None なら全てのキーを比べる
+fn diff_objects(
+    section: &'static str,
+    left: &serde_json::Value,
+    right: &serde_json::Value,
+    relevant: Option<&[&str]>,
+    differences: &mut Vec<Difference>,
+) {
+    let empty = serde_json::Map::new();
+    let left = left.as_object().unwrap_or(&empty);
+    let right = right.as_object().unwrap_or(&empty);
+    let mut names = left.keys().chain(right.keys()).collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+    for name in names {
+        if let Some(relevant) = relevant {
+            if !relevant.contains(&name.as_str()) {
+                continue;
+            }
+        }
+        let l = left.get(name);
+        let r = right.get(name);
+        if l != r {
+            differences.push(Difference {
+                section: section,
+                name: name.clone(),
+                left: l.cloned(),
+                right: r.cloned(),
+            });
+        }
+    }
+}
+
+//  メモリタイプは番号ではなくプロパティの組み合わせごとの数で比べる
+fn memory_type_counts(device: &serde_json::Value) -> serde_json::Value {
+    let mut map = serde_json::Map::new();
+    for memory_type in device["memory_types"].as_array().into_iter().flatten() {
+        let count = map
+            .entry(join_flags(&memory_type["property_flags"]))
+            .or_insert(json!(0));
+        *count = json!(count.as_u64().unwrap_or(0) + 1);
+    }
+    serde_json::Value::Object(map)
+}
+
+//  フォーマットはフォーマット、タイリング、機能の組ごとに対応しているかどうかで比べる
+fn format_support(device: &serde_json::Value) -> serde_json::Value {
+    let mut map = serde_json::Map::new();
+    for (name, format) in device["formats"].as_object().into_iter().flatten() {
+        for tiling in ["linear_tiling", "optimal_tiling", "buffer"].iter() {
+            for feature in format[*tiling].as_array().into_iter().flatten() {
+                map.insert(
+                    format!("{} {} {}", name, tiling, text_value(feature)),
+                    json!(true),
+                );
+            }
+        }
+    }
+    serde_json::Value::Object(map)
+}
+
+//  device_report で作った 2 つのデバイスの違いを返す
+//  all が false の場合は拡張、機能、制限を RENDERER_* に挙げたものだけ比べる
+pub fn diff_reports(
+    left: &serde_json::Value,
+    right: &serde_json::Value,
+    all: bool,
+) -> Vec<Difference> {
+    let mut differences = Vec::new();
+    let relevant = |names: &'static [&'static str]| if all { None } else { Some(names) };
+    diff_objects(
+        "extensions",
+        &left["extensions"],
+        &right["extensions"],
+        relevant(RENDERER_EXTENSIONS),
+        &mut differences,
+    );
+    diff_objects(
+        "features",
+        &left["features"],
+        &right["features"],
+        relevant(RENDERER_FEATURES),
+        &mut differences,
+    );
+    diff_objects(
+        "limits",
+        &left["limits"],
+        &right["limits"],
+        relevant(RENDERER_LIMITS),
+        &mut differences,
+    );
+    diff_objects(
+        "memory_types",
+        &memory_type_counts(left),
+        &memory_type_counts(right),
+        None,
+        &mut differences,
+    );
+    diff_objects(
+        "formats",
+        &format_support(left),
+        &format_support(right),
+        None,
+        &mut differences,
+    );
+    differences
+}
+
+fn section_name(section: &str) -> &'static str {
+    match section {
+        "extensions" => "拡張",
+        "features" => "機能",
+        "limits" => "制限",
+        "memory_types" => "メモリタイプ",
+        "formats" => "フォーマット",
+        _ => "その他",
+    }
+}
+
+//  左にしか無いものは -、右にしか無いものは +、値が違うものは ~ を付けて書く
+pub fn format_differences(differences: &[Difference]) -> String {
+    let mut text = String::new();
+    let mut section = "";
+    for difference in differences.iter() {
+        if difference.section != section {
+            section = difference.section;
+            text += &format!("{}\n", section_name(section));
+        }
+        text += &match (difference.left.as_ref(), difference.right.as_ref()) {
+            (Some(l), None) => format!("  - {}: {}\n", difference.name, text_value(l)),
+            (None, Some(r)) => format!("  + {}: {}\n", difference.name, text_value(r)),
+            (Some(l), Some(r)) => format!(
+                "  ~ {}: {} -> {}\n",
+                difference.name,
+                text_value(l),
+                text_value(r)
+            ),
+            (None, None) => String::new(),
+        };
+    }
+    text
+}
+
+pub fn print_differences(differences: &[Difference]) {
+    print!("{}", format_differences(differences));
+}
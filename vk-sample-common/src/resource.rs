@@ -0,0 +1,420 @@
+//  Vulkan のオブジェクトを所有し、 Drop で破棄する型
+//  デバイスやアロケータを借用するので、それらより先に破棄される事をコンパイラが保証する
+//  構造体のフィールドは宣言した順に破棄されるので、他のオブジェクトを参照するものを先に並べる
+//  ローカル変数は宣言と逆の順に破棄されるので、 defer! と同じ順になる
+use crate::allocator;
+use crate::debug::DebugNames;
+use crate::error;
+use ash::version::DeviceV1_0;
+
+//  vkDestroy* で破棄できるオブジェクト
+pub trait DeviceObject: Copy {
+    //  handle は device で作成したもので、実行中のコマンドから使われていない事
+    unsafe fn destroy(self, device: &ash::Device);
+}
+
+macro_rules! device_objects {
+    ($($ty:ident => $destroy:ident),* $(,)?) => {
+        $(
+            impl DeviceObject for ash::vk::$ty {
+                unsafe fn destroy(self, device: &ash::Device) {
+                    device.$destroy(self, None);
+                }
+            }
+        )*
+    };
+}
+
+device_objects! {
+    Buffer => destroy_buffer,
+    Image => destroy_image,
+    ImageView => destroy_image_view,
+    Sampler => destroy_sampler,
+    Framebuffer => destroy_framebuffer,
+    RenderPass => destroy_render_pass,
+    ShaderModule => destroy_shader_module,
+    PipelineLayout => destroy_pipeline_layout,
+    Pipeline => destroy_pipeline,
+    DescriptorSetLayout => destroy_descriptor_set_layout,
+    DescriptorPool => destroy_descriptor_pool,
+    CommandPool => destroy_command_pool,
+    Fence => destroy_fence,
+    Semaphore => destroy_semaphore,
+}
+
+//  device で作成したオブジェクトを 1 つ所有する
+//  Deref でハンドルをそのまま取り出せる
+pub struct Owned<'a, T: DeviceObject> {
+    device: &'a ash::Device,
+    handle: T,
+}
+
+impl<'a, T: DeviceObject> Owned<'a, T> {
+    pub fn new(device: &'a ash::Device, handle: T) -> Owned<'a, T> {
+        Owned {
+            device: device,
+            handle: handle,
+        }
+    }
+
+    pub fn handle(&self) -> T {
+        self.handle
+    }
+
+    //  破棄せずにハンドルを返し、所有をやめる
+    pub fn into_raw(self) -> T {
+        let handle = self.handle;
+        std::mem::forget(self);
+        handle
+    }
+}
+
+impl<T: DeviceObject> std::ops::Deref for Owned<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.handle
+    }
+}
+
+impl<T: DeviceObject> Drop for Owned<'_, T> {
+    fn drop(&mut self) {
+        unsafe { self.handle.destroy(self.device) }
+    }
+}
+
+pub type Buffer<'a> = Owned<'a, ash::vk::Buffer>;
+pub type Image<'a> = Owned<'a, ash::vk::Image>;
+pub type ImageView<'a> = Owned<'a, ash::vk::ImageView>;
+pub type Sampler<'a> = Owned<'a, ash::vk::Sampler>;
+pub type Framebuffer<'a> = Owned<'a, ash::vk::Framebuffer>;
+pub type RenderPass<'a> = Owned<'a, ash::vk::RenderPass>;
+pub type ShaderModule<'a> = Owned<'a, ash::vk::ShaderModule>;
+pub type PipelineLayout<'a> = Owned<'a, ash::vk::PipelineLayout>;
+pub type Pipeline<'a> = Owned<'a, ash::vk::Pipeline>;
+pub type DescriptorSetLayout<'a> = Owned<'a, ash::vk::DescriptorSetLayout>;
+pub type DescriptorPool<'a> = Owned<'a, ash::vk::DescriptorPool>;
+pub type CommandPool<'a> = Owned<'a, ash::vk::CommandPool>;
+pub type Fence<'a> = Owned<'a, ash::vk::Fence>;
+pub type Semaphore<'a> = Owned<'a, ash::vk::Semaphore>;
+
+pub fn create_fence<'a>(
+    device: &'a ash::Device,
+    flags: ash::vk::FenceCreateFlags,
+) -> Result<Fence<'a>, ash::vk::Result> {
+    let fence = unsafe {
+        device.create_fence(
+            &ash::vk::FenceCreateInfo::builder().flags(flags).build(),
+            None,
+        )?
+    };
+    Ok(Owned::new(device, fence))
+}
+
+pub fn create_semaphore<'a>(device: &'a ash::Device) -> Result<Semaphore<'a>, ash::vk::Result> {
+    let semaphore =
+        unsafe { device.create_semaphore(&ash::vk::SemaphoreCreateInfo::builder().build(), None)? };
+    Ok(Owned::new(device, semaphore))
+}
+
+//  スワップチェーンの拡張で作成したスワップチェーン
+//  作り直す場合は handle を新しいスワップチェーンの old_swapchain に渡してから、古い方を破棄する
+pub struct Swapchain<'a> {
+    loader: &'a ash::extensions::khr::Swapchain,
+    handle: ash::vk::SwapchainKHR,
+}
+
+impl<'a> Swapchain<'a> {
+    pub fn new(
+        loader: &'a ash::extensions::khr::Swapchain,
+        handle: ash::vk::SwapchainKHR,
+    ) -> Swapchain<'a> {
+        Swapchain {
+            loader: loader,
+            handle: handle,
+        }
+    }
+
+    pub fn handle(&self) -> ash::vk::SwapchainKHR {
+        self.handle
+    }
+}
+
+impl std::ops::Deref for Swapchain<'_> {
+    type Target = ash::vk::SwapchainKHR;
+
+    fn deref(&self) -> &ash::vk::SwapchainKHR {
+        &self.handle
+    }
+}
+
+impl Drop for Swapchain<'_> {
+    fn drop(&mut self) {
+        unsafe { self.loader.destroy_swapchain(self.handle, None) }
+    }
+}
+
+//  コマンドプールから確保したコマンドバッファ
+//  破棄するとコマンドプールに返す
+pub struct CommandBuffers<'a> {
+    device: &'a ash::Device,
+    command_pool: ash::vk::CommandPool,
+    buffers: Vec<ash::vk::CommandBuffer>,
+}
+
+impl<'a> CommandBuffers<'a> {
+    //  count が 0 の場合は何も確保しない
+    pub fn new(
+        device: &'a ash::Device,
+        command_pool: ash::vk::CommandPool,
+        count: u32,
+    ) -> Result<CommandBuffers<'a>, ash::vk::Result> {
+        let buffers = if count == 0 {
+            vec![]
+        } else {
+            unsafe {
+                device.allocate_command_buffers(
+                    &ash::vk::CommandBufferAllocateInfo::builder()
+                        .command_pool(command_pool)
+                        .level(ash::vk::CommandBufferLevel::PRIMARY)
+                        .command_buffer_count(count)
+                        .build(),
+                )?
+            }
+        };
+        Ok(CommandBuffers {
+            device: device,
+            command_pool: command_pool,
+            buffers: buffers,
+        })
+    }
+
+    //  コマンドバッファに「name 番号」の名前を付ける
+    pub fn name(&self, debug_names: &DebugNames, name: &str) {
+        for (i, buffer) in self.buffers.iter().enumerate() {
+            debug_names.name_object(*buffer, &format!("{} {}", name, i));
+        }
+    }
+}
+
+impl std::ops::Deref for CommandBuffers<'_> {
+    type Target = [ash::vk::CommandBuffer];
+
+    fn deref(&self) -> &[ash::vk::CommandBuffer] {
+        &self.buffers
+    }
+}
+
+impl Drop for CommandBuffers<'_> {
+    fn drop(&mut self) {
+        if !self.buffers.is_empty() {
+            unsafe {
+                self.device
+                    .free_command_buffers(self.command_pool, &self.buffers);
+            }
+        }
+    }
+}
+
+//  allocator::Allocator で確保したメモリ
+//  破棄すると Allocator に返す
+pub struct Allocation<'a> {
+    allocator: &'a allocator::Allocator,
+    allocation: allocator::Allocation,
+}
+
+impl<'a> Allocation<'a> {
+    pub fn new(
+        allocator: &'a allocator::Allocator,
+        allocation: allocator::Allocation,
+    ) -> Allocation<'a> {
+        Allocation {
+            allocator: allocator,
+            allocation: allocation,
+        }
+    }
+}
+
+impl std::ops::Deref for Allocation<'_> {
+    type Target = allocator::Allocation;
+
+    fn deref(&self) -> &allocator::Allocation {
+        &self.allocation
+    }
+}
+
+impl Drop for Allocation<'_> {
+    fn drop(&mut self) {
+        self.allocator.free(&self.allocation)
+    }
+}
+
+//  allocator::Allocator で確保したメモリを結び付けたバッファかイメージ
+//  フィールドは宣言した順に破棄されるので、バッファやイメージを破棄してからメモリを返す
+pub struct Bound<'a, T: DeviceObject> {
+    handle: Owned<'a, T>,
+    allocation: Allocation<'a>,
+}
+
+pub type BoundBuffer<'a> = Bound<'a, ash::vk::Buffer>;
+pub type BoundImage<'a> = Bound<'a, ash::vk::Image>;
+
+impl<'a, T: DeviceObject> Bound<'a, T> {
+    pub fn handle(&self) -> T {
+        self.handle.handle()
+    }
+
+    pub fn allocation(&self) -> &allocator::Allocation {
+        &self.allocation
+    }
+}
+
+impl<'a> Bound<'a, ash::vk::Buffer> {
+    pub fn new(
+        allocator: &'a allocator::Allocator,
+        create_info: &ash::vk::BufferCreateInfo,
+        allocation_create_info: &allocator::AllocationCreateInfo,
+    ) -> error::Result<BoundBuffer<'a>> {
+        let (buffer, allocation) = allocator.create_buffer(create_info, allocation_create_info)?;
+        Ok(Bound {
+            handle: Owned::new(allocator.device(), buffer),
+            allocation: Allocation::new(allocator, allocation),
+        })
+    }
+}
+
+impl<'a> Bound<'a, ash::vk::Image> {
+    pub fn new(
+        allocator: &'a allocator::Allocator,
+        create_info: &ash::vk::ImageCreateInfo,
+        allocation_create_info: &allocator::AllocationCreateInfo,
+    ) -> error::Result<BoundImage<'a>> {
+        let (image, allocation) = allocator.create_image(create_info, allocation_create_info)?;
+        Ok(Bound {
+            handle: Owned::new(allocator.device(), image),
+            allocation: Allocation::new(allocator, allocation),
+        })
+    }
+}
+
+impl<T: DeviceObject> std::ops::Deref for Bound<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.handle
+    }
+}
+
+//  vk-mem で確保したメモリを結び付けたバッファ
+//  破棄するとメモリも解放する
+#[cfg(feature = "vk-mem")]
+pub struct AllocatedBuffer<'a> {
+    allocator: &'a vk_mem::Allocator,
+    buffer: ash::vk::Buffer,
+    allocation: vk_mem::Allocation,
+    allocation_info: vk_mem::AllocationInfo,
+}
+
+#[cfg(feature = "vk-mem")]
+impl<'a> AllocatedBuffer<'a> {
+    pub fn new(
+        allocator: &'a vk_mem::Allocator,
+        create_info: &ash::vk::BufferCreateInfo,
+        allocation_create_info: &vk_mem::AllocationCreateInfo,
+    ) -> Result<AllocatedBuffer<'a>, vk_mem::Error> {
+        let (buffer, allocation, allocation_info) =
+            allocator.create_buffer(create_info, allocation_create_info)?;
+        Ok(AllocatedBuffer {
+            allocator: allocator,
+            buffer: buffer,
+            allocation: allocation,
+            allocation_info: allocation_info,
+        })
+    }
+
+    pub fn buffer(&self) -> ash::vk::Buffer {
+        self.buffer
+    }
+
+    pub fn allocation(&self) -> &vk_mem::Allocation {
+        &self.allocation
+    }
+
+    pub fn allocation_info(&self) -> &vk_mem::AllocationInfo {
+        &self.allocation_info
+    }
+}
+
+#[cfg(feature = "vk-mem")]
+impl std::ops::Deref for AllocatedBuffer<'_> {
+    type Target = ash::vk::Buffer;
+
+    fn deref(&self) -> &ash::vk::Buffer {
+        &self.buffer
+    }
+}
+
+#[cfg(feature = "vk-mem")]
+impl Drop for AllocatedBuffer<'_> {
+    fn drop(&mut self) {
+        //  破棄に失敗しても回復する方法が無いので無視する
+        let _ = self.allocator.destroy_buffer(self.buffer, &self.allocation);
+    }
+}
+
+//  vk-mem で確保したメモリを結び付けたイメージ
+//  破棄するとメモリも解放する
+#[cfg(feature = "vk-mem")]
+pub struct AllocatedImage<'a> {
+    allocator: &'a vk_mem::Allocator,
+    image: ash::vk::Image,
+    allocation: vk_mem::Allocation,
+    allocation_info: vk_mem::AllocationInfo,
+}
+
+#[cfg(feature = "vk-mem")]
+impl<'a> AllocatedImage<'a> {
+    pub fn new(
+        allocator: &'a vk_mem::Allocator,
+        create_info: &ash::vk::ImageCreateInfo,
+        allocation_create_info: &vk_mem::AllocationCreateInfo,
+    ) -> Result<AllocatedImage<'a>, vk_mem::Error> {
+        let (image, allocation, allocation_info) =
+            allocator.create_image(create_info, allocation_create_info)?;
+        Ok(AllocatedImage {
+            allocator: allocator,
+            image: image,
+            allocation: allocation,
+            allocation_info: allocation_info,
+        })
+    }
+
+    pub fn image(&self) -> ash::vk::Image {
+        self.image
+    }
+
+    pub fn allocation(&self) -> &vk_mem::Allocation {
+        &self.allocation
+    }
+
+    pub fn allocation_info(&self) -> &vk_mem::AllocationInfo {
+        &self.allocation_info
+    }
+}
+
+#[cfg(feature = "vk-mem")]
+impl std::ops::Deref for AllocatedImage<'_> {
+    type Target = ash::vk::Image;
+
+    fn deref(&self) -> &ash::vk::Image {
+        &self.image
+    }
+}
+
+#[cfg(feature = "vk-mem")]
+impl Drop for AllocatedImage<'_> {
+    fn drop(&mut self) {
+        //  破棄に失敗しても回復する方法が無いので無視する
+        let _ = self.allocator.destroy_image(self.image, &self.allocation);
+    }
+}
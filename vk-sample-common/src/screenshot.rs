@@ -0,0 +1,241 @@
+use crate::context::VulkanContext;
+use crate::error::{self, Error, ErrorContext};
+use crate::memory_type;
+use ash::version::{DeviceV1_0, InstanceV1_0};
+
+//  読み出したピクセルを PNG の並びである RGBA に揃える
+//  スワップチェーンのフォーマットは B8G8R8A8 が優先して選ばれるので R と B を入れ替える必要がある
+pub fn to_rgba(format: ash::vk::Format, pixels: &mut [u8]) -> error::Result<()> {
+    match format {
+        ash::vk::Format::R8G8B8A8_UNORM | ash::vk::Format::R8G8B8A8_SRGB => Ok(()),
+        ash::vk::Format::B8G8R8A8_UNORM | ash::vk::Format::B8G8R8A8_SRGB => {
+            pixels
+                .chunks_exact_mut(4)
+                .for_each(|pixel| pixel.swap(0, 2));
+            Ok(())
+        }
+        _ => Err(Error::Message(format!(
+            "{:?} のイメージは保存できない",
+            format
+        ))),
+    }
+}
+
+pub fn write_png(path: &str, width: u32, height: u32, rgba: &[u8]) -> error::Result<()> {
+    let file = std::fs::File::create(path).context(format!("{} を作成できない", path))?;
+    let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::RGBA);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(rgba))
+        .map_err(|e| Error::Message(format!("{} に書き込めない: {}", path, e)))
+}
+
+//  描画済みのカラーイメージをホストから見えるバッファにコピーして RGBA の並びで返す
+//  layout はイメージの現在のレイアウトで、コピーが終わると元のレイアウトに戻す
+pub fn read_image(
+    context: &VulkanContext,
+    command_pool: ash::vk::CommandPool,
+    image: ash::vk::Image,
+    layout: ash::vk::ImageLayout,
+    format: ash::vk::Format,
+    extent: ash::vk::Extent2D,
+) -> error::Result<Vec<u8>> {
+    let device = &context.device;
+    let size = extent.width as u64 * extent.height as u64 * 4;
+
+    let buffer = unsafe {
+        device.create_buffer(
+            &ash::vk::BufferCreateInfo::builder()
+                .size(size)
+                .usage(ash::vk::BufferUsageFlags::TRANSFER_DST)
+                .sharing_mode(ash::vk::SharingMode::EXCLUSIVE)
+                .build(),
+            None,
+        )
+    }
+    .context("読み出し用のバッファを作成できない")?;
+    defer! { unsafe { device.destroy_buffer(buffer, None); } }
+
+    let requirements = unsafe { device.get_buffer_memory_requirements(buffer) };
+    let memory_properties = unsafe {
+        context
+            .instance
+            .get_physical_device_memory_properties(context.physical_device)
+    };
+    //  読み出すだけなので HOST_CACHED を優先する
+    let memory_type = memory_type::select_memory_type(
+        &memory_properties,
+        &memory_type::MemoryTypeRequest {
+            type_bits: requirements.memory_type_bits,
+            required_flags: ash::vk::MemoryPropertyFlags::HOST_VISIBLE
+                | ash::vk::MemoryPropertyFlags::HOST_COHERENT,
+            preferred_flags: ash::vk::MemoryPropertyFlags::HOST_CACHED,
+            size: requirements.size,
+        },
+        None,
+    )
+    .context("読み出し用のメモリタイプを選べない")?;
+    let memory = unsafe {
+        device.allocate_memory(
+            &ash::vk::MemoryAllocateInfo::builder()
+                .allocation_size(requirements.size)
+                .memory_type_index(memory_type)
+                .build(),
+            None,
+        )
+    }
+    .context("読み出し用のメモリを確保できない")?;
+    defer! { unsafe { device.free_memory(memory, None); } }
+    unsafe { device.bind_buffer_memory(buffer, memory, 0) }
+        .context("バッファにメモリを結び付けられない")?;
+
+    let command_buffers = unsafe {
+        device.allocate_command_buffers(
+            &ash::vk::CommandBufferAllocateInfo::builder()
+                .command_pool(command_pool)
+                .level(ash::vk::CommandBufferLevel::PRIMARY)
+                .command_buffer_count(1)
+                .build(),
+        )
+    }
+    .context("コマンドバッファを作成できない")?;
+    defer! { unsafe { device.free_command_buffers(command_pool, command_buffers.as_slice()); } }
+    let command_buffer = command_buffers[0];
+
+    let subresource_range = ash::vk::ImageSubresourceRange::builder()
+        .aspect_mask(ash::vk::ImageAspectFlags::COLOR)
+        .level_count(1)
+        .layer_count(1)
+        .build();
+    let barrier = |old_layout, new_layout, src_access_mask, dst_access_mask| {
+        [ash::vk::ImageMemoryBarrier::builder()
+            .src_access_mask(src_access_mask)
+            .dst_access_mask(dst_access_mask)
+            .old_layout(old_layout)
+            .new_layout(new_layout)
+            .src_queue_family_index(ash::vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(ash::vk::QUEUE_FAMILY_IGNORED)
+            .image(image)
+            .subresource_range(subresource_range)
+            .build()]
+    };
+    let region = [ash::vk::BufferImageCopy::builder()
+        .image_subresource(
+            ash::vk::ImageSubresourceLayers::builder()
+                .aspect_mask(ash::vk::ImageAspectFlags::COLOR)
+                .layer_count(1)
+                .build(),
+        )
+        .image_extent(ash::vk::Extent3D {
+            width: extent.width,
+            height: extent.height,
+            depth: 1,
+        })
+        .build()];
+
+    unsafe {
+        device
+            .begin_command_buffer(
+                command_buffer,
+                &ash::vk::CommandBufferBeginInfo::builder()
+                    .flags(ash::vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
+                    .build(),
+            )
+            .context("コマンドの記録を開始できない")?;
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            ash::vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            ash::vk::PipelineStageFlags::TRANSFER,
+            ash::vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &barrier(
+                layout,
+                ash::vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                ash::vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                ash::vk::AccessFlags::TRANSFER_READ,
+            ),
+        );
+        device.cmd_copy_image_to_buffer(
+            command_buffer,
+            image,
+            ash::vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            buffer,
+            &region,
+        );
+        //  queue_wait_idle だけではコピーで書き込んだ内容がホストから見えないので、 HOST_READ に対するバリアを置く
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            ash::vk::PipelineStageFlags::TRANSFER,
+            ash::vk::PipelineStageFlags::HOST,
+            ash::vk::DependencyFlags::empty(),
+            &[],
+            &[ash::vk::BufferMemoryBarrier::builder()
+                .src_access_mask(ash::vk::AccessFlags::TRANSFER_WRITE)
+                .dst_access_mask(ash::vk::AccessFlags::HOST_READ)
+                .src_queue_family_index(ash::vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(ash::vk::QUEUE_FAMILY_IGNORED)
+                .buffer(buffer)
+                .offset(0)
+                .size(ash::vk::WHOLE_SIZE)
+                .build()],
+            &[],
+        );
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            ash::vk::PipelineStageFlags::TRANSFER,
+            ash::vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+            ash::vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &barrier(
+                ash::vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                layout,
+                ash::vk::AccessFlags::TRANSFER_READ,
+                ash::vk::AccessFlags::empty(),
+            ),
+        );
+        device
+            .end_command_buffer(command_buffer)
+            .context("コマンドの記録を終了できない")?;
+        device
+            .queue_submit(
+                context.graphics_queue,
+                &[ash::vk::SubmitInfo::builder()
+                    .command_buffers(&command_buffers)
+                    .build()],
+                ash::vk::Fence::null(),
+            )
+            .context("コマンドを送信できない")?;
+        device
+            .queue_wait_idle(context.graphics_queue)
+            .context("コピーの完了を待てない")?;
+    }
+
+    let mut pixels = vec![0u8; size as usize];
+    unsafe {
+        let mapped = device
+            .map_memory(memory, 0, size, ash::vk::MemoryMapFlags::empty())
+            .context("メモリをマップできない")?;
+        std::ptr::copy_nonoverlapping(mapped as *const u8, pixels.as_mut_ptr(), pixels.len());
+        device.unmap_memory(memory);
+    }
+    to_rgba(format, &mut pixels)?;
+    Ok(pixels)
+}
+
+//  read_image で読み出したイメージを PNG として保存する
+pub fn save_image(
+    context: &VulkanContext,
+    command_pool: ash::vk::CommandPool,
+    image: ash::vk::Image,
+    layout: ash::vk::ImageLayout,
+    format: ash::vk::Format,
+    extent: ash::vk::Extent2D,
+    path: &str,
+) -> error::Result<()> {
+    let pixels = read_image(context, command_pool, image, layout, format, extent)?;
+    write_png(path, extent.width, extent.height, &pixels)
+}
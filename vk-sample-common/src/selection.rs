@@ -0,0 +1,280 @@
+//  条件に合う物理デバイスを選ぶ
+//  条件を満たすデバイスにはスコアを付けて最も高いものを使い、満たさないデバイスには除外した理由を残す
+use crate::error::{self, Error, ErrorContext};
+use crate::report;
+use ash::version::InstanceV1_0;
+
+#[derive(Clone, Copy)]
+pub struct FormatRequirement {
+    pub format: ash::vk::Format,
+    pub tiling: ash::vk::ImageTiling,
+    pub features: ash::vk::FormatFeatureFlags,
+}
+
+//  デバイスに求める条件
+//  features と min_limits のキーは device_report の features と limits のキーと同じ
+#[derive(Clone, Default)]
+pub struct Requirements {
+    pub extensions: Vec<std::ffi::CString>,
+    pub features: Vec<String>,
+    //  全てのフラグを備えたキューファミリーが 1 つ以上必要
+    pub queue_flags: ash::vk::QueueFlags,
+    pub formats: Vec<FormatRequirement>,
+    pub min_limits: Vec<(String, f64)>,
+}
+
+impl Requirements {
+    //  グラフィクスキューと dext の拡張だけを求める
+    pub fn new(dext: &[&std::ffi::CStr]) -> Requirements {
+        Requirements {
+            extensions: dext.iter().map(|name| (*name).to_owned()).collect(),
+            queue_flags: ash::vk::QueueFlags::GRAPHICS,
+            ..Requirements::default()
+        }
+    }
+}
+
+pub struct Candidate {
+    pub device: ash::vk::PhysicalDevice,
+    pub name: String,
+    pub device_type: ash::vk::PhysicalDeviceType,
+    //  条件を満たす場合はスコア、満たさない場合は除外した理由
+    pub result: Result<u64, Vec<String>>,
+}
+
+//  条件と比べるデバイスの性質
+//  formats は Requirements の formats に挙げたフォーマットだけを持つ
+#[derive(Clone, Default)]
+pub struct DeviceInfo {
+    pub properties: ash::vk::PhysicalDeviceProperties,
+    pub memory: ash::vk::PhysicalDeviceMemoryProperties,
+    pub features: ash::vk::PhysicalDeviceFeatures,
+    pub extensions: Vec<std::ffi::CString>,
+    pub queue_families: Vec<ash::vk::QueueFamilyProperties>,
+    //  キューファミリーごとに表示できるかどうか、表示を求めない場合は None
+    pub presentable: Option<Vec<bool>>,
+    pub formats: Vec<(ash::vk::Format, ash::vk::FormatProperties)>,
+}
+
+impl DeviceInfo {
+    pub fn query(
+        instance: &ash::Instance,
+        device: ash::vk::PhysicalDevice,
+        requirements: &Requirements,
+        presentable: Option<&dyn Fn(ash::vk::PhysicalDevice, u32) -> bool>,
+    ) -> DeviceInfo {
+        let queue_families =
+            unsafe { instance.get_physical_device_queue_family_properties(device) };
+        DeviceInfo {
+            properties: unsafe { instance.get_physical_device_properties(device) },
+            memory: unsafe { instance.get_physical_device_memory_properties(device) },
+            features: unsafe { instance.get_physical_device_features(device) },
+            extensions: unsafe { instance.enumerate_device_extension_properties(device) }
+                .unwrap_or_default()
+                .iter()
+                .map(|v| unsafe { std::ffi::CStr::from_ptr(v.extension_name.as_ptr()) }.to_owned())
+                .collect(),
+            presentable: presentable.map(|presentable| {
+                (0..queue_families.len())
+                    .map(|i| presentable(device, i as u32))
+                    .collect()
+            }),
+            queue_families: queue_families,
+            formats: requirements
+                .formats
+                .iter()
+                .map(|requirement| {
+                    (requirement.format, unsafe {
+                        instance.get_physical_device_format_properties(device, requirement.format)
+                    })
+                })
+                .collect(),
+        }
+    }
+}
+
+//  ディスクリート GPU を優先し、同じ種類ならデバイスローカルなメモリが多い方を優先する
+pub fn score(info: &DeviceInfo) -> u64 {
+    let type_score = match info.properties.device_type {
+        ash::vk::PhysicalDeviceType::DISCRETE_GPU => 1000,
+        ash::vk::PhysicalDeviceType::INTEGRATED_GPU => 100,
+        ash::vk::PhysicalDeviceType::VIRTUAL_GPU => 50,
+        ash::vk::PhysicalDeviceType::CPU => 10,
+        _ => 0,
+    };
+    let local_memory = info.memory.memory_heaps[..info.memory.memory_heap_count as usize]
+        .iter()
+        .filter(|heap| heap.flags.contains(ash::vk::MemoryHeapFlags::DEVICE_LOCAL))
+        .map(|heap| heap.size)
+        .sum::<u64>();
+    //  メモリは GiB 単位で足すので、種類の違いを覆す事は無い
+    type_score + local_memory / (1024 * 1024 * 1024)
+}
+
+//  デバイスの作成時に有効にする機能
+//  デバイスが備えていても requirements で求めていない機能は有効にしない
+pub fn enabled_features(
+    requirements: &Requirements,
+) -> error::Result<ash::vk::PhysicalDeviceFeatures> {
+    let mut features = ash::vk::PhysicalDeviceFeatures::default();
+    for feature in requirements.features.iter() {
+        if !report::enable_feature(&mut features, feature) {
+            return Err(Error::Message(format!("{} という機能は無い", feature)));
+        }
+    }
+    Ok(features)
+}
+
+//  満たしていない条件ごとに理由を返す
+pub fn unmet_requirements(info: &DeviceInfo, requirements: &Requirements) -> Vec<String> {
+    let mut reasons = Vec::new();
+
+    for extension in requirements.extensions.iter() {
+        if !info.extensions.contains(extension) {
+            reasons.push(format!("拡張 {} が無い", extension.to_string_lossy()));
+        }
+    }
+
+    let features = report::features(&info.features);
+    for feature in requirements.features.iter() {
+        match features[feature.as_str()].as_bool() {
+            Some(true) => {}
+            Some(false) => reasons.push(format!("機能 {} が無い", feature)),
+            None => reasons.push(format!("{} という機能は無い", feature)),
+        }
+    }
+
+    if !info
+        .queue_families
+        .iter()
+        .any(|family| family.queue_flags.contains(requirements.queue_flags))
+    {
+        reasons.push(format!(
+            "{:?} を備えたキューが無い",
+            requirements.queue_flags
+        ));
+    }
+    if let Some(presentable) = info.presentable.as_ref() {
+        if !presentable.iter().any(|p| *p) {
+            reasons.push(String::from("表示できるキューが無い"));
+        }
+    }
+
+    for requirement in requirements.formats.iter() {
+        let props = info
+            .formats
+            .iter()
+            .find(|(format, _)| *format == requirement.format)
+            .map(|(_, props)| *props)
+            .unwrap_or_default();
+        let supported = match requirement.tiling {
+            ash::vk::ImageTiling::LINEAR => props.linear_tiling_features,
+            _ => props.optimal_tiling_features,
+        };
+        if !supported.contains(requirement.features) {
+            reasons.push(format!(
+                "{:?} の {:?} タイリングで {} が使えない",
+                requirement.format,
+                requirement.tiling,
+                report::format_feature_flag_names(requirement.features & !supported).join(" | ")
+            ));
+        }
+    }
+
+    let limits = report::limits(&info.properties.limits);
+    for (name, min) in requirements.min_limits.iter() {
+        match limits[name.as_str()].as_f64() {
+            Some(value) if value >= *min => {}
+            Some(value) => reasons.push(format!("制限 {} が {} で {} 未満", name, value, min)),
+            None => reasons.push(format!("{} という数値の制限は無い", name)),
+        }
+    }
+
+    reasons
+}
+
+//  列挙した順に全てのデバイスを調べる
+//  presentable を渡した場合はそれで表示できるキューファミリーがある事も求める
+pub fn evaluate_devices(
+    instance: &ash::Instance,
+    requirements: &Requirements,
+    presentable: Option<&dyn Fn(ash::vk::PhysicalDevice, u32) -> bool>,
+) -> error::Result<Vec<Candidate>> {
+    let devices =
+        unsafe { instance.enumerate_physical_devices() }.context("デバイスを列挙できない")?;
+    if devices.len() == 0 {
+        return Err(Error::from("利用可能なデバイスがない"));
+    }
+    Ok(devices
+        .into_iter()
+        .map(|device| {
+            let info = DeviceInfo::query(instance, device, requirements, presentable);
+            let reasons = unmet_requirements(&info, requirements);
+            Candidate {
+                device: device,
+                name: report::c_str(&info.properties.device_name),
+                device_type: info.properties.device_type,
+                result: if reasons.is_empty() {
+                    Ok(score(&info))
+                } else {
+                    Err(reasons)
+                },
+            }
+        })
+        .collect())
+}
+
+//  device_index を指定した場合は列挙した順でその番号のデバイスを、
+//  指定しない場合は条件を満たすうち最もスコアが高いデバイスを選ぶ
+pub fn select_device(candidates: &[Candidate], device_index: Option<u32>) -> error::Result<usize> {
+    match device_index {
+        Some(index) => match candidates.get(index as usize) {
+            Some(Candidate { result: Ok(_), .. }) => Ok(index as usize),
+            Some(Candidate {
+                name,
+                result: Err(reasons),
+                ..
+            }) => Err(Error::Message(format!(
+                "{}番目のデバイス {} は条件を満たさない: {}",
+                index,
+                name,
+                reasons.join(", ")
+            ))),
+            None => Err(Error::Message(format!(
+                "{}番目のデバイスは存在しない",
+                index
+            ))),
+        },
+        None => {
+            let mut best: Option<(usize, u64)> = None;
+            for (i, candidate) in candidates.iter().enumerate() {
+                if let Ok(score) = candidate.result {
+                    //  同じスコアなら先に列挙されたものを使う
+                    if best.map_or(true, |(_, best_score)| score > best_score) {
+                        best = Some((i, score));
+                    }
+                }
+            }
+            best.map(|(i, _)| i).context("条件を満たすデバイスがない")
+        }
+    }
+}
+
+pub fn print_candidates(candidates: &[Candidate]) {
+    println!("利用可能なデバイス");
+    for (i, candidate) in candidates.iter().enumerate() {
+        let device_type = report::device_type_name(&format!("{:?}", candidate.device_type));
+        match candidate.result.as_ref() {
+            Ok(score) => println!(
+                "{}: {}({}) スコア {}",
+                i, candidate.name, device_type, score
+            ),
+            Err(reasons) => {
+                println!("{}: {}({}) 除外", i, candidate.name, device_type);
+                for reason in reasons.iter() {
+                    println!("    {}", reason);
+                }
+            }
+        }
+    }
+}
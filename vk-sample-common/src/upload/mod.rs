@@ -0,0 +1,680 @@
+//  ステージングバッファを介してデバイスローカルなバッファやイメージにデータを転送する
+//  ステージングバッファは作成時に 1 度だけ確保して永続的にマップしておき、リングバッファとして使い回す
+//  コピーは 1 つのコマンドバッファにまとめて記録し、送信したバッチごとにフェンスで完了を待つ
+//  HOST_COHERENT でないメモリタイプしか無い場合は、書き込んだ範囲のフラッシュと読み出す範囲の無効化も行う
+use crate::align::{align_down, align_up, lcm};
+use crate::context::VulkanContext;
+use crate::error::{self, Error, ErrorContext};
+use crate::memory_type;
+use ash::version::{DeviceV1_0, InstanceV1_0};
+use std::collections::VecDeque;
+
+mod ring;
+
+pub use ring::StagingRing;
+
+//  ステージングバッファの大きさを指定しない場合の大きさ
+pub const DEFAULT_STAGING_SIZE: u64 = 16 * 1024 * 1024;
+
+//  vkCmdCopyBufferToImage のバッファ側のオフセットは 4 の倍数でなければならない
+//  vkCmdCopyBuffer のオフセットにはこの制約が無いので、バッファのコピーは揃えずに詰めて置く
+const IMAGE_COPY_BUFFER_ALIGNMENT: u64 = 4;
+
+//  圧縮していないフォーマットの 1 テクセルのバイト数
+//  深度とステンシルを両方持つフォーマット、圧縮したフォーマット、マルチプレーンのフォーマットは None
+//  コアのフォーマットは番号の順に同じ大きさのものが並んでいるので、番号の範囲で判断する
+pub fn texel_size(format: ash::vk::Format) -> Option<u64> {
+    match format.as_raw() {
+        //  R4G4_UNORM_PACK8
+        1 => Some(1),
+        //  R4G4B4A4_UNORM_PACK16 から A1R5G5B5_UNORM_PACK16
+        2..=8 => Some(2),
+        //  R8_UNORM から R8_SRGB
+        9..=15 => Some(1),
+        //  R8G8_UNORM から R8G8_SRGB
+        16..=22 => Some(2),
+        //  R8G8B8_UNORM から B8G8R8_SRGB
+        23..=36 => Some(3),
+        //  R8G8B8A8_UNORM から A2B10G10R10_SINT_PACK32
+        37..=69 => Some(4),
+        //  R16_UNORM から R16_SFLOAT
+        70..=76 => Some(2),
+        //  R16G16_UNORM から R16G16_SFLOAT
+        77..=83 => Some(4),
+        //  R16G16B16_UNORM から R16G16B16_SFLOAT
+        84..=90 => Some(6),
+        //  R16G16B16A16_UNORM から R16G16B16A16_SFLOAT
+        91..=97 => Some(8),
+        //  R32_UINT から R32_SFLOAT
+        98..=100 => Some(4),
+        //  R32G32_UINT から R32G32_SFLOAT
+        101..=103 => Some(8),
+        //  R32G32B32_UINT から R32G32B32_SFLOAT
+        104..=106 => Some(12),
+        //  R32G32B32A32_UINT から R32G32B32A32_SFLOAT
+        107..=109 => Some(16),
+        //  R64_UINT から R64_SFLOAT
+        110..=112 => Some(8),
+        //  R64G64_UINT から R64G64_SFLOAT
+        113..=115 => Some(16),
+        //  R64G64B64_UINT から R64G64B64_SFLOAT
+        116..=118 => Some(24),
+        //  R64G64B64A64_UINT から R64G64B64A64_SFLOAT
+        119..=121 => Some(32),
+        //  B10G11R11_UFLOAT_PACK32 と E5B9G9R9_UFLOAT_PACK32
+        122..=123 => Some(4),
+        //  D16_UNORM
+        124 => Some(2),
+        //  X8_D24_UNORM_PACK32 と D32_SFLOAT
+        125..=126 => Some(4),
+        //  S8_UINT
+        127 => Some(1),
+        _ => None,
+    }
+}
+
+//  vkCmdCopyBufferToImage のバッファ側のオフセットは 4 とテクセルの大きさの両方の倍数で、
+//  optimalBufferCopyOffsetAlignment の倍数にするとより速く転送できる
+pub fn image_copy_alignment(texel_size: u64, optimal_buffer_copy_offset_alignment: u64) -> u64 {
+    lcm(
+        lcm(IMAGE_COPY_BUFFER_ALIGNMENT, texel_size),
+        optimal_buffer_copy_offset_alignment,
+    )
+}
+
+fn as_bytes<T: Copy>(data: &[T]) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(data.as_ptr() as *const u8, std::mem::size_of_val(data)) }
+}
+
+struct Batch {
+    command_buffer: ash::vk::CommandBuffer,
+    fence: ash::vk::Fence,
+    //  このバッチでホストから書き込んだステージングバッファの範囲
+    written: Vec<(u64, u64)>,
+}
+
+//  記録したコピーは wait で送信して完了を待つ
+//  wait を呼ばずに破棄した場合も Drop で送信して完了を待つが、失敗しても表示する事しかできない
+pub struct Uploader<'a> {
+    context: &'a VulkanContext,
+    command_pool: ash::vk::CommandPool,
+    buffer: ash::vk::Buffer,
+    memory: ash::vk::DeviceMemory,
+    mapped: *mut u8,
+    coherent: bool,
+    non_coherent_atom_size: u64,
+    optimal_buffer_copy_offset_alignment: u64,
+    ring: StagingRing,
+    //  記録中のバッチ
+    recording: Option<Batch>,
+    //  送信済みで完了を待っているバッチ
+    //  ring が持つ閉じたバッチと同じ順に並ぶ
+    in_flight: VecDeque<Batch>,
+    //  完了してコマンドバッファとフェンスを再利用できるバッチ
+    free_batches: Vec<Batch>,
+    batch_count: usize,
+}
+
+impl<'a> Uploader<'a> {
+    //  グラフィクスキューで転送する
+    //  staging_size が 0 の場合は DEFAULT_STAGING_SIZE
+    pub fn new(context: &'a VulkanContext, staging_size: u64) -> error::Result<Uploader<'a>> {
+        let device = &context.device;
+        let props = unsafe {
+            context
+                .instance
+                .get_physical_device_properties(context.physical_device)
+        };
+        let memory_properties = unsafe {
+            context
+                .instance
+                .get_physical_device_memory_properties(context.physical_device)
+        };
+        let non_coherent_atom_size = props.limits.non_coherent_atom_size.max(1);
+        //  フラッシュする範囲の終わりが atom の倍数になるよう、大きさも atom に揃える
+        let size = align_up(
+            if staging_size == 0 {
+                DEFAULT_STAGING_SIZE
+            } else {
+                staging_size
+            },
+            non_coherent_atom_size,
+        );
+
+        let command_pool = unsafe {
+            device.create_command_pool(
+                &ash::vk::CommandPoolCreateInfo::builder()
+                    .flags(
+                        ash::vk::CommandPoolCreateFlags::TRANSIENT
+                            | ash::vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER,
+                    )
+                    .queue_family_index(context.graphics_queue_index)
+                    .build(),
+                None,
+            )
+        }
+        .context("転送用のコマンドプールを作成できない")?;
+        let command_pool = scopeguard::guard(command_pool, |command_pool| unsafe {
+            device.destroy_command_pool(command_pool, None);
+        });
+
+        let buffer = unsafe {
+            device.create_buffer(
+                &ash::vk::BufferCreateInfo::builder()
+                    .size(size)
+                    .usage(
+                        ash::vk::BufferUsageFlags::TRANSFER_SRC
+                            | ash::vk::BufferUsageFlags::TRANSFER_DST,
+                    )
+                    .sharing_mode(ash::vk::SharingMode::EXCLUSIVE)
+                    .build(),
+                None,
+            )
+        }
+        .context("ステージングバッファを作成できない")?;
+        let buffer = scopeguard::guard(buffer, |buffer| unsafe {
+            device.destroy_buffer(buffer, None);
+        });
+
+        let requirements = unsafe { device.get_buffer_memory_requirements(*buffer) };
+        //  HOST_COHERENT であればフラッシュが要らないので優先する
+        let memory_type_index = memory_type::select_memory_type(
+            &memory_properties,
+            &memory_type::MemoryTypeRequest {
+                type_bits: requirements.memory_type_bits,
+                required_flags: ash::vk::MemoryPropertyFlags::HOST_VISIBLE,
+                preferred_flags: ash::vk::MemoryPropertyFlags::HOST_COHERENT,
+                size: requirements.size,
+            },
+            None,
+        )
+        .context("ステージングバッファのメモリタイプを選べない")?;
+        let coherent = memory_properties.memory_types[memory_type_index as usize]
+            .property_flags
+            .contains(ash::vk::MemoryPropertyFlags::HOST_COHERENT);
+        let memory = unsafe {
+            device.allocate_memory(
+                &ash::vk::MemoryAllocateInfo::builder()
+                    .allocation_size(requirements.size)
+                    .memory_type_index(memory_type_index)
+                    .build(),
+                None,
+            )
+        }
+        .context("ステージングバッファのメモリを確保できない")?;
+        let memory = scopeguard::guard(memory, |memory| unsafe {
+            device.free_memory(memory, None);
+        });
+        unsafe { device.bind_buffer_memory(*buffer, *memory, 0) }
+            .context("ステージングバッファにメモリを結び付けられない")?;
+        let mapped = unsafe {
+            device.map_memory(
+                *memory,
+                0,
+                ash::vk::WHOLE_SIZE,
+                ash::vk::MemoryMapFlags::empty(),
+            )
+        }
+        .context("ステージングバッファをマップできない")? as *mut u8;
+        context
+            .debug_names
+            .name_object(*buffer, "staging ring buffer");
+
+        Ok(Uploader {
+            context: context,
+            command_pool: scopeguard::ScopeGuard::into_inner(command_pool),
+            buffer: scopeguard::ScopeGuard::into_inner(buffer),
+            memory: scopeguard::ScopeGuard::into_inner(memory),
+            mapped: mapped,
+            coherent: coherent,
+            non_coherent_atom_size: non_coherent_atom_size,
+            optimal_buffer_copy_offset_alignment: props.limits.optimal_buffer_copy_offset_alignment,
+            ring: StagingRing::new(size),
+            recording: None,
+            in_flight: VecDeque::new(),
+            free_batches: vec![],
+            batch_count: 0,
+        })
+    }
+
+    pub fn staging_size(&self) -> u64 {
+        self.ring.size()
+    }
+
+    //  data を dst の dst_offset の位置にコピーするコマンドを記録する
+    //  ステージングバッファより大きいデータは分割してコピーする
+    //  コピーが終わるのは submit か wait で送信した後
+    pub fn upload_buffer<T: Copy>(
+        &mut self,
+        dst: ash::vk::Buffer,
+        dst_offset: u64,
+        data: &[T],
+    ) -> error::Result<()> {
+        let bytes = as_bytes(data);
+        let chunk_size = self.ring.size() as usize;
+        for (i, chunk) in bytes.chunks(chunk_size).enumerate() {
+            let offset = self.write(chunk, 1)?;
+            let command_buffer = self.command_buffer()?;
+            unsafe {
+                self.context.device.cmd_copy_buffer(
+                    command_buffer,
+                    self.buffer,
+                    dst,
+                    &[ash::vk::BufferCopy::builder()
+                        .src_offset(offset)
+                        .dst_offset(dst_offset + (i * chunk_size) as u64)
+                        .size(chunk.len() as u64)
+                        .build()],
+                );
+            }
+        }
+        Ok(())
+    }
+
+    //  format のテクセルを隙間なく並べた data を image の subresource にコピーするコマンドを記録する
+    //  data の大きさは extent と subresource のレイヤー数の分のテクセルと同じでなければならない
+    //  イメージの元の内容は捨て、コピーが終わると final_layout に移す
+    //  イメージは分割しないので、 data はステージングバッファに収まらなければならない
+    pub fn upload_image<T: Copy>(
+        &mut self,
+        image: ash::vk::Image,
+        format: ash::vk::Format,
+        subresource: ash::vk::ImageSubresourceLayers,
+        extent: ash::vk::Extent3D,
+        data: &[T],
+        final_layout: ash::vk::ImageLayout,
+    ) -> error::Result<()> {
+        let texel_size = texel_size(format).ok_or_else(|| {
+            Error::Message(format!(
+                "{:?} のイメージにはテクセルをコピーできない",
+                format
+            ))
+        })?;
+        let bytes = as_bytes(data);
+        let expected = extent.width as u64
+            * extent.height as u64
+            * extent.depth as u64
+            * subresource.layer_count as u64
+            * texel_size;
+        if bytes.len() as u64 != expected {
+            return Err(Error::Message(format!(
+                "{:?} の {}x{}x{} のイメージ {} レイヤーには {} バイトが必要だが、データは {} バイト",
+                format,
+                extent.width,
+                extent.height,
+                extent.depth,
+                subresource.layer_count,
+                expected,
+                bytes.len()
+            )));
+        }
+        let alignment = image_copy_alignment(texel_size, self.optimal_buffer_copy_offset_alignment);
+        let offset = self.write(bytes, alignment)?;
+        let command_buffer = self.command_buffer()?;
+        let subresource_range = ash::vk::ImageSubresourceRange::builder()
+            .aspect_mask(subresource.aspect_mask)
+            .base_mip_level(subresource.mip_level)
+            .level_count(1)
+            .base_array_layer(subresource.base_array_layer)
+            .layer_count(subresource.layer_count)
+            .build();
+        let barrier = |old_layout, new_layout, src_access_mask, dst_access_mask| {
+            [ash::vk::ImageMemoryBarrier::builder()
+                .src_access_mask(src_access_mask)
+                .dst_access_mask(dst_access_mask)
+                .old_layout(old_layout)
+                .new_layout(new_layout)
+                .src_queue_family_index(ash::vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(ash::vk::QUEUE_FAMILY_IGNORED)
+                .image(image)
+                .subresource_range(subresource_range)
+                .build()]
+        };
+        let device = &self.context.device;
+        unsafe {
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                ash::vk::PipelineStageFlags::TOP_OF_PIPE,
+                ash::vk::PipelineStageFlags::TRANSFER,
+                ash::vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &barrier(
+                    ash::vk::ImageLayout::UNDEFINED,
+                    ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    ash::vk::AccessFlags::empty(),
+                    ash::vk::AccessFlags::TRANSFER_WRITE,
+                ),
+            );
+            device.cmd_copy_buffer_to_image(
+                command_buffer,
+                self.buffer,
+                image,
+                ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &[ash::vk::BufferImageCopy::builder()
+                    .buffer_offset(offset)
+                    .image_subresource(subresource)
+                    .image_extent(extent)
+                    .build()],
+            );
+            //  どの段階で使われるか分からないので、以降の全てのコマンドに対して可視にする
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                ash::vk::PipelineStageFlags::TRANSFER,
+                ash::vk::PipelineStageFlags::ALL_COMMANDS,
+                ash::vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &barrier(
+                    ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    final_layout,
+                    ash::vk::AccessFlags::TRANSFER_WRITE,
+                    ash::vk::AccessFlags::MEMORY_READ,
+                ),
+            );
+        }
+        Ok(())
+    }
+
+    //  src の offset から size バイトを読み出す
+    //  記録済みのコピーも含めて全て送信し、完了を待ってから返す
+    pub fn read_buffer(
+        &mut self,
+        src: ash::vk::Buffer,
+        offset: u64,
+        size: u64,
+    ) -> error::Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(size as usize);
+        //  無効化する範囲が他の範囲の書き込みを捨てないよう atom に揃えて確保する
+        let alignment = self.non_coherent_atom_size;
+        let chunk_size = align_down(self.ring.size(), alignment).max(alignment);
+        let mut read = 0;
+        while read < size {
+            let chunk = std::cmp::min(chunk_size, size - read);
+            let staging_offset = self.reserve(chunk, alignment)?;
+            let command_buffer = self.command_buffer()?;
+            unsafe {
+                self.context.device.cmd_copy_buffer(
+                    command_buffer,
+                    src,
+                    self.buffer,
+                    &[ash::vk::BufferCopy::builder()
+                        .src_offset(offset + read)
+                        .dst_offset(staging_offset)
+                        .size(chunk)
+                        .build()],
+                );
+                self.context.device.cmd_pipeline_barrier(
+                    command_buffer,
+                    ash::vk::PipelineStageFlags::TRANSFER,
+                    ash::vk::PipelineStageFlags::HOST,
+                    ash::vk::DependencyFlags::empty(),
+                    &[ash::vk::MemoryBarrier::builder()
+                        .src_access_mask(ash::vk::AccessFlags::TRANSFER_WRITE)
+                        .dst_access_mask(ash::vk::AccessFlags::HOST_READ)
+                        .build()],
+                    &[],
+                    &[],
+                );
+            }
+            self.wait()?;
+            if !self.coherent {
+                unsafe {
+                    self.context.device.invalidate_mapped_memory_ranges(&[
+                        self.mapped_range(staging_offset, staging_offset + chunk)
+                    ])
+                }
+                .context("ステージングバッファを無効化できない")?;
+            }
+            data.extend_from_slice(unsafe {
+                std::slice::from_raw_parts(self.mapped.add(staging_offset as usize), chunk as usize)
+            });
+            read += chunk;
+        }
+        Ok(data)
+    }
+
+    //  記録中のコピーを送信する
+    //  完了は待たないので、続けて別のコピーを記録できる
+    pub fn submit(&mut self) -> error::Result<()> {
+        let batch = match self.recording.take() {
+            Some(batch) => batch,
+            None => return Ok(()),
+        };
+        match self.end_and_submit(&batch) {
+            Ok(()) => {
+                self.ring.close_batch();
+                self.in_flight.push_back(batch);
+                Ok(())
+            }
+            Err(e) => {
+                self.free_batches.push(batch);
+                Err(e)
+            }
+        }
+    }
+
+    //  記録中のコピーを送信し、送信済みの全てのコピーの完了を待つ
+    pub fn wait(&mut self) -> error::Result<()> {
+        self.submit()?;
+        while !self.in_flight.is_empty() {
+            self.wait_oldest()?;
+        }
+        Ok(())
+    }
+
+    fn end_and_submit(&self, batch: &Batch) -> error::Result<()> {
+        let device = &self.context.device;
+        if !self.coherent && !batch.written.is_empty() {
+            let ranges = batch
+                .written
+                .iter()
+                .map(|(start, end)| self.mapped_range(*start, *end))
+                .collect::<Vec<_>>();
+            unsafe { device.flush_mapped_memory_ranges(&ranges) }
+                .context("ステージングバッファをフラッシュできない")?;
+        }
+        unsafe {
+            //  コピー先のバッファを後から送信するコマンドで読めるようにする
+            device.cmd_pipeline_barrier(
+                batch.command_buffer,
+                ash::vk::PipelineStageFlags::TRANSFER,
+                ash::vk::PipelineStageFlags::ALL_COMMANDS,
+                ash::vk::DependencyFlags::empty(),
+                &[ash::vk::MemoryBarrier::builder()
+                    .src_access_mask(ash::vk::AccessFlags::TRANSFER_WRITE)
+                    .dst_access_mask(ash::vk::AccessFlags::MEMORY_READ)
+                    .build()],
+                &[],
+                &[],
+            );
+            self.context.debug_names.end_label(batch.command_buffer);
+            device
+                .end_command_buffer(batch.command_buffer)
+                .context("転送コマンドの記録を終了できない")?;
+            device
+                .queue_submit(
+                    self.context.graphics_queue,
+                    &[ash::vk::SubmitInfo::builder()
+                        .command_buffers(&[batch.command_buffer])
+                        .build()],
+                    batch.fence,
+                )
+                .context("転送コマンドを送信できない")?;
+        }
+        Ok(())
+    }
+
+    fn wait_oldest(&mut self) -> error::Result<()> {
+        let mut batch = match self.in_flight.pop_front() {
+            Some(batch) => batch,
+            None => return Ok(()),
+        };
+        let device = &self.context.device;
+        let result = unsafe {
+            device
+                .wait_for_fences(&[batch.fence], true, std::u64::MAX)
+                .and_then(|_| device.reset_fences(&[batch.fence]))
+        };
+        if let Err(e) = result {
+            //  完了していないかもしれないので再利用しない
+            self.in_flight.push_front(batch);
+            return Err(e).context("転送の完了を待てない");
+        }
+        self.ring.release_batch();
+        batch.written.clear();
+        self.free_batches.push(batch);
+        Ok(())
+    }
+
+    //  data をステージングバッファに書き込み、そのオフセットを返す
+    fn write(&mut self, data: &[u8], alignment: u64) -> error::Result<u64> {
+        let offset = self.reserve(data.len() as u64, alignment)?;
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                data.as_ptr(),
+                self.mapped.add(offset as usize),
+                data.len(),
+            );
+        }
+        let end = offset + data.len() as u64;
+        self.command_buffer()?;
+        if let Some(batch) = self.recording.as_mut() {
+            batch.written.push((offset, end));
+        }
+        Ok(offset)
+    }
+
+    //  ステージングバッファの範囲を確保する
+    //  空きが無い場合は記録中のバッチを送信し、古いバッチから完了を待って空ける
+    fn reserve(&mut self, size: u64, alignment: u64) -> error::Result<u64> {
+        loop {
+            if let Some(offset) = self.ring.allocate(size, alignment) {
+                return Ok(offset);
+            }
+            if self.in_flight.is_empty() {
+                if self.recording.is_none() {
+                    return Err(Error::Message(format!(
+                        "{} バイトはステージングバッファ ({} バイト) に収まらない",
+                        size,
+                        self.ring.size()
+                    )));
+                }
+                self.submit()?;
+            }
+            self.wait_oldest()?;
+        }
+    }
+
+    //  記録中のコマンドバッファを返す
+    //  記録中のものが無ければ新しいバッチの記録を始める
+    fn command_buffer(&mut self) -> error::Result<ash::vk::CommandBuffer> {
+        if let Some(batch) = self.recording.as_ref() {
+            return Ok(batch.command_buffer);
+        }
+        let batch = match self.free_batches.pop() {
+            Some(batch) => batch,
+            None => self.create_batch()?,
+        };
+        let result = unsafe {
+            self.context.device.begin_command_buffer(
+                batch.command_buffer,
+                &ash::vk::CommandBufferBeginInfo::builder()
+                    .flags(ash::vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
+                    .build(),
+            )
+        };
+        if let Err(e) = result {
+            self.free_batches.push(batch);
+            return Err(e).context("転送コマンドの記録を開始できない");
+        }
+        self.context
+            .debug_names
+            .begin_label(batch.command_buffer, "upload");
+        let command_buffer = batch.command_buffer;
+        self.recording = Some(batch);
+        Ok(command_buffer)
+    }
+
+    fn create_batch(&mut self) -> error::Result<Batch> {
+        let device = &self.context.device;
+        let command_buffer = unsafe {
+            device.allocate_command_buffers(
+                &ash::vk::CommandBufferAllocateInfo::builder()
+                    .command_pool(self.command_pool)
+                    .level(ash::vk::CommandBufferLevel::PRIMARY)
+                    .command_buffer_count(1)
+                    .build(),
+            )
+        }
+        .context("転送用のコマンドバッファを確保できない")?[0];
+        let fence = match unsafe {
+            device.create_fence(&ash::vk::FenceCreateInfo::builder().build(), None)
+        } {
+            Ok(fence) => fence,
+            Err(e) => {
+                unsafe { device.free_command_buffers(self.command_pool, &[command_buffer]) };
+                return Err(e).context("転送用のフェンスを作成できない");
+            }
+        };
+        let debug_names = &self.context.debug_names;
+        debug_names.name_object(
+            command_buffer,
+            &format!("upload command buffer {}", self.batch_count),
+        );
+        debug_names.name_object(fence, &format!("upload fence {}", self.batch_count));
+        self.batch_count += 1;
+        Ok(Batch {
+            command_buffer: command_buffer,
+            fence: fence,
+            written: vec![],
+        })
+    }
+
+    //  start..end を含み、端が nonCoherentAtomSize の倍数になる範囲
+    fn mapped_range(&self, start: u64, end: u64) -> ash::vk::MappedMemoryRange {
+        let start = align_down(start, self.non_coherent_atom_size);
+        let end = align_up(end, self.non_coherent_atom_size);
+        ash::vk::MappedMemoryRange::builder()
+            .memory(self.memory)
+            .offset(start)
+            .size(end - start)
+            .build()
+    }
+}
+
+impl<'a> Drop for Uploader<'a> {
+    fn drop(&mut self) {
+        //  記録したまま送信していないコピーを黙って捨てないよう、ここで送信する
+        if self.recording.is_some() {
+            if let Err(e) = self.submit() {
+                eprintln!("記録したコピーを送信できない: {}", e);
+            }
+        }
+        let device = &self.context.device;
+        unsafe {
+            //  送信済みのコピーがステージングバッファを読み終わるまで待つ
+            let fences = self
+                .in_flight
+                .iter()
+                .map(|batch| batch.fence)
+                .collect::<Vec<_>>();
+            if !fences.is_empty() {
+                let _ = device.wait_for_fences(&fences, true, std::u64::MAX);
+            }
+            for batch in self
+                .recording
+                .iter()
+                .chain(self.in_flight.iter())
+                .chain(self.free_batches.iter())
+            {
+                device.destroy_fence(batch.fence, None);
+            }
+            //  コマンドバッファはコマンドプールと一緒に解放される
+            device.destroy_command_pool(self.command_pool, None);
+            device.unmap_memory(self.memory);
+            device.destroy_buffer(self.buffer, None);
+            device.free_memory(self.memory, None);
+        }
+    }
+}
@@ -0,0 +1,112 @@
+//  ステージングバッファの中を先頭から順に使い、末尾まで来たら先頭に戻るリングバッファ
+//  範囲は個別には解放せず、送信したバッチの単位で古い順に解放する
+//  デバイスには触れないので、オフセットと大きさだけでテストできる
+use crate::align::align_up;
+use std::collections::VecDeque;
+
+#[derive(Clone, Copy)]
+struct Batch {
+    //  バッチの最後の範囲の終わり
+    end: u64,
+    //  アラインメントや末尾の隙間も含めてバッチが使った大きさ
+    used: u64,
+}
+
+pub struct StagingRing {
+    size: u64,
+    //  次に確保する位置
+    head: u64,
+    //  まだ解放していない最も古い範囲の始まり
+    tail: u64,
+    //  head と tail が等しい時に空か満杯かを区別するため、使用中の大きさを数える
+    used: u64,
+    //  まだ閉じていないバッチが使った大きさ
+    open: u64,
+    //  閉じたバッチを古い順に
+    batches: VecDeque<Batch>,
+}
+
+impl StagingRing {
+    pub fn new(size: u64) -> StagingRing {
+        StagingRing {
+            size: size,
+            head: 0,
+            tail: 0,
+            used: 0,
+            open: 0,
+            batches: VecDeque::new(),
+        }
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    //  使用中の大きさ (アラインメントや末尾の隙間も含む)
+    pub fn used(&self) -> u64 {
+        self.used
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.used == 0
+    }
+
+    //  閉じたがまだ解放していないバッチの数
+    pub fn pending_batches(&self) -> usize {
+        self.batches.len()
+    }
+
+    //  size バイトを alignment に揃えて確保し、オフセットを返す
+    //  連続した空きが無い場合は None で、古いバッチを解放してから確保し直す
+    pub fn allocate(&mut self, size: u64, alignment: u64) -> Option<u64> {
+        if size == 0 || size > self.size {
+            return None;
+        }
+        //  空になったら先頭から使い直して、大きな範囲を取れるようにする
+        if self.used == 0 {
+            self.head = 0;
+            self.tail = 0;
+        }
+        let start = align_up(self.head, alignment);
+        let (start, end) = if self.used > 0 && self.head <= self.tail {
+            //  折り返した後なので空きは head..tail だけ
+            if start + size > self.tail {
+                return None;
+            }
+            (start, start + size)
+        } else if start + size <= self.size {
+            (start, start + size)
+        } else if size <= self.tail {
+            //  末尾に収まらないので先頭に戻る
+            (0, size)
+        } else {
+            return None;
+        };
+        let consumed = if start >= self.head {
+            end - self.head
+        } else {
+            self.size - self.head + end
+        };
+        self.head = end;
+        self.used += consumed;
+        self.open += consumed;
+        Some(start)
+    }
+
+    //  ここまでに確保した範囲を 1 つのバッチにまとめる
+    //  何も確保していなくても空のバッチを作るので、送信したコマンドバッファと 1 対 1 に対応する
+    pub fn close_batch(&mut self) {
+        self.batches.push_back(Batch {
+            end: self.head,
+            used: self.open,
+        });
+        self.open = 0;
+    }
+
+    //  最も古いバッチの範囲を解放する
+    pub fn release_batch(&mut self) {
+        let batch = self.batches.pop_front().expect("解放するバッチが無い");
+        self.tail = batch.end;
+        self.used -= batch.used;
+    }
+}
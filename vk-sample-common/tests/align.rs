@@ -0,0 +1,26 @@
+//  align のオフセットの切り上げと切り捨て、アラインメントの最小公倍数を確かめる
+use vk_sample_common::align::{align_down, align_up, lcm};
+
+#[test]
+fn rounds_to_multiples() {
+    assert_eq!(align_up(0, 16), 0);
+    assert_eq!(align_up(1, 16), 16);
+    assert_eq!(align_up(16, 16), 16);
+    assert_eq!(align_up(17, 12), 24);
+    assert_eq!(align_down(17, 16), 16);
+    assert_eq!(align_down(23, 12), 12);
+    //  0 と 1 は揃えない
+    assert_eq!(align_up(7, 0), 7);
+    assert_eq!(align_up(7, 1), 7);
+    assert_eq!(align_down(7, 0), 7);
+}
+
+#[test]
+fn least_common_multiple() {
+    assert_eq!(lcm(4, 3), 12);
+    assert_eq!(lcm(4, 6), 12);
+    assert_eq!(lcm(16, 4), 16);
+    assert_eq!(lcm(12, 16), 48);
+    assert_eq!(lcm(0, 8), 8);
+    assert_eq!(lcm(1, 1), 1);
+}
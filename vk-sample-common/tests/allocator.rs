@@ -0,0 +1,173 @@
+//  allocator::Tlsf が返すオフセットと、解放した範囲の結合を確かめる
+mod common;
+
+use common::Lcg;
+use vk_sample_common::allocator::{ResourceKind, Tlsf};
+
+const KIB: u64 = 1024;
+
+#[test]
+fn allocations_do_not_overlap_and_respect_alignment() {
+    let mut tlsf = Tlsf::new(64 * KIB, 1);
+    let mut ranges = Vec::new();
+    for (i, alignment) in [1, 4, 16, 256, 64, 512, 8].iter().enumerate() {
+        let size = 100 + i as u64 * 37;
+        let offset = tlsf
+            .allocate(size, *alignment, ResourceKind::Linear)
+            .unwrap();
+        assert_eq!(offset % alignment, 0);
+        ranges.push((offset, offset + size));
+    }
+    ranges.sort();
+    for pair in ranges.windows(2) {
+        assert!(pair[0].1 <= pair[1].0, "{:?} が重なっている", pair);
+    }
+    assert_eq!(tlsf.allocation_count(), 7);
+}
+
+#[test]
+fn free_merges_neighbours() {
+    let mut tlsf = Tlsf::new(4 * KIB, 1);
+    let a = tlsf.allocate(KIB, 1, ResourceKind::Linear).unwrap();
+    let b = tlsf.allocate(KIB, 1, ResourceKind::Linear).unwrap();
+    let c = tlsf.allocate(KIB, 1, ResourceKind::Linear).unwrap();
+    let d = tlsf.allocate(KIB, 1, ResourceKind::Linear).unwrap();
+    assert_eq!(tlsf.largest_free_block(), 0);
+    assert_eq!(tlsf.allocate(1, 1, ResourceKind::Linear), None);
+
+    //  間を空けて解放しても結合されるまでは大きな範囲は取れない
+    tlsf.free(a);
+    tlsf.free(c);
+    assert_eq!(tlsf.largest_free_block(), KIB);
+    assert_eq!(tlsf.allocate(2 * KIB, 1, ResourceKind::Linear), None);
+
+    tlsf.free(b);
+    assert_eq!(tlsf.largest_free_block(), 3 * KIB);
+    tlsf.free(d);
+    assert!(tlsf.is_empty());
+    assert_eq!(tlsf.allocated_size(), 0);
+    assert_eq!(tlsf.largest_free_block(), 4 * KIB);
+    assert_eq!(tlsf.allocate(4 * KIB, 1, ResourceKind::Linear), Some(0));
+}
+
+#[test]
+fn alignment_padding_is_reusable() {
+    let mut tlsf = Tlsf::new(4 * KIB, 1);
+    tlsf.allocate(16, 1, ResourceKind::Linear).unwrap();
+    //  16..1024 は空きのまま残る
+    let aligned = tlsf.allocate(KIB, KIB, ResourceKind::Linear).unwrap();
+    assert_eq!(aligned, KIB);
+    let small = tlsf.allocate(512, 16, ResourceKind::Linear).unwrap();
+    assert!(small + 512 <= KIB);
+}
+
+#[test]
+fn exhausted_block_reports_none() {
+    let mut tlsf = Tlsf::new(KIB, 1);
+    assert_eq!(tlsf.allocate(KIB + 1, 1, ResourceKind::Linear), None);
+    assert_eq!(tlsf.allocate(0, 1, ResourceKind::Linear), None);
+    assert_eq!(tlsf.allocate(KIB, 1, ResourceKind::Linear), Some(0));
+    assert_eq!(tlsf.allocate(1, 1, ResourceKind::Linear), None);
+}
+
+#[test]
+fn different_kinds_do_not_share_a_granularity_page() {
+    let granularity = KIB;
+    let mut tlsf = Tlsf::new(16 * KIB, granularity);
+    let buffer = tlsf.allocate(100, 4, ResourceKind::Linear).unwrap();
+    let image = tlsf.allocate(100, 4, ResourceKind::Optimal).unwrap();
+    assert_eq!(buffer, 0);
+    assert_eq!(image / granularity, 1);
+
+    //  同じ種類は同じページに詰める
+    let buffer2 = tlsf.allocate(100, 4, ResourceKind::Linear).unwrap();
+    assert_eq!(buffer2, 100);
+
+    //  Optimal の後ろに置く Linear は次のページから始まる
+    assert_eq!(
+        tlsf.allocate(900, 1, ResourceKind::Linear),
+        Some(2 * granularity)
+    );
+}
+
+#[test]
+fn next_allocation_of_different_kind_blocks_the_page() {
+    let granularity = KIB;
+    let mut tlsf = Tlsf::new(4 * KIB, granularity);
+    let first = tlsf.allocate(100, 1, ResourceKind::Optimal).unwrap();
+    let second = tlsf.allocate(100, 1, ResourceKind::Optimal).unwrap();
+    assert_eq!((first, second), (0, 100));
+    tlsf.free(first);
+
+    //  0..100 は空いているが、後ろの Optimal と同じページなので Linear は置けない
+    assert_eq!(
+        tlsf.allocate(50, 1, ResourceKind::Linear),
+        Some(granularity)
+    );
+    assert_eq!(tlsf.allocate(50, 1, ResourceKind::Optimal), Some(0));
+}
+
+#[test]
+fn granularity_is_ignored_for_the_same_kind() {
+    let mut tlsf = Tlsf::new(4 * KIB, KIB);
+    let a = tlsf.allocate(10, 1, ResourceKind::Optimal).unwrap();
+    let b = tlsf.allocate(10, 1, ResourceKind::Optimal).unwrap();
+    assert_eq!((a, b), (0, 10));
+}
+
+#[test]
+fn many_allocations_return_to_a_single_free_block() {
+    let granularity = 256;
+    let mut tlsf = Tlsf::new(1024 * KIB, granularity);
+    let mut allocations = Vec::new();
+    let mut rng = Lcg::new(12345);
+    for i in 0..500 {
+        let size = 1 + rng.below(1500);
+        let kind = if i % 3 == 0 {
+            ResourceKind::Optimal
+        } else {
+            ResourceKind::Linear
+        };
+        if let Some(offset) = tlsf.allocate(size, 1 << rng.below(8), kind) {
+            allocations.push((offset, size, kind));
+        }
+        //  時々解放して断片化させる
+        if i % 4 == 0 && !allocations.is_empty() {
+            let index = rng.below(allocations.len() as u64) as usize;
+            tlsf.free(allocations.swap_remove(index).0);
+        }
+    }
+
+    //  残っている範囲は重ならず、種類が異なる隣どうしは同じページに入らない
+    allocations.sort_by_key(|(offset, _, _)| *offset);
+    for pair in allocations.windows(2) {
+        let (a_offset, a_size, a_kind) = pair[0];
+        let (b_offset, _, b_kind) = pair[1];
+        assert!(a_offset + a_size <= b_offset);
+        if a_kind != b_kind {
+            assert_ne!(
+                (a_offset + a_size - 1) / granularity,
+                b_offset / granularity
+            );
+        }
+    }
+    assert_eq!(
+        tlsf.allocated_size(),
+        allocations.iter().map(|(_, size, _)| size).sum::<u64>()
+    );
+
+    for (offset, _, _) in allocations {
+        tlsf.free(offset);
+    }
+    assert!(tlsf.is_empty());
+    assert_eq!(tlsf.largest_free_block(), tlsf.size());
+}
+
+#[test]
+#[should_panic]
+fn double_free_panics() {
+    let mut tlsf = Tlsf::new(KIB, 1);
+    let offset = tlsf.allocate(16, 1, ResourceKind::Linear).unwrap();
+    tlsf.free(offset);
+    tlsf.free(offset);
+}
@@ -0,0 +1,87 @@
+//  Camera の射影が Vulkan のクリップ空間に合い、 framing がメッシュ全体を画面に収めることを確かめる
+use vk_sample_common::camera::Camera;
+use vk_sample_common::mesh::Aabb;
+
+fn aabb(min: [f32; 3], max: [f32; 3]) -> Aabb {
+    Aabb {
+        min: nalgebra_glm::vec3(min[0], min[1], min[2]),
+        max: nalgebra_glm::vec3(max[0], max[1], max[2]),
+    }
+}
+
+fn clip(camera: &Camera, aspect: f32, p: nalgebra_glm::Vec3) -> nalgebra_glm::Vec4 {
+    camera.projection_matrix(aspect) * camera.view_matrix() * nalgebra_glm::vec4(p.x, p.y, p.z, 1.0)
+}
+
+fn corners(bounds: &Aabb) -> Vec<nalgebra_glm::Vec3> {
+    (0..8)
+        .map(|i| {
+            nalgebra_glm::vec3(
+                if i & 1 == 0 {
+                    bounds.min.x
+                } else {
+                    bounds.max.x
+                },
+                if i & 2 == 0 {
+                    bounds.min.y
+                } else {
+                    bounds.max.y
+                },
+                if i & 4 == 0 {
+                    bounds.min.z
+                } else {
+                    bounds.max.z
+                },
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn framing_keeps_every_corner_in_clip_space() {
+    let boxes = [
+        aabb([-1.0, -1.0, -1.0], [1.0, 1.0, 1.0]),
+        aabb([10.0, 20.0, -30.0], [14.0, 21.0, -29.5]),
+        //  平たい箱と細長い箱
+        aabb([-5.0, 0.0, -5.0], [5.0, 0.0, 5.0]),
+        aabb([0.0, -100.0, 0.0], [0.1, 100.0, 0.1]),
+        //  大きさの無い箱は半径 1 の球として扱う
+        aabb([3.0, 3.0, 3.0], [3.0, 3.0, 3.0]),
+    ];
+    for bounds in boxes.iter() {
+        for aspect in [0.25, 0.75, 1.0, 16.0 / 9.0, 4.0].iter() {
+            let camera = Camera::framing(bounds, *aspect);
+            for corner in corners(bounds) {
+                let c = clip(&camera, *aspect, corner);
+                let message = format!("aspect {} corner {:?} clip {:?}", aspect, corner, c);
+                assert!(c.w > 0.0, "{}", message);
+                assert!(c.x.abs() <= c.w, "{}", message);
+                assert!(c.y.abs() <= c.w, "{}", message);
+                assert!(c.z >= 0.0 && c.z <= c.w, "{}", message);
+            }
+        }
+    }
+}
+
+#[test]
+fn projection_points_y_down_and_maps_depth_to_zero_one() {
+    let camera = Camera {
+        eye: nalgebra_glm::vec3(0.0, 0.0, 0.0),
+        target: nalgebra_glm::vec3(0.0, 0.0, -1.0),
+        up: nalgebra_glm::vec3(0.0, 1.0, 0.0),
+        fov_y: std::f32::consts::FRAC_PI_2,
+        near: 1.0,
+        far: 10.0,
+    };
+    let near = clip(&camera, 2.0, nalgebra_glm::vec3(0.0, 0.0, -1.0));
+    let far = clip(&camera, 2.0, nalgebra_glm::vec3(0.0, 0.0, -10.0));
+    assert!((near.z / near.w).abs() < 1e-6);
+    assert!((far.z / far.w - 1.0).abs() < 1e-6);
+
+    //  ワールドの上は画面の上、つまり Vulkan の y では負の側
+    let top = clip(&camera, 2.0, nalgebra_glm::vec3(0.0, 1.0, -1.0));
+    assert!((top.y / top.w + 1.0).abs() < 1e-6);
+    //  横は aspect の分だけ広く見える
+    let right = clip(&camera, 2.0, nalgebra_glm::vec3(2.0, 0.0, -1.0));
+    assert!((right.x / right.w - 1.0).abs() < 1e-6);
+}
@@ -0,0 +1,243 @@
+//  mesh::collada の読み込みを XML の文字列で確かめる
+use vk_sample_common::mesh::collada::parse;
+use vk_sample_common::mesh::Mesh;
+
+//  0..4 は XY 平面の四角形、 4 は (2, 0, 0)
+const POSITIONS: &str = "0 0 0  1 0 0  1 1 0  0 1 0  2 0 0";
+
+fn document(up_axis: &str, primitive: &str, nodes: &str) -> String {
+    format!(
+        r##"<?xml version="1.0" encoding="utf-8"?>
+<COLLADA xmlns="http://www.collada.org/2005/11/COLLADASchema" version="1.4.1">
+  <asset><up_axis>{}</up_axis></asset>
+  <library_geometries>
+    <geometry id="shape">
+      <mesh>
+        <source id="pos">
+          <float_array id="pos-array" count="15">{}</float_array>
+          <technique_common><accessor source="#pos-array" count="5" stride="3"/></technique_common>
+        </source>
+        <source id="nrm">
+          <float_array id="nrm-array" count="6">0 0 1  0 0 -1</float_array>
+          <technique_common><accessor source="#nrm-array" count="2" stride="3"/></technique_common>
+        </source>
+        <vertices id="vtx"><input semantic="POSITION" source="#pos"/></vertices>
+        {}
+      </mesh>
+    </geometry>
+  </library_geometries>
+  <library_visual_scenes>
+    <visual_scene id="Scene">{}</visual_scene>
+  </library_visual_scenes>
+  <scene><instance_visual_scene url="#Scene"/></scene>
+</COLLADA>"##,
+        up_axis, POSITIONS, primitive, nodes
+    )
+}
+
+const NODE: &str = r##"<node><instance_geometry url="#shape"/></node>"##;
+
+fn parse_primitive(primitive: &str) -> Mesh {
+    parse(&document("Y_UP", primitive, NODE)).unwrap()
+}
+
+fn positions(mesh: &Mesh) -> Vec<[f32; 3]> {
+    mesh.indices
+        .iter()
+        .map(|i| {
+            let p = mesh.vertices[*i as usize].position;
+            [p.x, p.y, p.z]
+        })
+        .collect()
+}
+
+fn assert_close(actual: nalgebra_glm::Vec3, expected: [f32; 3]) {
+    let expected = nalgebra_glm::vec3(expected[0], expected[1], expected[2]);
+    assert!(
+        nalgebra_glm::distance(&actual, &expected) < 1e-5,
+        "{:?} != {:?}",
+        actual,
+        expected
+    );
+}
+
+#[test]
+fn triangles() {
+    let mesh = parse_primitive(
+        r##"<triangles count="1"><input semantic="VERTEX" source="#vtx" offset="0"/><p>0 1 2</p></triangles>"##,
+    );
+    assert_eq!(mesh.vertices.len(), 3);
+    assert_eq!(
+        positions(&mesh),
+        vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0]]
+    );
+    //  法線が無いので面から作る
+    assert_close(mesh.vertices[0].normal, [0.0, 0.0, 1.0]);
+}
+
+#[test]
+fn polylist_is_split_by_vcount_into_fans() {
+    let mesh = parse_primitive(
+        r##"<polylist count="2"><input semantic="VERTEX" source="#vtx" offset="0"/>
+        <vcount>4 3</vcount><p>0 1 2 3  1 4 2</p></polylist>"##,
+    );
+    assert_eq!(mesh.indices, vec![0, 1, 2, 0, 2, 3, 1, 4, 2]);
+    assert_eq!(mesh.vertices.len(), 5);
+}
+
+#[test]
+fn polylist_with_too_few_indices_is_an_error() {
+    let result = parse(&document(
+        "Y_UP",
+        r##"<polylist count="1"><input semantic="VERTEX" source="#vtx" offset="0"/>
+        <vcount>4</vcount><p>0 1 2</p></polylist>"##,
+        NODE,
+    ));
+    assert!(result.is_err());
+}
+
+#[test]
+fn polygons_have_one_p_per_polygon() {
+    let mesh = parse_primitive(
+        r##"<polygons count="2"><input semantic="VERTEX" source="#vtx" offset="0"/>
+        <p>0 1 2 3</p><p>1 4 2</p></polygons>"##,
+    );
+    assert_eq!(
+        positions(&mesh),
+        vec![
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [2.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+        ]
+    );
+}
+
+#[test]
+fn trifans_share_the_first_vertex() {
+    let mesh = parse_primitive(
+        r##"<trifans count="1"><input semantic="VERTEX" source="#vtx" offset="0"/><p>0 1 2 3</p></trifans>"##,
+    );
+    assert_eq!(mesh.indices, vec![0, 1, 2, 0, 2, 3]);
+}
+
+#[test]
+fn tristrips_alternate_winding() {
+    let mesh = parse_primitive(
+        r##"<tristrips count="1"><input semantic="VERTEX" source="#vtx" offset="0"/><p>0 1 3 2</p></tristrips>"##,
+    );
+    assert_eq!(
+        positions(&mesh),
+        vec![
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+        ]
+    );
+    //  どちらの三角形も表が +Z を向く
+    for v in mesh.vertices.iter() {
+        assert_close(v.normal, [0.0, 0.0, 1.0]);
+    }
+}
+
+#[test]
+fn identical_index_tuples_are_merged() {
+    //  位置 0 は 2 回とも同じ法線なので 1 つに、位置 2 は法線が違うので 2 つになる
+    let mesh = parse_primitive(
+        r##"<triangles count="2">
+        <input semantic="VERTEX" source="#vtx" offset="0"/>
+        <input semantic="NORMAL" source="#nrm" offset="1"/>
+        <p>0 0 1 0 2 0  0 0 2 1 3 0</p></triangles>"##,
+    );
+    assert_eq!(mesh.vertices.len(), 5);
+    assert_eq!(mesh.indices, vec![0, 1, 2, 0, 3, 4]);
+    assert_close(mesh.vertices[2].normal, [0.0, 0.0, 1.0]);
+    assert_close(mesh.vertices[3].normal, [0.0, 0.0, -1.0]);
+}
+
+#[test]
+fn z_up_is_rotated_to_y_up() {
+    let mesh = parse(&document(
+        "Z_UP",
+        r##"<triangles count="1">
+        <input semantic="VERTEX" source="#vtx" offset="0"/>
+        <input semantic="NORMAL" source="#nrm" offset="1"/>
+        <p>0 0 1 0 2 0</p></triangles>"##,
+        NODE,
+    ))
+    .unwrap();
+    //  +Z が +Y に、 +Y が -Z になる
+    assert_close(mesh.vertices[2].position, [1.0, 0.0, -1.0]);
+    assert_close(mesh.vertices[0].normal, [0.0, 1.0, 0.0]);
+}
+
+#[test]
+fn node_transforms_are_nested() {
+    let mesh = parse(&document(
+        "Y_UP",
+        r##"<triangles count="1"><input semantic="VERTEX" source="#vtx" offset="0"/><p>0 1 2</p></triangles>"##,
+        r##"<node><translate>10 0 0</translate>
+          <node><scale>2 2 2</scale><instance_geometry url="#shape"/></node>
+        </node>
+        <node><matrix>1 0 0 0  0 1 0 5  0 0 1 0  0 0 0 1</matrix><instance_geometry url="#shape"/></node>"##,
+    ))
+    .unwrap();
+    assert_eq!(mesh.indices.len(), 6);
+    assert_eq!(
+        positions(&mesh),
+        vec![
+            [10.0, 0.0, 0.0],
+            [12.0, 0.0, 0.0],
+            [12.0, 2.0, 0.0],
+            //  matrix は行優先なので 4 列目が平行移動
+            [0.0, 5.0, 0.0],
+            [1.0, 5.0, 0.0],
+            [1.0, 6.0, 0.0],
+        ]
+    );
+}
+
+#[test]
+fn missing_geometry_is_an_error() {
+    let result = parse(&document(
+        "Y_UP",
+        r##"<triangles count="1"><input semantic="VERTEX" source="#vtx" offset="0"/><p>0 1 2</p></triangles>"##,
+        r##"<node><instance_geometry url="#nothing"/></node>"##,
+    ));
+    assert!(result.is_err());
+}
+
+#[test]
+fn binormal_sets_the_tangent_handedness() {
+    //  従法線が cross(normal, tangent) と逆向きなので w は -1
+    let primitive = r##"<triangles count="1">
+        <input semantic="VERTEX" source="#vtx" offset="0"/>
+        <input semantic="NORMAL" source="#nrm" offset="1"/>
+        <input semantic="TEXTANGENT" source="#tan" offset="1"/>
+        <input semantic="TEXBINORMAL" source="#bin" offset="1"/>
+        <p>0 0 1 0 2 0</p></triangles>
+        "##;
+    let sources = r##"<source id="tan">
+          <float_array id="tan-array" count="3">1 0 0</float_array>
+          <technique_common><accessor source="#tan-array" count="1" stride="3"/></technique_common>
+        </source>
+        <source id="bin">
+          <float_array id="bin-array" count="3">0 -1 0</float_array>
+          <technique_common><accessor source="#bin-array" count="1" stride="3"/></technique_common>
+        </source>"##;
+    let text = document("Y_UP", &format!("{}{}", sources, primitive), NODE);
+    let mesh = parse(&text).unwrap();
+    for v in mesh.vertices.iter() {
+        let tangent = v.tangent;
+        assert_eq!(tangent.w, -1.0);
+        assert_close(tangent.xyz(), [1.0, 0.0, 0.0]);
+    }
+}
@@ -0,0 +1,20 @@
+//  複数のテストで使う補助
+
+//  失敗を再現できるよう、乱数は種から決まる線形合同法で作る
+pub struct Lcg(u64);
+
+impl Lcg {
+    pub fn new(seed: u64) -> Lcg {
+        Lcg(seed)
+    }
+
+    //  0 以上 n 未満の値
+    //  下位のビットは周期が短いので上位のビットを使う
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (self.0 >> 33) % n
+    }
+}
@@ -0,0 +1,169 @@
+//  Configs::from の設定の重ね方と値の検査を、コマンドラインと環境変数を渡して確かめる
+use std::collections::HashMap;
+use vk_sample_common::config::{create_app, Configs, OutputFormat};
+
+fn configs(args: &[&str], env: &[(&str, &str)]) -> Result<Configs, String> {
+    let matches = create_app("test")
+        .get_matches_from_safe(std::iter::once("test").chain(args.iter().copied()))
+        .unwrap();
+    let env = env
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect::<HashMap<_, _>>();
+    Configs::from("test", &matches, &env)
+}
+
+//  テストごとに別の設定ファイルを書く
+fn config_file(name: &str, text: &str) -> String {
+    let dir: std::path::PathBuf = [env!("CARGO_TARGET_TMPDIR"), "config"].iter().collect();
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    std::fs::write(&path, text).unwrap();
+    path.display().to_string()
+}
+
+#[test]
+fn defaults() {
+    let config = configs(&[], &[]).unwrap();
+    assert_eq!(config.prog_name, "test");
+    assert!(!config.list);
+    assert_eq!(config.device_index, None);
+    assert_eq!((config.width, config.height), (640, 480));
+    assert!(!config.fullscreen && !config.validation && !config.validation_fatal);
+    assert!(!config.debug_mode && !config.headless);
+    assert_eq!(config.shader_dir, "./shaders/");
+    assert_eq!(config.mesh_file, "");
+    assert_eq!(config.screenshot, None);
+    assert_eq!(config.memory_report, None);
+    assert_eq!(config.format, OutputFormat::Text);
+}
+
+#[test]
+fn later_sources_override_earlier_ones() {
+    let path = config_file(
+        "layers.toml",
+        "width = 800\nheight = 600\nshader = \"toml/\"\nmesh = \"toml.obj\"\nheadless = true\n",
+    );
+    let env = [
+        ("VK_SAMPLE_CONFIG", path.as_str()),
+        ("VK_SAMPLE_HEIGHT", "700"),
+        ("VK_SAMPLE_SHADER", "env/"),
+        ("VK_SAMPLE_HEADLESS", "0"),
+        ("VK_SAMPLE_VALIDATION_FATAL", "1"),
+        //  VK_SAMPLE_ で始まらない環境変数は見ない
+        ("PATH", "/usr/bin"),
+    ];
+    let config = configs(&["--shader", "cli/", "--format", "json"], &env).unwrap();
+
+    assert_eq!(config.width, 800);
+    assert_eq!(config.height, 700);
+    assert_eq!(config.shader_dir, "cli/");
+    assert_eq!(config.mesh_file, "toml.obj");
+    assert!(!config.headless);
+    //  validation-fatal は validation も有効にする
+    assert!(config.validation_fatal && config.validation);
+    assert_eq!(config.format, OutputFormat::Json);
+}
+
+#[test]
+fn config_option_takes_precedence_over_the_environment() {
+    let option = config_file("option.toml", "width = 1024\n");
+    let ignored = config_file("ignored.toml", "width = 2048\n");
+    let config = configs(
+        &["--config", &option],
+        &[("VK_SAMPLE_CONFIG", ignored.as_str())],
+    )
+    .unwrap();
+    assert_eq!(config.width, 1024);
+}
+
+#[test]
+fn flags_and_numbers_are_validated() {
+    assert!(
+        configs(&[], &[("VK_SAMPLE_FULLSCREEN", "true")])
+            .unwrap()
+            .fullscreen
+    );
+    let error = configs(&[], &[("VK_SAMPLE_DEBUG", "yes")]).err().unwrap();
+    assert_eq!(
+        error,
+        "debug の値 \"yes\" は true か false でなければならない (環境変数 VK_SAMPLE_DEBUG)"
+    );
+
+    assert_eq!(
+        configs(&["--device", "0"], &[]).unwrap().device_index,
+        Some(0)
+    );
+    let error = configs(&["--width", "0"], &[]).err().unwrap();
+    assert_eq!(
+        error,
+        "width の値 \"0\" は 1 以上の整数でなければならない (--width)"
+    );
+    assert!(configs(&["--height=-1"], &[]).is_err());
+    assert!(configs(&["--device", "first"], &[]).is_err());
+    assert!(configs(&[], &[("VK_SAMPLE_FORMAT", "xml")]).is_err());
+    //  空のパスは指定しなかったものとして扱う
+    assert_eq!(
+        configs(&[], &[("VK_SAMPLE_SCREENSHOT", "")])
+            .unwrap()
+            .screenshot,
+        None
+    );
+}
+
+#[test]
+fn unknown_keys_are_errors() {
+    let error = configs(&[], &[("VK_SAMPLE_WIDHT", "800")]).err().unwrap();
+    assert_eq!(error, "環境変数 VK_SAMPLE_WIDHT に対応する設定は無い");
+
+    let path = config_file("unknown.toml", "widht = 800\n");
+    let error = configs(&["--config", &path], &[]).err().unwrap();
+    assert!(error.contains("不明なキー widht"), "{}", error);
+
+    let path = config_file("array.toml", "width = [800]\n");
+    assert!(configs(&["--config", &path], &[]).is_err());
+    let path = config_file("broken.toml", "width = \n");
+    assert!(configs(&["--config", &path], &[]).is_err());
+    assert!(configs(&["--config", "/nonexistent/config.toml"], &[]).is_err());
+}
+
+#[test]
+fn to_toml_round_trips() {
+    let original = configs(
+        &[
+            "--device",
+            "1",
+            "--width",
+            "320",
+            "--validation-fatal",
+            "--debug",
+            "--shader",
+            "shaders \"quoted\"/",
+            "--mesh",
+            "C:\\meshes\\box.dae",
+            "--screenshot",
+            "shot.png",
+            "--memory-report",
+            "memory.json",
+            "--format",
+            "json",
+        ],
+        &[],
+    )
+    .unwrap();
+    let text = original.to_toml();
+    let path = config_file("round_trip.toml", &text);
+    let restored = configs(&["--config", &path], &[]).unwrap();
+    assert_eq!(restored.to_toml(), text);
+    assert_eq!(restored.device_index, Some(1));
+    assert_eq!(restored.shader_dir, "shaders \"quoted\"/");
+    assert_eq!(restored.mesh_file, "C:\\meshes\\box.dae");
+
+    //  device を指定しない場合はコメントになり、読み直しても None のまま
+    let text = configs(&[], &[]).unwrap().to_toml();
+    let path = config_file("round_trip_default.toml", &text);
+    assert_eq!(
+        configs(&["--config", &path], &[]).unwrap().device_index,
+        None
+    );
+}
@@ -0,0 +1,31 @@
+//  debug の検証レイヤーの選び方を確かめる
+use vk_sample_common::debug::{
+    validation_layer, validation_layers, KHRONOS_VALIDATION, LUNARG_STANDARD_VALIDATION,
+};
+
+#[test]
+fn khronos_validation_is_preferred() {
+    assert_eq!(
+        validation_layer(&[
+            "VK_LAYER_MESA_overlay",
+            LUNARG_STANDARD_VALIDATION,
+            KHRONOS_VALIDATION
+        ]),
+        Some(KHRONOS_VALIDATION)
+    );
+    assert_eq!(
+        validation_layer(&[LUNARG_STANDARD_VALIDATION]),
+        Some(LUNARG_STANDARD_VALIDATION)
+    );
+    assert_eq!(validation_layer(&["VK_LAYER_MESA_overlay"]), None);
+}
+
+#[test]
+fn layers_are_enabled_only_when_validating() {
+    assert!(validation_layers(false, &[KHRONOS_VALIDATION]).is_empty());
+    assert_eq!(
+        validation_layers(true, &[KHRONOS_VALIDATION]),
+        vec![KHRONOS_VALIDATION]
+    );
+    assert!(validation_layers(true, Vec::<String>::new()).is_empty());
+}
@@ -0,0 +1,432 @@
+//  mesh::gltf の読み込みを data URI のバッファを埋め込んだ glTF と GLB で確かめる
+use serde_json::{json, Value};
+use vk_sample_common::mesh::gltf::parse;
+use vk_sample_common::mesh::Mesh;
+
+const FLOAT: u64 = 5126;
+const BYTE: u64 = 5120;
+const UNSIGNED_BYTE: u64 = 5121;
+const SHORT: u64 = 5122;
+const UNSIGNED_SHORT: u64 = 5123;
+const UNSIGNED_INT: u64 = 5125;
+
+//  バッファ 1 つと bufferView、 accessor を順に積み上げる
+#[derive(Default)]
+struct Builder {
+    data: Vec<u8>,
+    views: Vec<Value>,
+    accessors: Vec<Value>,
+}
+
+impl Builder {
+    fn view(&mut self, bytes: &[u8], stride: Option<usize>) -> usize {
+        while self.data.len() % 4 != 0 {
+            self.data.push(0);
+        }
+        let mut view = json!({
+            "buffer": 0,
+            "byteOffset": self.data.len(),
+            "byteLength": bytes.len(),
+        });
+        if let Some(stride) = stride {
+            view["byteStride"] = json!(stride);
+        }
+        self.data.extend_from_slice(bytes);
+        self.views.push(view);
+        self.views.len() - 1
+    }
+
+    fn accessor(&mut self, accessor: Value) -> usize {
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+
+    fn floats(&mut self, values: &[f32], kind: &str, components: usize) -> usize {
+        let view = self.view(&float_bytes(values), None);
+        self.accessor(json!({
+            "bufferView": view,
+            "componentType": FLOAT,
+            "count": values.len() / components,
+            "type": kind,
+        }))
+    }
+
+    //  data URI でバッファを埋め込んだ glTF にする
+    fn gltf(self, meshes: Value, nodes: Value) -> Vec<u8> {
+        let mut document = self.document(meshes, nodes);
+        document["buffers"][0]["uri"] = json!(format!(
+            "data:application/octet-stream;base64,{}",
+            base64::encode(&self.data)
+        ));
+        serde_json::to_vec(&document).unwrap()
+    }
+
+    //  JSON とバイナリのチャンクを持つ GLB にする
+    fn glb(self, meshes: Value, nodes: Value) -> Vec<u8> {
+        let mut json = serde_json::to_vec(&self.document(meshes, nodes)).unwrap();
+        while json.len() % 4 != 0 {
+            json.push(b' ');
+        }
+        let mut bin = self.data;
+        while bin.len() % 4 != 0 {
+            bin.push(0);
+        }
+        let mut glb = vec![];
+        let total = 12 + 8 + json.len() + 8 + bin.len();
+        for word in &[0x4654_6C67u32, 2, total as u32] {
+            glb.extend_from_slice(&word.to_le_bytes());
+        }
+        glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        glb.extend_from_slice(&0x4E4F_534Au32.to_le_bytes());
+        glb.extend_from_slice(&json);
+        glb.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        glb.extend_from_slice(&0x004E_4942u32.to_le_bytes());
+        glb.extend_from_slice(&bin);
+        glb
+    }
+
+    fn document(&self, meshes: Value, nodes: Value) -> Value {
+        json!({
+            "asset": { "version": "2.0" },
+            "buffers": [{ "byteLength": self.data.len() }],
+            "bufferViews": self.views,
+            "accessors": self.accessors,
+            "meshes": meshes,
+            "nodes": nodes,
+        })
+    }
+}
+
+fn float_bytes(values: &[f32]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|v| v.to_le_bytes().to_vec())
+        .collect()
+}
+
+fn parse_bytes(data: &[u8]) -> Mesh {
+    parse(data, std::path::Path::new("")).unwrap()
+}
+
+//  1 つのメッシュを 1 つのノードに置く
+fn single(attributes: Value) -> (Value, Value) {
+    (
+        json!([{ "primitives": [{ "attributes": attributes }] }]),
+        json!([{ "mesh": 0 }]),
+    )
+}
+
+const TRIANGLE: [f32; 9] = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+
+fn positions(mesh: &Mesh) -> Vec<[f32; 3]> {
+    mesh.indices
+        .iter()
+        .map(|i| {
+            let p = mesh.vertices[*i as usize].position;
+            [p.x, p.y, p.z]
+        })
+        .collect()
+}
+
+fn assert_close(actual: &[f32], expected: &[f32]) {
+    assert_eq!(actual.len(), expected.len());
+    for (a, e) in actual.iter().zip(expected) {
+        assert!((a - e).abs() < 1e-4, "{:?} != {:?}", actual, expected);
+    }
+}
+
+#[test]
+fn interleaved_attributes_follow_the_byte_stride() {
+    //  位置 (12 バイト) とテクスチャ座標 (8 バイト) を 1 つの bufferView に交互に並べる
+    let mut interleaved = vec![];
+    for i in 0..3 {
+        interleaved.extend_from_slice(&TRIANGLE[i * 3..i * 3 + 3]);
+        interleaved.extend_from_slice(&[i as f32 * 0.25, 1.0 - i as f32 * 0.25]);
+    }
+    let mut builder = Builder::default();
+    let view = builder.view(&float_bytes(&interleaved), Some(20));
+    let position = builder.accessor(json!({
+        "bufferView": view, "componentType": FLOAT, "count": 3, "type": "VEC3",
+    }));
+    let texcoord = builder.accessor(json!({
+        "bufferView": view, "byteOffset": 12, "componentType": FLOAT, "count": 3, "type": "VEC2",
+    }));
+    let (meshes, nodes) = single(json!({ "POSITION": position, "TEXCOORD_0": texcoord }));
+    let mesh = parse_bytes(&builder.gltf(meshes, nodes));
+
+    assert_eq!(mesh.indices, vec![0, 1, 2]);
+    for (i, v) in mesh.vertices.iter().enumerate() {
+        let p = v.position;
+        let uv = v.texcoord;
+        assert_close(&[p.x, p.y, p.z], &TRIANGLE[i * 3..i * 3 + 3]);
+        assert_close(&[uv.x, uv.y], &[i as f32 * 0.25, 1.0 - i as f32 * 0.25]);
+    }
+}
+
+#[test]
+fn normalized_integers_are_scaled() {
+    let mut builder = Builder::default();
+    let position = builder.floats(&TRIANGLE, "VEC3", 3);
+    //  BYTE の法線は要素ごとに 4 バイトに揃える
+    let normal_bytes = [0i8, 0, 127, 0, 0, 0, 127, 0, 0, 0, -128, 0];
+    let normal_view = builder.view(
+        &normal_bytes.iter().map(|b| *b as u8).collect::<Vec<_>>(),
+        Some(4),
+    );
+    let normal = builder.accessor(json!({
+        "bufferView": normal_view, "componentType": BYTE, "normalized": true,
+        "count": 3, "type": "VEC3",
+    }));
+    let texcoord_bytes = [0i16, 32767, 16384, -32768, 32767, 0]
+        .iter()
+        .flat_map(|v| v.to_le_bytes().to_vec())
+        .collect::<Vec<_>>();
+    let texcoord_view = builder.view(&texcoord_bytes, None);
+    let texcoord = builder.accessor(json!({
+        "bufferView": texcoord_view, "componentType": SHORT, "normalized": true,
+        "count": 3, "type": "VEC2",
+    }));
+    let (meshes, nodes) = single(json!({
+        "POSITION": position, "NORMAL": normal, "TEXCOORD_0": texcoord,
+    }));
+    let mesh = parse_bytes(&builder.gltf(meshes, nodes));
+
+    let normals = mesh
+        .vertices
+        .iter()
+        .map(|v| {
+            let n = v.normal;
+            [n.x, n.y, n.z]
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        normals,
+        vec![[0.0, 0.0, 1.0], [0.0, 0.0, 1.0], [0.0, 0.0, -1.0]]
+    );
+    let texcoords = mesh
+        .vertices
+        .iter()
+        .flat_map(|v| {
+            let uv = v.texcoord;
+            vec![uv.x, uv.y]
+        })
+        .collect::<Vec<_>>();
+    //  SHORT の -32768 は -1.0 に切り詰められる
+    assert_close(&texcoords, &[0.0, 1.0, 16384.0 / 32767.0, -1.0, 1.0, 0.0]);
+}
+
+#[test]
+fn indices_of_every_component_type() {
+    let mut builder = Builder::default();
+    let position = builder.floats(&TRIANGLE, "VEC3", 3);
+    let mut primitives = vec![];
+    let index_bytes: Vec<(u64, Vec<u8>)> = vec![
+        (UNSIGNED_BYTE, vec![0, 2, 1]),
+        (
+            UNSIGNED_SHORT,
+            [2u16, 1, 0]
+                .iter()
+                .flat_map(|i| i.to_le_bytes().to_vec())
+                .collect(),
+        ),
+        (
+            UNSIGNED_INT,
+            [1u32, 0, 2]
+                .iter()
+                .flat_map(|i| i.to_le_bytes().to_vec())
+                .collect(),
+        ),
+    ];
+    for (component_type, bytes) in index_bytes {
+        let view = builder.view(&bytes, None);
+        let indices = builder.accessor(json!({
+            "bufferView": view, "componentType": component_type, "count": 3, "type": "SCALAR",
+        }));
+        primitives.push(json!({ "attributes": { "POSITION": position }, "indices": indices }));
+    }
+    let meshes = json!([{ "name": "indexed", "primitives": primitives }]);
+    let mesh = parse_bytes(&builder.gltf(meshes, json!([{ "mesh": 0 }])));
+
+    //  プリミティブごとに頂点を持つので、 2 つ目以降は 3 ずつずれる
+    assert_eq!(mesh.indices, vec![0, 2, 1, 5, 4, 3, 7, 6, 8]);
+    assert_eq!(mesh.groups.len(), 3);
+    for (i, group) in mesh.groups.iter().enumerate() {
+        assert_eq!(group.name, "indexed");
+        assert_eq!(group.first_index, i as u32 * 3);
+        assert_eq!(group.index_count, 3);
+    }
+}
+
+#[test]
+fn sparse_accessors_replace_elements() {
+    let mut builder = Builder::default();
+    let base = builder.view(&float_bytes(&TRIANGLE), None);
+    let sparse_indices = builder.view(&[2u16.to_le_bytes(), 0u16.to_le_bytes()].concat(), None);
+    let sparse_values = builder.view(&float_bytes(&[0.0, 2.0, 0.0, 0.0, 0.0, 1.0]), None);
+    let sparse = json!({
+        "count": 2,
+        "indices": { "bufferView": sparse_indices, "componentType": UNSIGNED_SHORT },
+        "values": { "bufferView": sparse_values },
+    });
+    let position = builder.accessor(json!({
+        "bufferView": base, "componentType": FLOAT, "count": 3, "type": "VEC3",
+        "sparse": sparse,
+    }));
+    //  bufferView の無い sparse accessor は 0 から始まる
+    let without_view = builder.accessor(json!({
+        "componentType": FLOAT, "count": 3, "type": "VEC3", "sparse": sparse,
+    }));
+    let meshes = json!([
+        { "primitives": [{ "attributes": { "POSITION": position } }] },
+        { "primitives": [{ "attributes": { "POSITION": without_view } }] },
+    ]);
+    let nodes = json!([{ "mesh": 0 }, { "mesh": 1, "translation": [0.0, 0.0, 5.0] }]);
+    let mesh = parse_bytes(&builder.gltf(meshes, nodes));
+
+    assert_eq!(
+        positions(&mesh),
+        vec![
+            [0.0, 0.0, 1.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 2.0, 0.0],
+            [0.0, 0.0, 6.0],
+            [0.0, 0.0, 5.0],
+            [0.0, 2.0, 5.0],
+        ]
+    );
+}
+
+#[test]
+fn binary_glb() {
+    let mut builder = Builder::default();
+    let position = builder.floats(&TRIANGLE, "VEC3", 3);
+    let tangent = builder.floats(
+        &[
+            1.0, 0.0, 0.0, -1.0, 1.0, 0.0, 0.0, -1.0, 1.0, 0.0, 0.0, -1.0,
+        ],
+        "VEC4",
+        4,
+    );
+    let (meshes, nodes) = single(json!({ "POSITION": position, "TANGENT": tangent }));
+    let mesh = parse_bytes(&builder.glb(meshes, nodes));
+
+    assert_eq!(
+        positions(&mesh),
+        vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+    );
+    //  法線は無いので面から作られ、 TANGENT の w はそのまま残る
+    for v in mesh.vertices.iter() {
+        let normal = v.normal;
+        let tangent = v.tangent;
+        assert_close(&[normal.x, normal.y, normal.z], &[0.0, 0.0, 1.0]);
+        assert_close(
+            &[tangent.x, tangent.y, tangent.z, tangent.w],
+            &[1.0, 0.0, 0.0, -1.0],
+        );
+    }
+}
+
+#[test]
+fn truncated_glb_is_an_error() {
+    let mut builder = Builder::default();
+    let position = builder.floats(&TRIANGLE, "VEC3", 3);
+    let (meshes, nodes) = single(json!({ "POSITION": position }));
+    let mut glb = builder.glb(meshes, nodes);
+    let length = glb.len() as u32 + 16;
+    glb[8..12].copy_from_slice(&length.to_le_bytes());
+    glb.truncate(glb.len() - 4);
+    assert!(parse(&glb, std::path::Path::new("")).is_err());
+}
+
+#[test]
+fn node_hierarchy_combines_trs_and_matrix() {
+    let mut builder = Builder::default();
+    let position = builder.floats(&TRIANGLE, "VEC3", 3);
+    let (meshes, _) = single(json!({ "POSITION": position }));
+    //  親は z 軸で 90 度回して x に 10 動かし、子は列優先の行列で y に 2 動かして 3 倍にする
+    let half = std::f32::consts::FRAC_1_SQRT_2;
+    let nodes = json!([
+        {
+            "children": [1],
+            "translation": [10.0, 0.0, 0.0],
+            "rotation": [0.0, 0.0, half, half],
+        },
+        {
+            "mesh": 0,
+            "matrix": [
+                3.0, 0.0, 0.0, 0.0,
+                0.0, 3.0, 0.0, 0.0,
+                0.0, 0.0, 3.0, 0.0,
+                0.0, 2.0, 0.0, 1.0,
+            ],
+        },
+    ]);
+    let mesh = parse_bytes(&builder.gltf(meshes, nodes));
+
+    //  (x, y) は子で (3x, 3y + 2)、親の回転で (-(3y + 2), 3x)、平行移動で x に 10 足される
+    let expected = [[8.0, 0.0, 0.0], [8.0, 3.0, 0.0], [5.0, 0.0, 0.0]];
+    for (actual, expected) in positions(&mesh).iter().zip(expected.iter()) {
+        assert_close(actual, expected);
+    }
+    assert_eq!(mesh.indices, vec![0, 1, 2]);
+}
+
+#[test]
+fn mirroring_scale_flips_winding_and_handedness() {
+    let mut builder = Builder::default();
+    let position = builder.floats(&TRIANGLE, "VEC3", 3);
+    let normal = builder.floats(&[0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0], "VEC3", 3);
+    let tangent = builder.floats(
+        &[1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0],
+        "VEC4",
+        4,
+    );
+    let (meshes, _) = single(json!({
+        "POSITION": position, "NORMAL": normal, "TANGENT": tangent,
+    }));
+    let nodes = json!([
+        { "children": [1], "scale": [-1.0, 1.0, 1.0] },
+        { "mesh": 0 },
+    ]);
+    let mesh = parse_bytes(&builder.gltf(meshes, nodes));
+
+    assert_eq!(mesh.indices, vec![0, 2, 1]);
+    //  面の向き (時計回りかどうか) は変わらず、法線と同じ側を向いている
+    let p = positions(&mesh);
+    let edge1 = nalgebra_glm::vec3(p[1][0] - p[0][0], p[1][1] - p[0][1], p[1][2] - p[0][2]);
+    let edge2 = nalgebra_glm::vec3(p[2][0] - p[0][0], p[2][1] - p[0][1], p[2][2] - p[0][2]);
+    assert!(nalgebra_glm::cross(&edge1, &edge2).z > 0.0);
+    for v in mesh.vertices.iter() {
+        let normal = v.normal;
+        let tangent = v.tangent;
+        assert_close(&[normal.x, normal.y, normal.z], &[0.0, 0.0, 1.0]);
+        assert_close(
+            &[tangent.x, tangent.y, tangent.z, tangent.w],
+            &[-1.0, 0.0, 0.0, -1.0],
+        );
+        //  従法線 w * cross(normal, tangent) は元の +y のまま
+        let bitangent = nalgebra_glm::cross(&normal, &tangent.xyz()) * tangent.w;
+        assert_close(&[bitangent.x, bitangent.y, bitangent.z], &[0.0, 1.0, 0.0]);
+    }
+}
+
+#[test]
+fn unsupported_version_is_an_error() {
+    let document = br#"{ "asset": { "version": "1.0" } }"#;
+    assert!(parse(document, std::path::Path::new("")).is_err());
+}
+
+#[test]
+fn unsupported_required_extensions_are_an_error() {
+    let document = br#"{
+        "asset": { "version": "2.0" },
+        "extensionsUsed": [ "KHR_draco_mesh_compression", "KHR_mesh_quantization" ],
+        "extensionsRequired": [ "KHR_draco_mesh_compression", "KHR_mesh_quantization" ]
+    }"#;
+    let error = match parse(document, std::path::Path::new("")) {
+        Ok(_) => panic!("必須の拡張を無視して読み込んだ"),
+        Err(e) => e,
+    };
+    assert!(error.contains("KHR_draco_mesh_compression"), "{}", error);
+    assert!(error.contains("KHR_mesh_quantization"), "{}", error);
+}
@@ -0,0 +1,109 @@
+//  memory_report の JSON の組み立てを合成した統計で確かめる
+#![cfg(feature = "vk-mem")]
+use vk_sample_common::memory_report::report_from_stats;
+use vk_sample_common::memory_type::HeapBudget;
+
+const MIB: u64 = 1024 * 1024;
+
+fn memory_properties() -> ash::vk::PhysicalDeviceMemoryProperties {
+    let mut props = ash::vk::PhysicalDeviceMemoryProperties::default();
+    props.memory_heap_count = 2;
+    props.memory_heaps[0] = ash::vk::MemoryHeap {
+        size: 4096 * MIB,
+        flags: ash::vk::MemoryHeapFlags::DEVICE_LOCAL,
+    };
+    props.memory_heaps[1] = ash::vk::MemoryHeap {
+        size: 8192 * MIB,
+        flags: ash::vk::MemoryHeapFlags::empty(),
+    };
+    props.memory_type_count = 2;
+    props.memory_types[0] = ash::vk::MemoryType {
+        property_flags: ash::vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        heap_index: 0,
+    };
+    props.memory_types[1] = ash::vk::MemoryType {
+        property_flags: ash::vk::MemoryPropertyFlags::HOST_VISIBLE
+            | ash::vk::MemoryPropertyFlags::HOST_COHERENT,
+        heap_index: 1,
+    };
+    props
+}
+
+fn stat_info(blocks: u32, allocations: u32, used: u64) -> vk_mem::ffi::VmaStatInfo {
+    let mut info: vk_mem::ffi::VmaStatInfo = unsafe { std::mem::zeroed() };
+    info.blockCount = blocks;
+    info.allocationCount = allocations;
+    info.usedBytes = used;
+    info.unusedBytes = blocks as u64 * 256 * MIB - used;
+    info.allocationSizeMax = used;
+    info
+}
+
+//  デバイスローカルに 2 つ、ホストから見えるメモリに 1 つ割り当てた状態
+fn stats() -> vk_mem::ffi::VmaStats {
+    let mut stats: vk_mem::ffi::VmaStats = unsafe { std::mem::zeroed() };
+    stats.memoryType[0] = stat_info(1, 2, 3 * MIB);
+    stats.memoryType[1] = stat_info(1, 1, MIB);
+    stats.memoryHeap[0] = stats.memoryType[0];
+    stats.memoryHeap[1] = stats.memoryType[1];
+    stats.total = stat_info(2, 3, 4 * MIB);
+    stats
+}
+
+#[test]
+fn heaps_and_types_carry_their_statistics() {
+    let report = report_from_stats(&memory_properties(), &stats(), None);
+    assert_eq!(report["total"]["allocations"], 3);
+    assert_eq!(report["total"]["used_bytes"], 4 * MIB);
+
+    let heaps = report["memory_heaps"].as_array().unwrap();
+    assert_eq!(heaps.len(), 2);
+    assert_eq!(heaps[0]["size"], 4096 * MIB);
+    assert_eq!(heaps[0]["flags"], serde_json::json!(["DEVICE_LOCAL"]));
+    assert_eq!(heaps[0]["allocations"], 2);
+    assert_eq!(heaps[1]["unused_bytes"], 255 * MIB);
+
+    let types = report["memory_types"].as_array().unwrap();
+    assert_eq!(types.len(), 2);
+    assert_eq!(types[1]["heap_index"], 1);
+    assert_eq!(types[1]["blocks"], 1);
+    assert_eq!(types[1]["largest_allocation"], MIB);
+}
+
+#[test]
+fn budgets_are_null_without_the_extension() {
+    let report = report_from_stats(&memory_properties(), &stats(), None);
+    assert_eq!(report["memory_budget"], false);
+    for heap in report["memory_heaps"].as_array().unwrap() {
+        assert!(heap["budget"].is_null());
+        assert!(heap["usage"].is_null());
+    }
+}
+
+#[test]
+fn budgets_are_reported_per_heap() {
+    let budgets = [
+        HeapBudget {
+            budget: 3500 * MIB,
+            usage: 900 * MIB,
+        },
+        HeapBudget {
+            budget: 6000 * MIB,
+            usage: 10 * MIB,
+        },
+    ];
+    let report = report_from_stats(&memory_properties(), &stats(), Some(&budgets));
+    assert_eq!(report["memory_budget"], true);
+    assert_eq!(report["memory_heaps"][0]["budget"], 3500 * MIB);
+    assert_eq!(report["memory_heaps"][0]["usage"], 900 * MIB);
+    assert_eq!(report["memory_heaps"][1]["usage"], 10 * MIB);
+}
+
+#[test]
+fn empty_types_report_no_largest_allocation() {
+    let mut stats = stats();
+    stats.memoryType[1] = unsafe { std::mem::zeroed() };
+    stats.memoryType[1].allocationSizeMax = u64::MAX;
+    let report = report_from_stats(&memory_properties(), &stats, None);
+    assert_eq!(report["memory_types"][1]["largest_allocation"], 0);
+}
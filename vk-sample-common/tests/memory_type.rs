@@ -0,0 +1,252 @@
+//  memory_type の選び方を実際のデバイスに似せたメモリタイプの並びで確かめる
+use ash::vk::MemoryPropertyFlags as Flags;
+use vk_sample_common::memory_type::{
+    memory_type_candidates, select_memory_type, HeapBudget, MemoryTypeError, MemoryTypeRequest,
+};
+
+const MIB: u64 = 1024 * 1024;
+const GIB: u64 = 1024 * MIB;
+const ALL_TYPES: u32 = !0;
+
+fn memory_properties(
+    heaps: &[(u64, ash::vk::MemoryHeapFlags)],
+    types: &[(Flags, u32)],
+) -> ash::vk::PhysicalDeviceMemoryProperties {
+    let mut props = ash::vk::PhysicalDeviceMemoryProperties::default();
+    props.memory_heap_count = heaps.len() as u32;
+    for (i, (size, flags)) in heaps.iter().enumerate() {
+        props.memory_heaps[i] = ash::vk::MemoryHeap {
+            size: *size,
+            flags: *flags,
+        };
+    }
+    props.memory_type_count = types.len() as u32;
+    for (i, (flags, heap_index)) in types.iter().enumerate() {
+        props.memory_types[i] = ash::vk::MemoryType {
+            property_flags: *flags,
+            heap_index: *heap_index,
+        };
+    }
+    props
+}
+
+//  ディスクリート GPU (VRAM 8GiB 、 256MiB の BAR)
+fn discrete() -> ash::vk::PhysicalDeviceMemoryProperties {
+    memory_properties(
+        &[
+            (8 * GIB, ash::vk::MemoryHeapFlags::DEVICE_LOCAL),
+            (16 * GIB, ash::vk::MemoryHeapFlags::empty()),
+            (256 * MIB, ash::vk::MemoryHeapFlags::DEVICE_LOCAL),
+        ],
+        &[
+            (Flags::empty(), 1),
+            (Flags::DEVICE_LOCAL, 0),
+            (Flags::HOST_VISIBLE | Flags::HOST_COHERENT, 1),
+            (
+                Flags::HOST_VISIBLE | Flags::HOST_COHERENT | Flags::HOST_CACHED,
+                1,
+            ),
+            (
+                Flags::DEVICE_LOCAL | Flags::HOST_VISIBLE | Flags::HOST_COHERENT,
+                2,
+            ),
+        ],
+    )
+}
+
+//  統合 GPU (メモリは全て DEVICE_LOCAL)
+fn integrated() -> ash::vk::PhysicalDeviceMemoryProperties {
+    memory_properties(
+        &[(4 * GIB, ash::vk::MemoryHeapFlags::DEVICE_LOCAL)],
+        &[
+            (Flags::DEVICE_LOCAL, 0),
+            (
+                Flags::DEVICE_LOCAL | Flags::HOST_VISIBLE | Flags::HOST_COHERENT,
+                0,
+            ),
+            (
+                Flags::DEVICE_LOCAL
+                    | Flags::HOST_VISIBLE
+                    | Flags::HOST_COHERENT
+                    | Flags::HOST_CACHED,
+                0,
+            ),
+        ],
+    )
+}
+
+//  ソフトウェアラスタライザ (lavapipe)
+fn software() -> ash::vk::PhysicalDeviceMemoryProperties {
+    memory_properties(
+        &[(2 * GIB, ash::vk::MemoryHeapFlags::DEVICE_LOCAL)],
+        &[(
+            Flags::DEVICE_LOCAL | Flags::HOST_VISIBLE | Flags::HOST_COHERENT | Flags::HOST_CACHED,
+            0,
+        )],
+    )
+}
+
+fn device_local(size: u64) -> MemoryTypeRequest {
+    MemoryTypeRequest {
+        type_bits: ALL_TYPES,
+        preferred_flags: Flags::DEVICE_LOCAL,
+        size: size,
+        ..MemoryTypeRequest::default()
+    }
+}
+
+fn staging(size: u64) -> MemoryTypeRequest {
+    MemoryTypeRequest {
+        type_bits: ALL_TYPES,
+        required_flags: Flags::HOST_VISIBLE | Flags::HOST_COHERENT,
+        size: size,
+        ..MemoryTypeRequest::default()
+    }
+}
+
+fn readback(size: u64) -> MemoryTypeRequest {
+    MemoryTypeRequest {
+        preferred_flags: Flags::HOST_CACHED,
+        ..staging(size)
+    }
+}
+
+#[test]
+fn discrete_device_local_uses_vram_not_bar() {
+    assert_eq!(
+        select_memory_type(&discrete(), &device_local(MIB), None),
+        Ok(1)
+    );
+}
+
+#[test]
+fn discrete_staging_avoids_device_local_and_cached() {
+    assert_eq!(select_memory_type(&discrete(), &staging(MIB), None), Ok(2));
+    assert_eq!(select_memory_type(&discrete(), &readback(MIB), None), Ok(3));
+}
+
+#[test]
+fn discrete_falls_back_when_type_bits_exclude_vram() {
+    let request = MemoryTypeRequest {
+        type_bits: 0b11101,
+        ..device_local(MIB)
+    };
+    assert_eq!(select_memory_type(&discrete(), &request, None), Ok(4));
+}
+
+#[test]
+fn discrete_candidates_are_ordered_best_first() {
+    assert_eq!(
+        memory_type_candidates(&discrete(), &device_local(MIB), None),
+        Ok(vec![1, 4, 0, 2, 3])
+    );
+}
+
+#[test]
+fn large_resources_skip_small_heaps() {
+    let request = MemoryTypeRequest {
+        type_bits: 0b10000,
+        ..device_local(512 * MIB)
+    };
+    assert_eq!(
+        select_memory_type(&discrete(), &request, None),
+        Err(MemoryTypeError::HeapTooSmall { size: 512 * MIB })
+    );
+    //  BAR に入らない大きさなら VRAM から
+    let request = MemoryTypeRequest {
+        required_flags: Flags::DEVICE_LOCAL,
+        ..device_local(512 * MIB)
+    };
+    assert_eq!(
+        memory_type_candidates(&discrete(), &request, None),
+        Ok(vec![1])
+    );
+}
+
+#[test]
+fn exhausted_budget_moves_to_another_heap() {
+    let budgets = [
+        HeapBudget {
+            budget: 8 * GIB,
+            usage: 8 * GIB - MIB,
+        },
+        HeapBudget {
+            budget: 16 * GIB,
+            usage: 0,
+        },
+        HeapBudget {
+            budget: 256 * MIB,
+            usage: 0,
+        },
+    ];
+    assert_eq!(
+        select_memory_type(&discrete(), &device_local(64 * MIB), Some(&budgets)),
+        Ok(4)
+    );
+
+    let request = MemoryTypeRequest {
+        required_flags: Flags::DEVICE_LOCAL,
+        ..device_local(GIB)
+    };
+    assert_eq!(
+        select_memory_type(&discrete(), &request, Some(&budgets)),
+        Err(MemoryTypeError::OverBudget { size: GIB })
+    );
+}
+
+#[test]
+fn integrated_prefers_plain_device_local() {
+    assert_eq!(
+        select_memory_type(&integrated(), &device_local(MIB), None),
+        Ok(0)
+    );
+    assert_eq!(
+        select_memory_type(&integrated(), &staging(MIB), None),
+        Ok(1)
+    );
+    assert_eq!(
+        select_memory_type(&integrated(), &readback(MIB), None),
+        Ok(2)
+    );
+}
+
+#[test]
+fn software_has_a_single_type_for_everything() {
+    for request in [device_local(MIB), staging(MIB), readback(MIB)].iter() {
+        assert_eq!(select_memory_type(&software(), request, None), Ok(0));
+    }
+}
+
+#[test]
+fn reports_why_no_type_matches() {
+    let request = MemoryTypeRequest {
+        type_bits: 0,
+        ..device_local(MIB)
+    };
+    assert_eq!(
+        select_memory_type(&discrete(), &request, None),
+        Err(MemoryTypeError::NoCompatibleType { type_bits: 0 })
+    );
+
+    //  type 0 と 1 はホストから見えない
+    let request = MemoryTypeRequest {
+        type_bits: 0b00011,
+        ..staging(MIB)
+    };
+    assert_eq!(
+        select_memory_type(&discrete(), &request, None),
+        Err(MemoryTypeError::MissingRequiredFlags {
+            required_flags: Flags::HOST_VISIBLE | Flags::HOST_COHERENT
+        })
+    );
+
+    //  type_bits にはメモリタイプの数より上のビットが立っていても良い
+    let request = MemoryTypeRequest {
+        type_bits: 1 << 31,
+        ..device_local(MIB)
+    };
+    assert_eq!(
+        select_memory_type(&software(), &request, None),
+        Err(MemoryTypeError::NoCompatibleType { type_bits: 1 << 31 })
+    );
+}
@@ -0,0 +1,122 @@
+//  Mesh のインデックスの形式、頂点の溶接、連結、バウンディングボックスを確かめる
+use vk_sample_common::mesh::{Group, Indices, Mesh};
+use vk_sample_common::Vertex;
+
+fn vertex(x: f32, y: f32, z: f32) -> Vertex {
+    let mut vertex = Vertex::default();
+    vertex.position = nalgebra_glm::vec3(x, y, z);
+    vertex.normal = nalgebra_glm::vec3(0.0, 0.0, 1.0);
+    vertex
+}
+
+fn mesh_with_vertices(count: usize) -> Mesh {
+    Mesh {
+        vertices: (0..count).map(|i| vertex(i as f32, 0.0, 0.0)).collect(),
+        indices: vec![0, 1, count as u32 - 1],
+        ..Mesh::default()
+    }
+}
+
+#[test]
+fn indices_are_16_bit_up_to_65536_vertices() {
+    let indices = mesh_with_vertices(65536).indices();
+    match &indices {
+        Indices::U16(i) => assert_eq!(i, &vec![0, 1, 65535]),
+        Indices::U32(_) => panic!("65536 個の頂点は 16 ビットで表せる"),
+    }
+    assert_eq!(indices.len(), 3);
+    assert_eq!(indices.as_bytes(), &[0, 0, 1, 0, 0xff, 0xff]);
+}
+
+#[test]
+fn indices_are_32_bit_above_65536_vertices() {
+    let indices = mesh_with_vertices(65537).indices();
+    match &indices {
+        Indices::U32(i) => assert_eq!(i, &vec![0, 1, 65536]),
+        Indices::U16(_) => panic!("65537 個の頂点は 16 ビットで表せない"),
+    }
+    assert_eq!(indices.as_bytes().len(), 12);
+    assert_eq!(&indices.as_bytes()[8..], &65536u32.to_le_bytes());
+}
+
+#[test]
+fn weld_merges_bit_identical_vertices() {
+    let mut mirrored = vertex(1.0, 0.0, 0.0);
+    mirrored.tangent = nalgebra_glm::vec4(1.0, 0.0, 0.0, -1.0);
+    let mut mesh = Mesh {
+        vertices: vec![
+            vertex(0.0, 0.0, 0.0),
+            vertex(1.0, 0.0, 0.0),
+            vertex(0.0, 1.0, 0.0),
+            vertex(1.0, 0.0, 0.0),
+            vertex(0.0, 1.0, 0.0),
+            vertex(1.0, 1.0, 0.0),
+            //  0.0 と -0.0 はビットが違うのでまとめない
+            vertex(-0.0, 0.0, 0.0),
+            //  接線の w だけが違う頂点もまとめない
+            mirrored,
+        ],
+        indices: vec![0, 1, 2, 3, 5, 4, 6, 7, 2],
+        ..Mesh::default()
+    };
+    mesh.weld();
+
+    assert_eq!(mesh.vertices.len(), 6);
+    assert_eq!(mesh.indices, vec![0, 1, 2, 1, 3, 2, 4, 5, 2]);
+    let x = mesh
+        .vertices
+        .iter()
+        .map(|v| {
+            let p = v.position;
+            p.x
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(x, vec![0.0, 1.0, 0.0, 1.0, -0.0, 1.0]);
+    let tangent = mesh.vertices[5].tangent;
+    assert_eq!(tangent.w, -1.0);
+}
+
+#[test]
+fn append_offsets_indices_groups_and_materials() {
+    let mut mesh = mesh_with_vertices(3);
+    mesh.groups.push(Group {
+        name: String::from("first"),
+        material: Some(0),
+        first_index: 0,
+        index_count: 3,
+    });
+    mesh.materials.push(Default::default());
+    let mut other = mesh_with_vertices(4);
+    other.groups.push(Group {
+        name: String::from("second"),
+        material: Some(0),
+        first_index: 0,
+        index_count: 3,
+    });
+    other.materials.push(Default::default());
+    mesh.append(other);
+
+    assert_eq!(mesh.vertices.len(), 7);
+    assert_eq!(mesh.indices, vec![0, 1, 2, 3, 4, 6]);
+    assert_eq!(mesh.groups[1].first_index, 3);
+    assert_eq!(mesh.groups[1].material, Some(1));
+    assert_eq!(mesh.materials.len(), 2);
+}
+
+#[test]
+fn bounds_enclose_every_vertex() {
+    assert!(Mesh::default().bounds().is_none());
+    let mesh = Mesh {
+        vertices: vec![
+            vertex(1.0, -2.0, 3.0),
+            vertex(-1.0, 4.0, 0.0),
+            vertex(0.0, 0.0, -5.0),
+        ],
+        ..Mesh::default()
+    };
+    let bounds = mesh.bounds().unwrap();
+    assert_eq!(bounds.min, nalgebra_glm::vec3(-1.0, -2.0, -5.0));
+    assert_eq!(bounds.max, nalgebra_glm::vec3(1.0, 4.0, 3.0));
+    assert_eq!(bounds.center(), nalgebra_glm::vec3(0.0, 1.0, -1.0));
+    assert_eq!(bounds.size(), nalgebra_glm::vec3(2.0, 6.0, 8.0));
+}
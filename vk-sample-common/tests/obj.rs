@@ -0,0 +1,174 @@
+//  mesh::obj の読み込みを OBJ と MTL の文字列で確かめる
+use vk_sample_common::mesh::obj::{parse, parse_mtl};
+use vk_sample_common::mesh::Mesh;
+
+const QUAD: &str = "
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+";
+
+fn parse_text(text: &str) -> Mesh {
+    parse(text, std::path::Path::new("")).unwrap()
+}
+
+fn positions(mesh: &Mesh) -> Vec<[f32; 3]> {
+    mesh.indices
+        .iter()
+        .map(|i| {
+            let p = mesh.vertices[*i as usize].position;
+            [p.x, p.y, p.z]
+        })
+        .collect()
+}
+
+#[test]
+fn negative_indices_count_from_the_end() {
+    let mesh = parse_text(&format!("{}f -1 -2 -3\n", QUAD));
+    assert_eq!(
+        positions(&mesh),
+        vec![[0.0, 1.0, 0.0], [1.0, 1.0, 0.0], [1.0, 0.0, 0.0]]
+    );
+}
+
+#[test]
+fn negative_indices_only_see_earlier_elements() {
+    //  -1 は f より前に定義された最後の頂点
+    let mesh = parse_text("v 0 0 0\nv 1 0 0\nv 1 1 0\nf -3 -2 -1\nv 5 5 5\n");
+    assert_eq!(positions(&mesh)[2], [1.0, 1.0, 0.0]);
+    assert!(parse("v 0 0 0\nv 1 0 0\nf -1 -2 -3\n", std::path::Path::new("")).is_err());
+}
+
+#[test]
+fn g_o_and_usemtl_split_groups() {
+    let mesh = parse_text(&format!(
+        "{}g first\nf 1 2 3\nf 1 3 4\no second\nf 1 2 3\nusemtl red\nf 1 3 4\nusemtl red\nf 1 2 4\n",
+        QUAD
+    ));
+    let groups = mesh
+        .groups
+        .iter()
+        .map(|g| (g.name.as_str(), g.material, g.first_index, g.index_count))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        groups,
+        vec![
+            ("first", None, 0, 6),
+            ("second", None, 6, 3),
+            ("second", Some(0), 9, 3),
+            ("second", Some(0), 12, 3),
+        ]
+    );
+    //  MTL に無いマテリアルは既定の値で作る
+    assert_eq!(mesh.materials.len(), 1);
+    assert_eq!(mesh.materials[0].name, "red");
+}
+
+#[test]
+fn shared_corners_become_one_vertex() {
+    let mesh = parse_text(&format!(
+        "{}f 1/1/1 2/2/1 3/3/1\nf 1/1/1 3/3/1 4/4/1\n",
+        QUAD
+    ));
+    assert_eq!(mesh.vertices.len(), 4);
+    assert_eq!(mesh.indices, vec![0, 1, 2, 0, 2, 3]);
+}
+
+#[test]
+fn different_texcoords_keep_vertices_apart() {
+    let mesh = parse_text(&format!(
+        "{}f 1/1/1 2/2/1 3/3/1\nf 1/2/1 3/3/1 4/4/1\n",
+        QUAD
+    ));
+    assert_eq!(mesh.vertices.len(), 5);
+}
+
+#[test]
+fn polygons_are_fan_triangulated() {
+    let mesh = parse_text("v 0 0 0\nv 1 0 0\nv 2 1 0\nv 1 2 0\nv 0 1 0\nf 1 2 3 4 5\n");
+    assert_eq!(mesh.indices, vec![0, 1, 2, 0, 2, 3, 0, 3, 4]);
+    //  法線が無いので面から作る
+    let n = mesh.vertices[0].normal;
+    assert!((n.z - 1.0).abs() < 1e-5);
+}
+
+#[test]
+fn faces_need_three_vertices_and_valid_indices() {
+    let dir = std::path::Path::new("");
+    assert!(parse(&format!("{}f 1 2\n", QUAD), dir).is_err());
+    assert!(parse(&format!("{}f 1 2 5\n", QUAD), dir).is_err());
+    assert!(parse(&format!("{}f 1/9 2 3\n", QUAD), dir).is_err());
+    assert!(parse(QUAD, dir).is_err());
+}
+
+#[test]
+fn mtl_colors_opacity_and_textures() {
+    let dir = std::path::Path::new("textures");
+    let materials = parse_mtl(
+        "newmtl red\nKd 1 0 0\nd 0.5\nmap_Kd -bm 1 red.png\nnewmtl glass\nTr 0.25\nmap_Bump glass_n.png\n",
+        dir,
+    )
+    .unwrap();
+    assert_eq!(materials.len(), 2);
+    assert_eq!(materials[0].name, "red");
+    assert_eq!(materials[0].diffuse, nalgebra_glm::vec3(1.0, 0.0, 0.0));
+    assert_eq!(materials[0].opacity, 0.5);
+    assert_eq!(
+        materials[0].diffuse_texture.as_deref(),
+        Some(dir.join("red.png").display().to_string().as_str())
+    );
+    assert_eq!(materials[1].opacity, 0.75);
+    assert!(materials[1].diffuse_texture.is_none());
+    assert_eq!(
+        materials[1].normal_texture.as_deref(),
+        Some(dir.join("glass_n.png").display().to_string().as_str())
+    );
+}
+
+#[test]
+fn mtl_values_need_newmtl() {
+    assert!(parse_mtl("Kd 1 0 0\n", std::path::Path::new("")).is_err());
+}
+
+#[test]
+fn mtllib_is_read_relative_to_the_obj() {
+    let dir: std::path::PathBuf = [env!("CARGO_TARGET_TMPDIR"), "obj_mtllib"].iter().collect();
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("quad.mtl"), "newmtl blue\nKd 0 0 1\n").unwrap();
+    let mesh = parse(
+        &format!("mtllib quad.mtl\n{}usemtl blue\nf 1 2 3\n", QUAD),
+        &dir,
+    )
+    .unwrap();
+    assert_eq!(mesh.materials.len(), 1);
+    assert_eq!(mesh.materials[0].diffuse, nalgebra_glm::vec3(0.0, 0.0, 1.0));
+    assert_eq!(mesh.groups[0].material, Some(0));
+}
+
+#[test]
+fn missing_mtllib_falls_back_to_default_materials() {
+    let dir: std::path::PathBuf = [env!("CARGO_TARGET_TMPDIR"), "obj_missing_mtllib"]
+        .iter()
+        .collect();
+    std::fs::create_dir_all(&dir).unwrap();
+    let _ = std::fs::remove_file(dir.join("missing.mtl"));
+    let mesh = parse(
+        &format!("mtllib missing.mtl\n{}usemtl blue\nf 1 2 3\n", QUAD),
+        &dir,
+    )
+    .unwrap();
+    assert_eq!(mesh.indices.len(), 3);
+    assert_eq!(mesh.materials.len(), 1);
+    assert_eq!(mesh.materials[0].name, "blue");
+    assert_eq!(
+        mesh.materials[0].diffuse,
+        vk_sample_common::mesh::Material::default().diffuse
+    );
+    assert_eq!(mesh.groups[0].material, Some(0));
+}
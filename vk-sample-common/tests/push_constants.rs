@@ -0,0 +1,91 @@
+//  shaders/ の SPIR-V の push_constant ブロックが PushConstants の配置と一致する事を確かめる
+//  シェーダを変更して compile.sh を実行し忘れた場合や、片方だけ変更した場合に失敗する
+use vk_sample_common::PushConstants;
+
+const OP_NAME: u32 = 5;
+const OP_MEMBER_DECORATE: u32 = 72;
+const DECORATION_OFFSET: u32 = 35;
+
+fn read_spirv(name: &str) -> Vec<u32> {
+    let path: std::path::PathBuf = [env!("CARGO_MANIFEST_DIR"), "..", "shaders", name]
+        .iter()
+        .collect();
+    let bytes = std::fs::read(&path).expect("SPIR-V を読めない");
+    assert_eq!(bytes.len() % 4, 0);
+    let words = bytes
+        .chunks_exact(4)
+        .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+        .collect::<Vec<_>>();
+    assert_eq!(words[0], 0x07230203, "{} は SPIR-V ではない", name);
+    words
+}
+
+fn literal_string(words: &[u32]) -> String {
+    let bytes = words
+        .iter()
+        .flat_map(|word| word.to_le_bytes().to_vec())
+        .take_while(|byte| *byte != 0)
+        .collect::<Vec<_>>();
+    String::from_utf8(bytes).unwrap()
+}
+
+//  type_name という名前の構造体のメンバーのオフセットをメンバーの順に返す
+fn member_offsets(words: &[u32], type_name: &str) -> Vec<u32> {
+    let mut type_id = None;
+    //  構造体の ID 、メンバーの番号、オフセット
+    let mut decorations = Vec::<(u32, u32, u32)>::new();
+    let mut i = 5;
+    while i < words.len() {
+        let count = (words[i] >> 16) as usize;
+        let opcode = words[i] & 0xffff;
+        let operands = &words[i + 1..i + count];
+        if opcode == OP_NAME && literal_string(&operands[1..]) == type_name {
+            type_id = Some(operands[0]);
+        }
+        if opcode == OP_MEMBER_DECORATE && operands[2] == DECORATION_OFFSET {
+            decorations.push((operands[0], operands[1], operands[3]));
+        }
+        i += count;
+    }
+    let type_id = type_id.expect("構造体が見つからない");
+    let mut members = decorations
+        .into_iter()
+        .filter(|(id, _, _)| *id == type_id)
+        .map(|(_, member, offset)| (member, offset))
+        .collect::<Vec<_>>();
+    members.sort();
+    members.into_iter().map(|(_, offset)| offset).collect()
+}
+
+fn rust_offsets() -> Vec<u32> {
+    let push_constants = PushConstants::new(
+        nalgebra_glm::identity(),
+        nalgebra_glm::identity(),
+        nalgebra_glm::vec3(0.0, 0.0, 0.0),
+        nalgebra_glm::vec3(0.0, 0.0, 0.0),
+    );
+    let base = &push_constants as *const PushConstants as usize;
+    vec![
+        &push_constants.world_matrix as *const _ as usize - base,
+        &push_constants.projection_matrix as *const _ as usize - base,
+        &push_constants.eye as *const _ as usize - base,
+        &push_constants.lightpos as *const _ as usize - base,
+    ]
+    .into_iter()
+    .map(|offset| offset as u32)
+    .collect()
+}
+
+#[test]
+fn fragment_shader_matches_push_constants() {
+    let offsets = member_offsets(&read_spirv("simple.frag.spv"), "PushConstants");
+    assert_eq!(offsets, rust_offsets());
+    //  最後の vec3 が PushConstants に収まっている
+    assert!(offsets[3] as usize + 12 <= std::mem::size_of::<PushConstants>());
+}
+
+#[test]
+fn vertex_shader_matches_push_constants() {
+    let offsets = member_offsets(&read_spirv("simple.vert.spv"), "PushConstants");
+    assert_eq!(offsets, rust_offsets()[..2].to_vec());
+}
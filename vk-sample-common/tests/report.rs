@@ -0,0 +1,165 @@
+//  report::diff_reports と format_differences を 02_list_devices --format json と同じ形のレポートで確かめる
+use serde_json::json;
+use vk_sample_common::report::{
+    diff_reports, format_differences, Difference, RENDERER_EXTENSIONS, RENDERER_FEATURES,
+    RENDERER_LIMITS,
+};
+
+fn device() -> serde_json::Value {
+    json!({
+        "name": "test device",
+        "extensions": { "VK_KHR_swapchain": 70, "VK_KHR_maintenance1": 2 },
+        "features": { "sampler_anisotropy": true, "wide_lines": false },
+        "limits": { "max_push_constants_size": 128, "timestamp_period": 1.0 },
+        "memory_types": [
+            { "heap_index": 0, "property_flags": ["DEVICE_LOCAL"] },
+            { "heap_index": 1, "property_flags": ["HOST_VISIBLE", "HOST_COHERENT"] },
+        ],
+        "formats": {
+            "R8G8B8A8_UNORM": {
+                "linear_tiling": ["SAMPLED_IMAGE"],
+                "optimal_tiling": ["SAMPLED_IMAGE", "COLOR_ATTACHMENT"],
+                "buffer": [],
+            },
+        },
+    })
+}
+
+fn summary(differences: &[Difference]) -> Vec<(&str, &str, Option<String>, Option<String>)> {
+    differences
+        .iter()
+        .map(|d| {
+            (
+                d.section,
+                d.name.as_str(),
+                d.left.as_ref().map(|v| v.to_string()),
+                d.right.as_ref().map(|v| v.to_string()),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn identical_reports_have_no_differences() {
+    assert!(diff_reports(&device(), &device(), false).is_empty());
+    assert!(diff_reports(&device(), &device(), true).is_empty());
+}
+
+#[test]
+fn added_removed_and_changed_keys() {
+    let left = device();
+    let mut right = device();
+    right["extensions"]["VK_EXT_memory_budget"] = json!(1);
+    right["features"]
+        .as_object_mut()
+        .unwrap()
+        .remove("wide_lines");
+    right["limits"]["max_push_constants_size"] = json!(256);
+    let differences = diff_reports(&left, &right, false);
+
+    assert_eq!(
+        summary(&differences),
+        vec![
+            (
+                "extensions",
+                "VK_EXT_memory_budget",
+                None,
+                Some(String::from("1"))
+            ),
+            ("features", "wide_lines", Some(String::from("false")), None),
+            (
+                "limits",
+                "max_push_constants_size",
+                Some(String::from("128")),
+                Some(String::from("256"))
+            ),
+        ]
+    );
+    assert_eq!(
+        format_differences(&differences),
+        "拡張\n  + VK_EXT_memory_budget: 1\n機能\n  - wide_lines: false\n制限\n  ~ max_push_constants_size: 128 -> 256\n"
+    );
+}
+
+#[test]
+fn only_renderer_keys_are_compared_without_all() {
+    let left = device();
+    let mut right = device();
+    right["extensions"]["VK_EXT_unrelated"] = json!(1);
+    right["features"]["geometry_shader"] = json!(true);
+    right["limits"]["max_viewports"] = json!(16);
+    assert!(!RENDERER_EXTENSIONS.contains(&"VK_EXT_unrelated"));
+    assert!(!RENDERER_FEATURES.contains(&"geometry_shader"));
+    assert!(!RENDERER_LIMITS.contains(&"max_viewports"));
+
+    assert!(diff_reports(&left, &right, false).is_empty());
+    let names = diff_reports(&left, &right, true)
+        .iter()
+        .map(|d| d.name.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        vec!["VK_EXT_unrelated", "geometry_shader", "max_viewports"]
+    );
+}
+
+#[test]
+fn memory_types_are_compared_by_property_flags() {
+    let left = device();
+    //  順番だけが違う場合は同じとみなす
+    let mut reordered = device();
+    reordered["memory_types"].as_array_mut().unwrap().reverse();
+    assert!(diff_reports(&left, &reordered, false).is_empty());
+
+    let mut right = device();
+    right["memory_types"]
+        .as_array_mut()
+        .unwrap()
+        .push(json!({ "heap_index": 0, "property_flags": ["DEVICE_LOCAL"] }));
+    right["memory_types"]
+        .as_array_mut()
+        .unwrap()
+        .push(json!({ "heap_index": 0, "property_flags": [] }));
+    assert_eq!(
+        summary(&diff_reports(&left, &right, false)),
+        vec![
+            (
+                "memory_types",
+                "DEVICE_LOCAL",
+                Some(String::from("1")),
+                Some(String::from("2"))
+            ),
+            ("memory_types", "なし", None, Some(String::from("1"))),
+        ]
+    );
+}
+
+#[test]
+fn format_features_are_compared_per_tiling() {
+    let left = device();
+    let mut right = device();
+    right["formats"]["R8G8B8A8_UNORM"]["optimal_tiling"] = json!(["SAMPLED_IMAGE"]);
+    right["formats"]["R8G8B8A8_UNORM"]["buffer"] = json!(["VERTEX_BUFFER"]);
+    let differences = diff_reports(&left, &right, false);
+    assert_eq!(
+        summary(&differences),
+        vec![
+            (
+                "formats",
+                "R8G8B8A8_UNORM buffer VERTEX_BUFFER",
+                None,
+                Some(String::from("true"))
+            ),
+            (
+                "formats",
+                "R8G8B8A8_UNORM optimal_tiling COLOR_ATTACHMENT",
+                Some(String::from("true")),
+                None
+            ),
+        ]
+    );
+    assert_eq!(
+        format_differences(&differences),
+        "フォーマット\n  + R8G8B8A8_UNORM buffer VERTEX_BUFFER: true\n  - R8G8B8A8_UNORM optimal_tiling COLOR_ATTACHMENT: true\n"
+    );
+}
@@ -0,0 +1,434 @@
+//  resource の型が Drop で正しい関数を正しい順に呼ぶ事を確かめる
+//  Vulkan の関数を呼び出しを記録するだけの関数に差し替えた ash::Instance と ash::Device を使う
+use ash::version::InstanceV1_0;
+use ash::vk::Handle;
+use std::cell::{Cell, RefCell};
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use vk_sample_common::allocator;
+use vk_sample_common::resource;
+
+thread_local! {
+    static CALLS: RefCell<Vec<String>> = RefCell::new(vec![]);
+    //  vkGetBufferMemoryRequirements2 が専用のメモリを求めるかどうか
+    static REQUIRES_DEDICATED: Cell<bool> = Cell::new(false);
+}
+
+fn record(call: String) {
+    CALLS.with(|calls| calls.borrow_mut().push(call));
+}
+
+fn take_calls() -> Vec<String> {
+    CALLS.with(|calls| std::mem::take(&mut *calls.borrow_mut()))
+}
+
+extern "system" fn destroy_fence(
+    _device: ash::vk::Device,
+    fence: ash::vk::Fence,
+    _allocator: *const ash::vk::AllocationCallbacks,
+) {
+    record(format!("vkDestroyFence {}", fence.as_raw()));
+}
+
+extern "system" fn destroy_swapchain(
+    _device: ash::vk::Device,
+    swapchain: ash::vk::SwapchainKHR,
+    _allocator: *const ash::vk::AllocationCallbacks,
+) {
+    record(format!("vkDestroySwapchainKHR {}", swapchain.as_raw()));
+}
+
+extern "system" fn allocate_command_buffers(
+    _device: ash::vk::Device,
+    info: *const ash::vk::CommandBufferAllocateInfo,
+    buffers: *mut ash::vk::CommandBuffer,
+) -> ash::vk::Result {
+    let info = unsafe { &*info };
+    for i in 0..info.command_buffer_count {
+        unsafe { *buffers.add(i as usize) = ash::vk::CommandBuffer::from_raw(100 + i as u64) };
+    }
+    record(format!(
+        "vkAllocateCommandBuffers {} {}",
+        info.command_pool.as_raw(),
+        info.command_buffer_count
+    ));
+    ash::vk::Result::SUCCESS
+}
+
+extern "system" fn free_command_buffers(
+    _device: ash::vk::Device,
+    command_pool: ash::vk::CommandPool,
+    count: u32,
+    buffers: *const ash::vk::CommandBuffer,
+) {
+    let buffers = unsafe { std::slice::from_raw_parts(buffers, count as usize) };
+    let buffers: Vec<String> = buffers.iter().map(|b| b.as_raw().to_string()).collect();
+    record(format!(
+        "vkFreeCommandBuffers {} {}",
+        command_pool.as_raw(),
+        buffers.join(",")
+    ));
+}
+
+extern "system" fn get_physical_device_memory_properties(
+    _physical_device: ash::vk::PhysicalDevice,
+    properties: *mut ash::vk::PhysicalDeviceMemoryProperties,
+) {
+    let mut memory_properties = ash::vk::PhysicalDeviceMemoryProperties::default();
+    memory_properties.memory_type_count = 2;
+    memory_properties.memory_types[0] = ash::vk::MemoryType {
+        property_flags: ash::vk::MemoryPropertyFlags::DEVICE_LOCAL
+            | ash::vk::MemoryPropertyFlags::HOST_VISIBLE
+            | ash::vk::MemoryPropertyFlags::HOST_COHERENT,
+        heap_index: 0,
+    };
+    memory_properties.memory_types[1] = ash::vk::MemoryType {
+        property_flags: ash::vk::MemoryPropertyFlags::HOST_VISIBLE,
+        heap_index: 0,
+    };
+    memory_properties.memory_heap_count = 1;
+    memory_properties.memory_heaps[0] = ash::vk::MemoryHeap {
+        size: 256 << 20,
+        flags: ash::vk::MemoryHeapFlags::DEVICE_LOCAL,
+    };
+    unsafe { *properties = memory_properties };
+}
+
+extern "system" fn get_physical_device_properties(
+    _physical_device: ash::vk::PhysicalDevice,
+    properties: *mut ash::vk::PhysicalDeviceProperties,
+) {
+    let mut device_properties = ash::vk::PhysicalDeviceProperties::default();
+    device_properties.api_version = ash::vk::make_version(1, 1, 0);
+    device_properties.limits.buffer_image_granularity = 1024;
+    device_properties.limits.non_coherent_atom_size = 64;
+    unsafe { *properties = device_properties };
+}
+
+extern "system" fn create_buffer(
+    _device: ash::vk::Device,
+    _info: *const ash::vk::BufferCreateInfo,
+    _allocator: *const ash::vk::AllocationCallbacks,
+    buffer: *mut ash::vk::Buffer,
+) -> ash::vk::Result {
+    unsafe { *buffer = ash::vk::Buffer::from_raw(7) };
+    record("vkCreateBuffer 7".to_string());
+    ash::vk::Result::SUCCESS
+}
+
+extern "system" fn destroy_buffer(
+    _device: ash::vk::Device,
+    buffer: ash::vk::Buffer,
+    _allocator: *const ash::vk::AllocationCallbacks,
+) {
+    record(format!("vkDestroyBuffer {}", buffer.as_raw()));
+}
+
+extern "system" fn get_buffer_memory_requirements(
+    _device: ash::vk::Device,
+    _buffer: ash::vk::Buffer,
+    requirements: *mut ash::vk::MemoryRequirements,
+) {
+    unsafe {
+        *requirements = ash::vk::MemoryRequirements {
+            size: 256,
+            alignment: 16,
+            memory_type_bits: 1,
+        }
+    };
+}
+
+extern "system" fn get_buffer_memory_requirements2(
+    device: ash::vk::Device,
+    info: *const ash::vk::BufferMemoryRequirementsInfo2,
+    requirements: *mut ash::vk::MemoryRequirements2,
+) {
+    let requirements = unsafe { &mut *requirements };
+    get_buffer_memory_requirements(
+        device,
+        unsafe { (*info).buffer },
+        &mut requirements.memory_requirements,
+    );
+    let dedicated = requirements.p_next as *mut ash::vk::MemoryDedicatedRequirements;
+    if !dedicated.is_null() {
+        let required = REQUIRES_DEDICATED.with(|required| required.get());
+        unsafe {
+            (*dedicated).requires_dedicated_allocation = required as ash::vk::Bool32;
+            (*dedicated).prefers_dedicated_allocation = required as ash::vk::Bool32;
+        }
+    }
+}
+
+extern "system" fn allocate_memory(
+    _device: ash::vk::Device,
+    info: *const ash::vk::MemoryAllocateInfo,
+    _allocator: *const ash::vk::AllocationCallbacks,
+    memory: *mut ash::vk::DeviceMemory,
+) -> ash::vk::Result {
+    let info = unsafe { &*info };
+    unsafe { *memory = ash::vk::DeviceMemory::from_raw(9) };
+    let dedicated = info.p_next as *const ash::vk::MemoryDedicatedAllocateInfo;
+    if dedicated.is_null() {
+        record(format!("vkAllocateMemory {}", info.allocation_size));
+    } else {
+        record(format!(
+            "vkAllocateMemory {} dedicated {}",
+            info.allocation_size,
+            unsafe { (*dedicated).buffer }.as_raw()
+        ));
+    }
+    ash::vk::Result::SUCCESS
+}
+
+extern "system" fn free_memory(
+    _device: ash::vk::Device,
+    memory: ash::vk::DeviceMemory,
+    _allocator: *const ash::vk::AllocationCallbacks,
+) {
+    record(format!("vkFreeMemory {}", memory.as_raw()));
+}
+
+extern "system" fn bind_buffer_memory(
+    _device: ash::vk::Device,
+    buffer: ash::vk::Buffer,
+    memory: ash::vk::DeviceMemory,
+    offset: ash::vk::DeviceSize,
+) -> ash::vk::Result {
+    record(format!(
+        "vkBindBufferMemory {} {} {}",
+        buffer.as_raw(),
+        memory.as_raw(),
+        offset
+    ));
+    ash::vk::Result::SUCCESS
+}
+
+//  ここに無い関数は ash が呼び出すと panic する関数のままになる
+extern "system" fn get_proc_addr(
+    _instance: u64,
+    name: *const c_char,
+) -> ash::vk::PFN_vkVoidFunction {
+    let name = unsafe { CStr::from_ptr(name) }.to_bytes();
+    let f: *const () = match name {
+        b"vkGetDeviceProcAddr" => get_proc_addr as *const (),
+        b"vkDestroyFence" => destroy_fence as *const (),
+        b"vkDestroySwapchainKHR" => destroy_swapchain as *const (),
+        b"vkAllocateCommandBuffers" => allocate_command_buffers as *const (),
+        b"vkFreeCommandBuffers" => free_command_buffers as *const (),
+        b"vkGetPhysicalDeviceMemoryProperties" => {
+            get_physical_device_memory_properties as *const ()
+        }
+        b"vkGetPhysicalDeviceProperties" => get_physical_device_properties as *const (),
+        b"vkCreateBuffer" => create_buffer as *const (),
+        b"vkDestroyBuffer" => destroy_buffer as *const (),
+        b"vkGetBufferMemoryRequirements" => get_buffer_memory_requirements as *const (),
+        b"vkGetBufferMemoryRequirements2" => get_buffer_memory_requirements2 as *const (),
+        b"vkAllocateMemory" => allocate_memory as *const (),
+        b"vkFreeMemory" => free_memory as *const (),
+        b"vkBindBufferMemory" => bind_buffer_memory as *const (),
+        _ => return None,
+    };
+    Some(unsafe { std::mem::transmute(f) })
+}
+
+fn fake_instance() -> ash::Instance {
+    let get_instance_proc_addr: extern "system" fn(
+        ash::vk::Instance,
+        *const c_char,
+    ) -> ash::vk::PFN_vkVoidFunction = unsafe { std::mem::transmute(get_proc_addr as *const ()) };
+    unsafe {
+        ash::Instance::load(
+            &ash::vk::StaticFn {
+                get_instance_proc_addr: get_instance_proc_addr,
+            },
+            ash::vk::Instance::from_raw(1),
+        )
+    }
+}
+
+fn fake_device(instance: &ash::Instance) -> ash::Device {
+    unsafe { ash::Device::load(instance.fp_v1_0(), ash::vk::Device::from_raw(2)) }
+}
+
+#[test]
+fn owned_destroys_the_handle_on_drop() {
+    let instance = fake_instance();
+    let device = fake_device(&instance);
+    {
+        let fence = resource::Owned::new(&device, ash::vk::Fence::from_raw(5));
+        assert_eq!(*fence, ash::vk::Fence::from_raw(5));
+        assert!(take_calls().is_empty());
+    }
+    assert_eq!(take_calls(), vec!["vkDestroyFence 5"]);
+}
+
+#[test]
+fn into_raw_gives_up_ownership() {
+    let instance = fake_instance();
+    let device = fake_device(&instance);
+    let fence = resource::Owned::new(&device, ash::vk::Fence::from_raw(5));
+    assert_eq!(fence.into_raw(), ash::vk::Fence::from_raw(5));
+    assert!(take_calls().is_empty());
+}
+
+#[test]
+fn locals_are_destroyed_in_reverse_order() {
+    let instance = fake_instance();
+    let device = fake_device(&instance);
+    {
+        let _first = resource::Owned::new(&device, ash::vk::Fence::from_raw(1));
+        let _second = resource::Owned::new(&device, ash::vk::Fence::from_raw(2));
+    }
+    assert_eq!(take_calls(), vec!["vkDestroyFence 2", "vkDestroyFence 1"]);
+}
+
+#[test]
+fn swapchain_is_destroyed_with_the_loader() {
+    let instance = fake_instance();
+    let device = fake_device(&instance);
+    let loader = ash::extensions::khr::Swapchain::new(&instance, &device);
+    {
+        let swapchain = resource::Swapchain::new(&loader, ash::vk::SwapchainKHR::from_raw(3));
+        assert_eq!(swapchain.handle(), ash::vk::SwapchainKHR::from_raw(3));
+        assert!(take_calls().is_empty());
+    }
+    assert_eq!(take_calls(), vec!["vkDestroySwapchainKHR 3"]);
+}
+
+#[test]
+fn command_buffers_are_returned_to_the_pool() {
+    let instance = fake_instance();
+    let device = fake_device(&instance);
+    let pool = ash::vk::CommandPool::from_raw(4);
+    {
+        let buffers = resource::CommandBuffers::new(&device, pool, 3).unwrap();
+        assert_eq!(
+            &buffers[..],
+            &[
+                ash::vk::CommandBuffer::from_raw(100),
+                ash::vk::CommandBuffer::from_raw(101),
+                ash::vk::CommandBuffer::from_raw(102),
+            ]
+        );
+        assert_eq!(take_calls(), vec!["vkAllocateCommandBuffers 4 3"]);
+    }
+    assert_eq!(take_calls(), vec!["vkFreeCommandBuffers 4 100,101,102"]);
+}
+
+#[test]
+fn no_command_buffers_calls_nothing() {
+    let instance = fake_instance();
+    let device = fake_device(&instance);
+    {
+        let buffers =
+            resource::CommandBuffers::new(&device, ash::vk::CommandPool::from_raw(4), 0).unwrap();
+        assert!(buffers.is_empty());
+    }
+    assert!(take_calls().is_empty());
+}
+
+#[test]
+fn bound_buffer_is_destroyed_before_its_memory() {
+    let instance = fake_instance();
+    let device = fake_device(&instance);
+    let allocator = allocator::Allocator::new(
+        &instance,
+        ash::vk::PhysicalDevice::from_raw(6),
+        &device,
+        &allocator::AllocatorCreateInfo::default(),
+    );
+    {
+        let buffer = resource::BoundBuffer::new(
+            &allocator,
+            &ash::vk::BufferCreateInfo::builder()
+                .size(256)
+                .usage(ash::vk::BufferUsageFlags::UNIFORM_BUFFER)
+                .build(),
+            &allocator::AllocationCreateInfo {
+                dedicated: true,
+                ..allocator::AllocationCreateInfo::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(*buffer, ash::vk::Buffer::from_raw(7));
+        assert_eq!(
+            buffer.allocation().memory,
+            ash::vk::DeviceMemory::from_raw(9)
+        );
+        assert_eq!(
+            take_calls(),
+            vec![
+                "vkCreateBuffer 7",
+                "vkAllocateMemory 256",
+                "vkBindBufferMemory 7 9 0"
+            ]
+        );
+    }
+    assert_eq!(take_calls(), vec!["vkDestroyBuffer 7", "vkFreeMemory 9"]);
+}
+
+#[test]
+fn required_dedicated_allocation_names_the_buffer() {
+    let instance = fake_instance();
+    let device = fake_device(&instance);
+    let allocator = allocator::Allocator::new(
+        &instance,
+        ash::vk::PhysicalDevice::from_raw(6),
+        &device,
+        &allocator::AllocatorCreateInfo::default(),
+    );
+    REQUIRES_DEDICATED.with(|required| required.set(true));
+    let (buffer, allocation) = allocator
+        .create_buffer(
+            &ash::vk::BufferCreateInfo::builder()
+                .size(256)
+                .usage(ash::vk::BufferUsageFlags::UNIFORM_BUFFER)
+                .build(),
+            &allocator::AllocationCreateInfo::default(),
+        )
+        .unwrap();
+    REQUIRES_DEDICATED.with(|required| required.set(false));
+    assert_eq!(
+        take_calls(),
+        vec![
+            "vkCreateBuffer 7",
+            "vkAllocateMemory 256 dedicated 7",
+            "vkBindBufferMemory 7 9 0"
+        ]
+    );
+    allocator.destroy_buffer(buffer, &allocation);
+    assert_eq!(take_calls(), vec!["vkDestroyBuffer 7", "vkFreeMemory 9"]);
+}
+
+#[test]
+fn non_coherent_allocations_do_not_share_an_atom() {
+    let instance = fake_instance();
+    let device = fake_device(&instance);
+    let allocator = allocator::Allocator::new(
+        &instance,
+        ash::vk::PhysicalDevice::from_raw(6),
+        &device,
+        &allocator::AllocatorCreateInfo::default(),
+    );
+    let requirements = ash::vk::MemoryRequirements {
+        size: 100,
+        alignment: 4,
+        memory_type_bits: 0b10,
+    };
+    let info = allocator::AllocationCreateInfo {
+        required_flags: ash::vk::MemoryPropertyFlags::HOST_VISIBLE,
+        ..allocator::AllocationCreateInfo::default()
+    };
+    let first = allocator
+        .allocate(&requirements, &info, allocator::ResourceKind::Linear)
+        .unwrap();
+    let second = allocator
+        .allocate(&requirements, &info, allocator::ResourceKind::Linear)
+        .unwrap();
+    assert_eq!(first.memory_type_index, 1);
+    assert_eq!(first.offset, 0);
+    assert_eq!(second.offset, 128);
+    allocator.free(&second);
+    allocator.free(&first);
+    take_calls();
+}
@@ -0,0 +1,184 @@
+//  selection のスコア、条件の判定、デバイスの選び方を Vulkan を使わずに確かめる
+use vk_sample_common::selection::{
+    enabled_features, score, select_device, unmet_requirements, Candidate, DeviceInfo,
+    FormatRequirement, Requirements,
+};
+
+const GIB: u64 = 1024 * 1024 * 1024;
+
+fn device(
+    device_type: ash::vk::PhysicalDeviceType,
+    heaps: &[(u64, ash::vk::MemoryHeapFlags)],
+) -> DeviceInfo {
+    let mut info = DeviceInfo::default();
+    info.properties.device_type = device_type;
+    info.memory.memory_heap_count = heaps.len() as u32;
+    for (heap, (size, flags)) in info.memory.memory_heaps.iter_mut().zip(heaps) {
+        heap.size = *size;
+        heap.flags = *flags;
+    }
+    info.queue_families.push(ash::vk::QueueFamilyProperties {
+        queue_flags: ash::vk::QueueFlags::GRAPHICS | ash::vk::QueueFlags::TRANSFER,
+        queue_count: 1,
+        ..Default::default()
+    });
+    info
+}
+
+fn discrete(local_gib: u64) -> DeviceInfo {
+    device(
+        ash::vk::PhysicalDeviceType::DISCRETE_GPU,
+        &[(local_gib * GIB, ash::vk::MemoryHeapFlags::DEVICE_LOCAL)],
+    )
+}
+
+fn candidate(name: &str, result: Result<u64, Vec<String>>) -> Candidate {
+    Candidate {
+        device: ash::vk::PhysicalDevice::null(),
+        name: String::from(name),
+        device_type: ash::vk::PhysicalDeviceType::DISCRETE_GPU,
+        result: result,
+    }
+}
+
+#[test]
+fn device_type_outweighs_memory() {
+    let integrated = device(
+        ash::vk::PhysicalDeviceType::INTEGRATED_GPU,
+        &[
+            (64 * GIB, ash::vk::MemoryHeapFlags::DEVICE_LOCAL),
+            (128 * GIB, ash::vk::MemoryHeapFlags::empty()),
+        ],
+    );
+    assert_eq!(score(&integrated), 164);
+    assert_eq!(score(&discrete(8)), 1008);
+    assert!(score(&discrete(0)) > score(&integrated));
+    //  memory_heap_count より後ろのヒープは数えない
+    let mut info = discrete(2);
+    info.memory.memory_heaps[1].size = 100 * GIB;
+    info.memory.memory_heaps[1].flags = ash::vk::MemoryHeapFlags::DEVICE_LOCAL;
+    assert_eq!(score(&info), 1002);
+}
+
+#[test]
+fn satisfied_requirements_have_no_reasons() {
+    let mut info = discrete(4);
+    info.extensions
+        .push(ash::extensions::khr::Swapchain::name().to_owned());
+    info.features.sampler_anisotropy = ash::vk::TRUE;
+    info.properties.limits.max_push_constants_size = 256;
+    info.presentable = Some(vec![true]);
+    info.formats.push((
+        ash::vk::Format::R8G8B8A8_UNORM,
+        ash::vk::FormatProperties {
+            optimal_tiling_features: ash::vk::FormatFeatureFlags::COLOR_ATTACHMENT
+                | ash::vk::FormatFeatureFlags::SAMPLED_IMAGE,
+            ..Default::default()
+        },
+    ));
+    let mut requirements = Requirements::new(&[ash::extensions::khr::Swapchain::name()]);
+    requirements
+        .features
+        .push(String::from("sampler_anisotropy"));
+    requirements
+        .min_limits
+        .push((String::from("max_push_constants_size"), 128.0));
+    requirements.formats.push(FormatRequirement {
+        format: ash::vk::Format::R8G8B8A8_UNORM,
+        tiling: ash::vk::ImageTiling::OPTIMAL,
+        features: ash::vk::FormatFeatureFlags::COLOR_ATTACHMENT,
+    });
+    assert!(unmet_requirements(&info, &requirements).is_empty());
+}
+
+#[test]
+fn every_unmet_requirement_is_reported() {
+    let mut info = discrete(4);
+    info.presentable = Some(vec![false]);
+    info.properties.limits.max_push_constants_size = 64;
+    let mut requirements = Requirements::new(&[ash::extensions::khr::Swapchain::name()]);
+    requirements.queue_flags |= ash::vk::QueueFlags::COMPUTE;
+    requirements.features.push(String::from("wide_lines"));
+    requirements.features.push(String::from("no_such_feature"));
+    requirements
+        .min_limits
+        .push((String::from("max_push_constants_size"), 128.0));
+    requirements
+        .min_limits
+        .push((String::from("no_such_limit"), 1.0));
+    requirements.formats.push(FormatRequirement {
+        format: ash::vk::Format::D32_SFLOAT,
+        tiling: ash::vk::ImageTiling::OPTIMAL,
+        features: ash::vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
+    });
+    let reasons = unmet_requirements(&info, &requirements);
+
+    assert_eq!(reasons.len(), 8, "{:?}", reasons);
+    assert_eq!(reasons[0], "拡張 VK_KHR_swapchain が無い");
+    assert_eq!(reasons[1], "機能 wide_lines が無い");
+    assert_eq!(reasons[2], "no_such_feature という機能は無い");
+    assert!(
+        reasons[3].ends_with("を備えたキューが無い"),
+        "{}",
+        reasons[3]
+    );
+    assert_eq!(reasons[4], "表示できるキューが無い");
+    assert!(reasons[5].starts_with("D32_SFLOAT の OPTIMAL タイリングで"));
+    assert_eq!(reasons[6], "制限 max_push_constants_size が 64 で 128 未満");
+    assert_eq!(reasons[7], "no_such_limit という数値の制限は無い");
+}
+
+#[test]
+fn only_required_features_are_enabled() {
+    let mut requirements = Requirements::new(&[]);
+    requirements
+        .features
+        .push(String::from("sampler_anisotropy"));
+    requirements.features.push(String::from("wide_lines"));
+    let features = enabled_features(&requirements).unwrap();
+
+    assert_eq!(features.sampler_anisotropy, ash::vk::TRUE);
+    assert_eq!(features.wide_lines, ash::vk::TRUE);
+    assert_eq!(features.robust_buffer_access, ash::vk::FALSE);
+    assert_eq!(features.geometry_shader, ash::vk::FALSE);
+
+    requirements.features.push(String::from("no_such_feature"));
+    assert!(enabled_features(&requirements).is_err());
+}
+
+#[test]
+fn explicit_index_must_satisfy_the_requirements() {
+    let candidates = vec![
+        candidate(
+            "rejected",
+            Err(vec![String::from("拡張 VK_KHR_swapchain が無い")]),
+        ),
+        candidate("accepted", Ok(100)),
+    ];
+    assert_eq!(select_device(&candidates, Some(1)).unwrap(), 1);
+
+    let error = select_device(&candidates, Some(0)).unwrap_err().to_string();
+    assert!(error.contains("rejected"), "{}", error);
+    assert!(error.contains("拡張 VK_KHR_swapchain が無い"), "{}", error);
+
+    let error = select_device(&candidates, Some(2)).unwrap_err().to_string();
+    assert_eq!(error, "2番目のデバイスは存在しない");
+}
+
+#[test]
+fn highest_score_wins_and_ties_keep_the_first() {
+    let candidates = vec![
+        candidate("integrated", Ok(108)),
+        candidate(
+            "rejected",
+            Err(vec![String::from("表示できるキューが無い")]),
+        ),
+        candidate("discrete", Ok(1008)),
+        candidate("same discrete", Ok(1008)),
+    ];
+    assert_eq!(select_device(&candidates, None).unwrap(), 2);
+
+    let rejected = vec![candidate("rejected", Err(vec![String::new()]))];
+    assert!(select_device(&rejected, None).is_err());
+    assert!(select_device(&[], None).is_err());
+}
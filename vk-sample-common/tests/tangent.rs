@@ -0,0 +1,151 @@
+//  Mesh::generate_tangents の接線と従法線の向きを、テクスチャ座標が分かっている四角形で確かめる
+use vk_sample_common::mesh::Mesh;
+use vk_sample_common::Vertex;
+
+fn vertex(x: f32, y: f32, u: f32, v: f32) -> Vertex {
+    let mut vertex = Vertex::default();
+    vertex.position = nalgebra_glm::vec3(x, y, 0.0);
+    vertex.normal = nalgebra_glm::vec3(0.0, 0.0, 1.0);
+    vertex.texcoord = nalgebra_glm::vec2(u, v);
+    vertex
+}
+
+fn assert_close(actual: nalgebra_glm::Vec4, expected: [f32; 4]) {
+    let expected = nalgebra_glm::vec4(expected[0], expected[1], expected[2], expected[3]);
+    assert!(
+        nalgebra_glm::distance(&actual, &expected) < 1e-5,
+        "{:?} != {:?}",
+        actual,
+        expected
+    );
+}
+
+//  w * cross(normal, tangent) が v の増える向きになっている
+fn assert_bitangent_follows_v(mesh: &Mesh) {
+    for v in mesh.vertices.iter() {
+        let tangent = v.tangent;
+        let normal = v.normal;
+        let bitangent = nalgebra_glm::cross(&normal, &tangent.xyz()) * tangent.w;
+        assert!(
+            nalgebra_glm::distance(&bitangent, &nalgebra_glm::vec3(0.0, 1.0, 0.0)) < 1e-5,
+            "{:?}",
+            bitangent
+        );
+    }
+}
+
+#[test]
+fn planar_quad() {
+    let mut mesh = Mesh {
+        vertices: vec![
+            vertex(0.0, 0.0, 0.0, 0.0),
+            vertex(1.0, 0.0, 1.0, 0.0),
+            vertex(1.0, 1.0, 1.0, 1.0),
+            vertex(0.0, 1.0, 0.0, 1.0),
+        ],
+        indices: vec![0, 1, 2, 0, 2, 3],
+        ..Mesh::default()
+    };
+    mesh.generate_tangents();
+    assert_eq!(mesh.vertices.len(), 4);
+    for v in mesh.vertices.iter() {
+        assert_close(v.tangent, [1.0, 0.0, 0.0, 1.0]);
+    }
+    assert_bitangent_follows_v(&mesh);
+}
+
+#[test]
+fn mirrored_quad() {
+    //  u が -X に向かって増えるので、接線は -X で従法線の向きは -1
+    let mut mesh = Mesh {
+        vertices: vec![
+            vertex(0.0, 0.0, 1.0, 0.0),
+            vertex(1.0, 0.0, 0.0, 0.0),
+            vertex(1.0, 1.0, 0.0, 1.0),
+            vertex(0.0, 1.0, 1.0, 1.0),
+        ],
+        indices: vec![0, 1, 2, 0, 2, 3],
+        ..Mesh::default()
+    };
+    mesh.generate_tangents();
+    assert_eq!(mesh.vertices.len(), 4);
+    for v in mesh.vertices.iter() {
+        assert_close(v.tangent, [-1.0, 0.0, 0.0, -1.0]);
+    }
+    assert_bitangent_follows_v(&mesh);
+}
+
+#[test]
+fn mirror_seam_splits_shared_vertices() {
+    //  u = |x| で X = 0 を境に左右対称に貼る
+    //  X = 0 の 2 頂点は両側で共有しているので、裏返っている側のために複製する
+    let mut mesh = Mesh {
+        vertices: vec![
+            vertex(-1.0, 0.0, 1.0, 0.0),
+            vertex(0.0, 0.0, 0.0, 0.0),
+            vertex(1.0, 0.0, 1.0, 0.0),
+            vertex(-1.0, 1.0, 1.0, 1.0),
+            vertex(0.0, 1.0, 0.0, 1.0),
+            vertex(1.0, 1.0, 1.0, 1.0),
+        ],
+        indices: vec![0, 1, 4, 0, 4, 3, 1, 2, 5, 1, 5, 4],
+        ..Mesh::default()
+    };
+    mesh.generate_tangents();
+    assert_eq!(mesh.vertices.len(), 8);
+    for triangle in mesh.indices.chunks_exact(3) {
+        let left = triangle.iter().any(|i| {
+            let position = mesh.vertices[*i as usize].position;
+            position.x < 0.0
+        });
+        let expected = if left {
+            [-1.0, 0.0, 0.0, -1.0]
+        } else {
+            [1.0, 0.0, 0.0, 1.0]
+        };
+        for i in triangle {
+            assert_close(mesh.vertices[*i as usize].tangent, expected);
+        }
+    }
+    assert_bitangent_follows_v(&mesh);
+}
+
+#[test]
+fn tangents_are_orthogonal_to_the_normal() {
+    //  法線が面から傾いていても接線は法線と直交させる
+    let mut mesh = Mesh {
+        vertices: vec![
+            vertex(0.0, 0.0, 0.0, 0.0),
+            vertex(1.0, 0.0, 1.0, 0.0),
+            vertex(0.0, 1.0, 0.0, 1.0),
+        ],
+        indices: vec![0, 1, 2],
+        ..Mesh::default()
+    };
+    let tilted = nalgebra_glm::normalize(&nalgebra_glm::vec3(1.0, 0.0, 1.0));
+    mesh.vertices[0].normal = tilted;
+    mesh.generate_tangents();
+    let tangent = mesh.vertices[0].tangent;
+    assert!(nalgebra_glm::dot(&tangent.xyz(), &tilted).abs() < 1e-5);
+    assert!((nalgebra_glm::length(&tangent.xyz()) - 1.0).abs() < 1e-5);
+    assert_eq!(tangent.w, 1.0);
+}
+
+#[test]
+fn degenerate_texcoords_fall_back_to_a_perpendicular_tangent() {
+    let mut mesh = Mesh {
+        vertices: vec![
+            vertex(0.0, 0.0, 0.0, 0.0),
+            vertex(1.0, 0.0, 0.0, 0.0),
+            vertex(0.0, 1.0, 0.0, 0.0),
+        ],
+        indices: vec![0, 1, 2],
+        ..Mesh::default()
+    };
+    mesh.generate_tangents();
+    for v in mesh.vertices.iter() {
+        let tangent = v.tangent;
+        assert!(nalgebra_glm::dot(&tangent.xyz(), &nalgebra_glm::vec3(0.0, 0.0, 1.0)).abs() < 1e-5);
+        assert_eq!(tangent.w, 1.0);
+    }
+}
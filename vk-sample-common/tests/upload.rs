@@ -0,0 +1,173 @@
+//  upload::StagingRing の確保と解放の順序、イメージのコピーのアラインメントを確かめる
+mod common;
+
+use common::Lcg;
+use vk_sample_common::upload::{image_copy_alignment, texel_size, StagingRing};
+
+#[test]
+fn allocations_are_sequential_and_aligned() {
+    let mut ring = StagingRing::new(1024);
+    assert_eq!(ring.allocate(10, 4), Some(0));
+    assert_eq!(ring.allocate(10, 4), Some(12));
+    assert_eq!(ring.allocate(1, 16), Some(32));
+    assert_eq!(ring.used(), 33);
+    assert_eq!(ring.allocate(0, 4), None);
+    assert_eq!(ring.allocate(1025, 1), None);
+}
+
+#[test]
+fn full_ring_waits_for_the_oldest_batch() {
+    let mut ring = StagingRing::new(1024);
+    assert_eq!(ring.allocate(512, 1), Some(0));
+    ring.close_batch();
+    assert_eq!(ring.allocate(512, 1), Some(512));
+    ring.close_batch();
+    assert_eq!(ring.used(), 1024);
+    assert_eq!(ring.allocate(1, 1), None);
+
+    ring.release_batch();
+    assert_eq!(ring.pending_batches(), 1);
+    assert_eq!(ring.allocate(256, 1), Some(0));
+    assert_eq!(ring.allocate(256, 1), Some(256));
+    assert_eq!(ring.allocate(1, 1), None);
+}
+
+#[test]
+fn wraps_to_the_front_when_the_tail_is_too_short() {
+    let mut ring = StagingRing::new(1024);
+    ring.allocate(400, 1).unwrap();
+    ring.close_batch();
+    ring.allocate(400, 1).unwrap();
+    ring.close_batch();
+    ring.release_batch();
+
+    //  800..1024 には収まらないので先頭に戻り、末尾の隙間も使用中として数える
+    assert_eq!(ring.allocate(300, 1), Some(0));
+    assert_eq!(ring.used(), 400 + 224 + 300);
+    ring.close_batch();
+    //  400..800 のバッチがまだ残っている
+    assert_eq!(ring.allocate(200, 1), None);
+    assert_eq!(ring.allocate(100, 1), Some(300));
+
+    ring.release_batch();
+    ring.release_batch();
+    //  最後の 100 バイトのバッチは閉じていないので残る
+    assert_eq!(ring.used(), 100);
+    assert_eq!(ring.allocate(600, 1), Some(400));
+}
+
+#[test]
+fn empty_ring_restarts_from_the_front() {
+    let mut ring = StagingRing::new(1024);
+    ring.allocate(1000, 1).unwrap();
+    ring.close_batch();
+    ring.release_batch();
+    assert!(ring.is_empty());
+    assert_eq!(ring.allocate(1024, 1), Some(0));
+}
+
+#[test]
+fn empty_batches_keep_submissions_in_step() {
+    let mut ring = StagingRing::new(1024);
+    ring.allocate(100, 1).unwrap();
+    ring.close_batch();
+    ring.close_batch();
+    assert_eq!(ring.pending_batches(), 2);
+    ring.release_batch();
+    assert!(ring.is_empty());
+    ring.release_batch();
+    assert_eq!(ring.pending_batches(), 0);
+}
+
+#[test]
+fn ranges_in_use_never_overlap() {
+    let size = 4096;
+    let mut ring = StagingRing::new(size);
+    //  閉じたバッチの範囲を古い順に
+    let mut batches: std::collections::VecDeque<Vec<(u64, u64)>> = Default::default();
+    let mut open = Vec::new();
+    let mut rng = Lcg::new(98765);
+    for i in 0..2000 {
+        let len = 1 + rng.below(700);
+        let alignment = 1 << rng.below(5);
+        match ring.allocate(len, alignment) {
+            Some(offset) => {
+                assert_eq!(offset % alignment, 0);
+                assert!(offset + len <= size);
+                let in_use = batches.iter().flatten().chain(open.iter());
+                for &(start, end) in in_use {
+                    assert!(
+                        offset + len <= start || end <= offset,
+                        "{}..{} が {}..{} と重なっている",
+                        offset,
+                        offset + len,
+                        start,
+                        end
+                    );
+                }
+                open.push((offset, offset + len));
+            }
+            None => {
+                //  空きが無い時は古いバッチの完了を待つ
+                if batches.is_empty() {
+                    ring.close_batch();
+                    batches.push_back(std::mem::replace(&mut open, Vec::new()));
+                }
+                ring.release_batch();
+                batches.pop_front();
+            }
+        }
+        if i % 5 == 0 {
+            ring.close_batch();
+            batches.push_back(std::mem::replace(&mut open, Vec::new()));
+        }
+    }
+    while !batches.is_empty() {
+        ring.release_batch();
+        batches.pop_front();
+    }
+    if open.is_empty() {
+        assert!(ring.is_empty());
+    }
+}
+
+#[test]
+fn texel_sizes_of_uncompressed_formats() {
+    assert_eq!(texel_size(ash::vk::Format::R8_UNORM), Some(1));
+    assert_eq!(texel_size(ash::vk::Format::R5G6B5_UNORM_PACK16), Some(2));
+    assert_eq!(texel_size(ash::vk::Format::B8G8R8_SRGB), Some(3));
+    assert_eq!(texel_size(ash::vk::Format::R8G8B8A8_SRGB), Some(4));
+    assert_eq!(
+        texel_size(ash::vk::Format::A2B10G10R10_UINT_PACK32),
+        Some(4)
+    );
+    assert_eq!(texel_size(ash::vk::Format::R16G16B16_SFLOAT), Some(6));
+    assert_eq!(texel_size(ash::vk::Format::R32G32B32_SFLOAT), Some(12));
+    assert_eq!(texel_size(ash::vk::Format::R32G32B32A32_SFLOAT), Some(16));
+    assert_eq!(texel_size(ash::vk::Format::R64G64B64A64_SFLOAT), Some(32));
+    assert_eq!(texel_size(ash::vk::Format::E5B9G9R9_UFLOAT_PACK32), Some(4));
+    assert_eq!(texel_size(ash::vk::Format::D16_UNORM), Some(2));
+    assert_eq!(texel_size(ash::vk::Format::S8_UINT), Some(1));
+    assert_eq!(texel_size(ash::vk::Format::UNDEFINED), None);
+    assert_eq!(texel_size(ash::vk::Format::D24_UNORM_S8_UINT), None);
+    assert_eq!(texel_size(ash::vk::Format::BC1_RGB_UNORM_BLOCK), None);
+}
+
+#[test]
+fn image_copies_align_to_texels_and_four_bytes() {
+    //  3 バイトや 12 バイトのテクセルは 16 の倍数に揃えても足りない
+    assert_eq!(image_copy_alignment(3, 1), 12);
+    assert_eq!(image_copy_alignment(12, 16), 48);
+    assert_eq!(image_copy_alignment(6, 1), 12);
+    assert_eq!(image_copy_alignment(1, 1), 4);
+    assert_eq!(image_copy_alignment(4, 64), 64);
+    assert_eq!(image_copy_alignment(32, 0), 32);
+    for texel in [1, 2, 3, 4, 6, 8, 12, 16, 24, 32].iter() {
+        for optimal in [1, 4, 16, 64, 256].iter() {
+            let alignment = image_copy_alignment(*texel, *optimal);
+            assert_eq!(alignment % 4, 0);
+            assert_eq!(alignment % texel, 0);
+            assert_eq!(alignment % optimal, 0);
+        }
+    }
+}
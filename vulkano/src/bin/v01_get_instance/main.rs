@@ -1,8 +1,9 @@
 //  01_get_instance
 use std::borrow::Cow;
 use vk_sample_common::config;
+use vk_sample_common::error::{Error, ErrorContext};
 
-fn main() {
+fn main() -> Result<(), Error> {
     let config = config::Configs::new("get_instance");
     let app_info = vulkano::instance::ApplicationInfo {
         application_name: Some(Cow::from(config.prog_name.as_str())),
@@ -22,11 +23,9 @@ fn main() {
     vulkano::instance::Instance::new(
         Some(&app_info),
         &vulkano::instance::InstanceExtensions::none(),
-        if config.validation {
-            vec!["VK_LAYER_LUNARG_standard_validation"]
-        } else {
-            Vec::<&str>::new()
-        },
+        vk_sample_common::debug::vulkano_validation_layers(config.validation),
     )
-    .unwrap();
+    .context("インスタンスを作成できない")?;
+
+    Ok(())
 }
@@ -1,8 +1,9 @@
 //  02_list_devices
 use std::borrow::Cow;
 use vk_sample_common::config;
+use vk_sample_common::error::{Error, ErrorContext};
 
-fn main() {
+fn main() -> Result<(), Error> {
     let config = config::Configs::new("list_devices");
     let app_info = vulkano::instance::ApplicationInfo {
         application_name: Some(Cow::from(config.prog_name.as_str())),
@@ -22,18 +23,13 @@ fn main() {
     let instance = vulkano::instance::Instance::new(
         Some(&app_info),
         &vulkano::instance::InstanceExtensions::none(),
-        if config.validation {
-            vec!["VK_LAYER_LUNARG_standard_validation"]
-        } else {
-            Vec::<&str>::new()
-        },
+        vk_sample_common::debug::vulkano_validation_layers(config.validation),
     )
-    .unwrap();
+    .context("インスタンスを作成できない")?;
 
     let devices = vulkano::instance::PhysicalDevice::enumerate(&instance);
     if devices.len() == 0 {
-        eprintln!("利用可能なデバイスがない");
-        return;
+        return Err(Error::from("利用可能なデバイスがない"));
     }
     for device in devices {
         println!(
@@ -62,7 +58,9 @@ fn main() {
         let avail_dext = vulkano::device::RawDeviceExtensions::supported_by_device(device);
         println!("  利用可能な拡張");
         for ext in avail_dext.iter() {
-            println!("    {}", ext.to_str().unwrap());
+            println!("    {}", ext.to_string_lossy());
         }
     }
+
+    Ok(())
 }
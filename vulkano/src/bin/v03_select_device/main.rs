@@ -1,10 +1,11 @@
 //  03_select_device
 use std::borrow::Cow;
 use vk_sample_common::config;
+use vk_sample_common::error::{Error, ErrorContext};
 use vulkano::VulkanObject;
 
-fn main() {
-    let glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
+fn main() -> Result<(), Error> {
+    let glfw = glfw::init(glfw::FAIL_ON_ERRORS).context("GLFW を初期化できない")?;
 
     let config = config::Configs::new("select_device");
     let app_info = vulkano::instance::ApplicationInfo {
@@ -23,26 +24,25 @@ fn main() {
     };
 
     let mut required_ext = std::collections::HashSet::<std::ffi::CString>::new();
-    for x in glfw.get_required_instance_extensions().unwrap() {
-        required_ext.insert(std::ffi::CString::new(x).unwrap());
+    for x in glfw
+        .get_required_instance_extensions()
+        .context("GLFW が Vulkan を使えない")?
+    {
+        required_ext
+            .insert(std::ffi::CString::new(x).context("インスタンスの拡張の名前が正しくない")?);
     }
     let ext = vulkano::instance::RawInstanceExtensions::new(required_ext);
 
     let instance = vulkano::instance::Instance::new(
         Some(&app_info),
         ext,
-        if config.validation {
-            vec!["VK_LAYER_LUNARG_standard_validation"]
-        } else {
-            Vec::<&str>::new()
-        },
+        vk_sample_common::debug::vulkano_validation_layers(config.validation),
     )
-    .unwrap();
+    .context("インスタンスを作成できない")?;
 
     let devices = vulkano::instance::PhysicalDevice::enumerate(&instance);
     if devices.len() == 0 {
-        eprintln!("利用可能なデバイスがない");
-        return;
+        return Err(Error::from("利用可能なデバイスがない"));
     }
 
     let validated_devices: Vec<vulkano::instance::PhysicalDevice> = devices
@@ -60,12 +60,13 @@ fn main() {
         })
         .collect();
     if validated_devices.len() == 0 {
-        eprintln!("必要な拡張とレイヤーを備えたデバイスがない");
-        return;
+        return Err(Error::from("必要な拡張とレイヤーを備えたデバイスがない"));
     }
 
     println!("利用可能なデバイス");
     for i in 0..validated_devices.len() {
         println!("{}: {}", i, validated_devices[i].name())
     }
+
+    Ok(())
 }
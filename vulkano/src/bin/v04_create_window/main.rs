@@ -2,11 +2,12 @@
 
 use std::borrow::Cow;
 use vk_sample_common::config;
+use vk_sample_common::error::{Error, ErrorContext};
 use vulkano::VulkanObject;
 
 #[allow(unused_variables)]
-fn main() {
-    let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
+fn main() -> Result<(), Error> {
+    let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).context("GLFW を初期化できない")?;
 
     let config = config::Configs::new("create_window");
     let app_info = vulkano::instance::ApplicationInfo {
@@ -25,26 +26,25 @@ fn main() {
     };
 
     let mut required_ext = std::collections::HashSet::<std::ffi::CString>::new();
-    for x in glfw.get_required_instance_extensions().unwrap() {
-        required_ext.insert(std::ffi::CString::new(x).unwrap());
+    for x in glfw
+        .get_required_instance_extensions()
+        .context("GLFW が Vulkan を使えない")?
+    {
+        required_ext
+            .insert(std::ffi::CString::new(x).context("インスタンスの拡張の名前が正しくない")?);
     }
     let ext = vulkano::instance::RawInstanceExtensions::new(required_ext);
 
     let instance = vulkano::instance::Instance::new(
         Some(&app_info),
         ext,
-        if config.validation {
-            vec!["VK_LAYER_LUNARG_standard_validation"]
-        } else {
-            Vec::<&str>::new()
-        },
+        vk_sample_common::debug::vulkano_validation_layers(config.validation),
     )
-    .unwrap();
+    .context("インスタンスを作成できない")?;
 
     let devices = vulkano::instance::PhysicalDevice::enumerate(&instance);
     if devices.len() == 0 {
-        eprintln!("利用可能なデバイスがない");
-        return;
+        return Err(Error::from("利用可能なデバイスがない"));
     }
 
     let validated_devices: Vec<vulkano::instance::PhysicalDevice> = devices
@@ -62,8 +62,7 @@ fn main() {
         })
         .collect();
     if validated_devices.len() == 0 {
-        eprintln!("必要な拡張とレイヤーを備えたデバイスがない");
-        return;
+        return Err(Error::from("必要な拡張とレイヤーを備えたデバイスがない"));
     }
 
     println!("利用可能なデバイス");
@@ -88,19 +87,23 @@ fn main() {
                 },
             )
         })
-        .expect("ウィンドウを作成できない");
+        .context("ウィンドウを作成できない")?;
 
     let mut raw_surface: vk_sys::SurfaceKHR = 0;
-    if window.create_window_surface(
+    let result = window.create_window_surface(
         instance.internal_object(),
         std::ptr::null(),
         &mut raw_surface,
-    ) != 0
-    {
-        eprintln!("サーフェスを作成できない");
-        return;
+    );
+    if result != 0 {
+        return Err(Error::Message(format!(
+            "サーフェスを作成できない: VkResult {}",
+            result
+        )));
     }
 
     let surface =
         unsafe { vulkano::swapchain::Surface::from_raw_surface(instance, raw_surface, window) };
+
+    Ok(())
 }
@@ -1,11 +1,12 @@
 //  08_create_render_pass
 use std::borrow::Cow;
 use vk_sample_common::config;
+use vk_sample_common::error::{Error, ErrorContext};
 use vulkano::VulkanObject;
 
 #[allow(unused_variables)]
-fn main() {
-    let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
+fn main() -> Result<(), Error> {
+    let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).context("GLFW を初期化できない")?;
 
     let config = config::Configs::new("create_render_pass");
     let app_info = vulkano::instance::ApplicationInfo {
@@ -24,26 +25,25 @@ fn main() {
     };
 
     let mut required_ext = std::collections::HashSet::<std::ffi::CString>::new();
-    for x in glfw.get_required_instance_extensions().unwrap() {
-        required_ext.insert(std::ffi::CString::new(x).unwrap());
+    for x in glfw
+        .get_required_instance_extensions()
+        .context("GLFW が Vulkan を使えない")?
+    {
+        required_ext
+            .insert(std::ffi::CString::new(x).context("インスタンスの拡張の名前が正しくない")?);
     }
     let ext = vulkano::instance::RawInstanceExtensions::new(required_ext);
 
     let instance = vulkano::instance::Instance::new(
         Some(&app_info),
         ext,
-        if config.validation {
-            vec!["VK_LAYER_LUNARG_standard_validation"]
-        } else {
-            Vec::<&str>::new()
-        },
+        vk_sample_common::debug::vulkano_validation_layers(config.validation),
     )
-    .unwrap();
+    .context("インスタンスを作成できない")?;
 
     let devices = vulkano::instance::PhysicalDevice::enumerate(&instance);
     if devices.len() == 0 {
-        eprintln!("利用可能なデバイスがない");
-        return;
+        return Err(Error::from("利用可能なデバイスがない"));
     }
 
     let dext = vulkano::device::DeviceExtensions {
@@ -69,8 +69,7 @@ fn main() {
         })
         .collect();
     if validated_devices.len() == 0 {
-        eprintln!("必要な拡張とレイヤーを備えたデバイスがない");
-        return;
+        return Err(Error::from("必要な拡張とレイヤーを備えたデバイスがない"));
     }
 
     println!("利用可能なデバイス");
@@ -95,32 +94,44 @@ fn main() {
                 },
             )
         })
-        .expect("ウィンドウを作成できない");
+        .context("ウィンドウを作成できない")?;
 
     let mut raw_surface: vk_sys::SurfaceKHR = 0;
-    if window.create_window_surface(
+    let result = window.create_window_surface(
         instance.internal_object(),
         std::ptr::null(),
         &mut raw_surface,
-    ) != 0
-    {
-        eprintln!("サーフェスを作成できない");
-        return;
+    );
+    if result != 0 {
+        return Err(Error::Message(format!(
+            "サーフェスを作成できない: VkResult {}",
+            result
+        )));
     }
 
     let surface = std::sync::Arc::new(unsafe {
         vulkano::swapchain::Surface::from_raw_surface(instance.clone(), raw_surface, window)
     });
 
-    if config.device_index as usize >= validated_devices.len() {
-        eprintln!("{} 番目のデバイスは存在しない", config.device_index);
-        return;
+    let device_index = config.device_index.unwrap_or(0) as usize;
+    if device_index >= validated_devices.len() {
+        return Err(Error::Message(format!(
+            "{} 番目のデバイスは存在しない",
+            device_index
+        )));
     }
 
-    let physical_device = validated_devices[config.device_index as usize];
-    let mut queue_props = physical_device
-        .queue_families()
-        .filter(|family| surface.is_supported(*family).unwrap());
+    let physical_device = validated_devices[device_index];
+    let mut queue_props = vec![];
+    for family in physical_device.queue_families() {
+        if surface
+            .is_supported(family)
+            .context("キューファミリーが表示に対応しているか調べられない")?
+        {
+            queue_props.push(family);
+        }
+    }
+    let mut queue_props = queue_props.into_iter();
 
     let graphics_queue = queue_props.find(|queue_prop| queue_prop.supports_graphics());
 
@@ -131,8 +142,7 @@ fn main() {
     };
 
     if graphics_queue.is_none() || present_queue.is_none() {
-        eprintln!("必要なキューが備わっていない");
-        return;
+        return Err(Error::from("必要なキューが備わっていない"));
     }
 
     let graphics_queue = graphics_queue.unwrap();
@@ -149,15 +159,14 @@ fn main() {
             vec![(graphics_queue, 0.0), (present_queue, 0.0)]
         },
     )
-    .unwrap();
+    .context("デバイスを作成できない")?;
 
     let formats = surface
         .capabilities(physical_device)
-        .unwrap()
+        .context("サーフェスのフォーマットを取得できない")?
         .supported_formats;
     if formats.len() == 0 {
-        eprintln!("利用可能なピクセルフォーマットが無い");
-        return;
+        return Err(Error::from("利用可能なピクセルフォーマットが無い"));
     }
     let format = match formats
         .iter()
@@ -168,7 +177,7 @@ fn main() {
             .iter()
             .find(|f| f.0 == vulkano::format::Format::R8G8B8A8Unorm),
     }
-    .expect("利用可能なピクセルフォーマットが無い");
+    .context("利用可能なピクセルフォーマットが無い")?;
 
     let render_pass = vulkano::single_pass_renderpass!(
         device.clone(),
@@ -191,5 +200,7 @@ fn main() {
                 depth_stencil: {depth_stencil}
             }
     )
-    .unwrap();
+    .context("レンダーパスを作成できない")?;
+
+    Ok(())
 }